@@ -1,40 +1,140 @@
 use core::fmt;
 use core::result::Result::Ok;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::time::Duration;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use bitcoin::absolute::LockTime;
 use bitcoin::blockdata::opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_IF};
 use bitcoin::blockdata::opcodes::OP_FALSE;
 use bitcoin::blockdata::script;
-use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::hashes::Hash;
 use bitcoin::key::{TapTweak, TweakedPublicKey, UntweakedKeypair};
+use bitcoin::policy::MAX_STANDARD_TX_WEIGHT;
 use bitcoin::script::PushBytesBuf;
 use bitcoin::secp256k1::constants::SCHNORR_SIGNATURE_SIZE;
 use bitcoin::secp256k1::schnorr::Signature;
 use bitcoin::secp256k1::{self, Secp256k1, SecretKey, XOnlyPublicKey};
-use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
 use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder};
 use bitcoin::{
-    Address, Amount, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
-    Witness,
+    Address, Amount, Network, OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Txid, Witness,
 };
+use rand::{CryptoRng, Rng, RngCore};
 use tracing::{instrument, trace, warn};
 
-use crate::helpers::{BODY_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG, SIGNATURE_TAG};
-use crate::spec::utxo::UTXO;
-use crate::REVEAL_OUTPUT_AMOUNT;
+use crate::helpers::compression::CompressionScheme;
+use crate::helpers::encryption::EncryptionScheme;
+use crate::helpers::parsers::signed_body_hash;
+use crate::helpers::{
+    BODY_TAG, COMPRESSION_TAG, ENCRYPTION_TAG, ENVELOPE_VERSION, PUBLICKEY_TAG, RANDOM_TAG,
+    ROLLUP_NAME_TAG, SIGNATURE_TAG, VERSION_TAG,
+};
+use crate::spec::utxo::{ScriptType, UTXO};
+use crate::{DUST_THRESHOLD, REVEAL_OUTPUT_AMOUNT};
+
+/// Fee rate used by the transaction builders, stored in sat/vB.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct FeeRate(f64);
+
+impl FeeRate {
+    /// Default `-minrelaytxfee` of bitcoind on every network, 1 sat/vB.
+    /// Nodes of a network started with another value are configured through
+    /// [`BitcoinDaConfig::min_relay_fee_rate`].
+    pub const MIN_RELAY: Self = Self(1.0);
+
+    /// Fails if the rate is not finite or negative.
+    pub fn from_sat_per_vb(sat_per_vb: f64) -> Result<Self, InvalidFeeRate> {
+        if !sat_per_vb.is_finite() || sat_per_vb < 0.0 {
+            return Err(InvalidFeeRate(sat_per_vb));
+        }
+        Ok(Self(sat_per_vb))
+    }
+
+    pub fn from_sat_per_kvb(sat_per_kvb: f64) -> Result<Self, InvalidFeeRate> {
+        Self::from_sat_per_vb(sat_per_kvb / 1000.0)
+    }
+
+    /// The rate is rounded to whole satoshis per kvB, like `estimatesmartfee` amounts are.
+    pub fn from_btc_per_kvb(btc_per_kvb: f64) -> Result<Self, InvalidFeeRate> {
+        Self::from_sat_per_kvb((btc_per_kvb * 100_000_000.0).round())
+    }
+
+    pub fn as_sat_per_vb(&self) -> f64 {
+        self.0
+    }
+
+    /// Fee in satoshis for a transaction of the given virtual size, rounded up.
+    pub fn fee_for_vsize(&self, vsize: usize) -> u64 {
+        ((vsize as f64) * self.0).ceil() as u64
+    }
+}
+
+/// Error returned when a [`FeeRate`] is created from a value that is not a fee rate.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("fee rate of {0} sat/vB is not a finite, non-negative number")]
+pub struct InvalidFeeRate(pub f64);
+
+/// Fee rate a signed transaction spending `input_value` sats actually pays, for reconciling
+/// broadcast transactions with the fee rate they were built for.
+/// Returns `None` if the outputs spend more than `input_value`.
+pub fn actual_fee_rate(tx: &Transaction, input_value: u64) -> Option<FeeRate> {
+    let output_value: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    let fee = input_value.checked_sub(output_value)?;
+    FeeRate::from_sat_per_vb(fee as f64 / tx.vsize() as f64).ok()
+}
+
+// Fails if transactions paying `fee_rate` would not be relayed
+fn check_min_relay_fee_rate(
+    fee_rate: FeeRate,
+    min_relay_fee_rate: FeeRate,
+) -> Result<(), anyhow::Error> {
+    if fee_rate < min_relay_fee_rate {
+        return Err(anyhow!(
+            "fee rate {} sat/vB is below the minimum relay fee rate of {} sat/vB",
+            fee_rate.as_sat_per_vb(),
+            min_relay_fee_rate.as_sat_per_vb()
+        ));
+    }
+    Ok(())
+}
+
+/// Error returned by [`sign_blob_with_private_key`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SignError {
+    /// The digest of the blob is not a valid message to sign.
+    #[error("invalid message to sign: {0}")]
+    InvalidMessage(#[from] secp256k1::Error),
+}
 
 // Signs a message with a private key
 pub fn sign_blob_with_private_key(
     blob: &[u8],
     private_key: &SecretKey,
-) -> Result<(Vec<u8>, Vec<u8>), ()> {
-    let message = sha256d::Hash::hash(blob).to_byte_array();
+) -> Result<(Vec<u8>, Vec<u8>), SignError> {
+    sign_body_with_private_key(
+        blob,
+        CompressionScheme::Brotli,
+        EncryptionScheme::None,
+        private_key,
+    )
+}
+
+/// Signs an envelope body declared with the given schemes, see [`signed_body_hash`].
+/// Returns the compact signature and the compressed public key.
+pub fn sign_body_with_private_key(
+    body: &[u8],
+    compression: CompressionScheme,
+    encryption: EncryptionScheme,
+    private_key: &SecretKey,
+) -> Result<(Vec<u8>, Vec<u8>), SignError> {
+    let message = signed_body_hash(body, compression, encryption);
     let secp = Secp256k1::new();
     let public_key = secp256k1::PublicKey::from_secret_key(&secp, private_key);
-    let msg = secp256k1::Message::from_digest_slice(&message).unwrap();
+    let msg = secp256k1::Message::from_digest_slice(&message)?;
     let sig = secp.sign_ecdsa(&msg, private_key);
     Ok((
         sig.serialize_compact().to_vec(),
@@ -48,6 +148,90 @@ fn get_size(
     script: Option<&ScriptBuf>,
     control_block: Option<&ControlBlock>,
 ) -> usize {
+    try_get_size(inputs, outputs, script, control_block).expect("Cannot estimate tx size")
+}
+
+/// Closed form of the size `get_size` estimates, for transactions with P2TR outputs only.
+/// Every input carries a schnorr signature under `sighash_type`, which is a byte longer for
+/// any type but [`TapSighashType::Default`], and if `has_tapscript_reveal` is set the single
+/// input also carries a `script_len` bytes reveal script and a `control_block_len` bytes
+/// control block. Agrees with `get_size` within 1 vB without building the transaction.
+pub fn estimate_vsize(
+    num_inputs: usize,
+    num_outputs: usize,
+    has_tapscript_reveal: bool,
+    script_len: usize,
+    control_block_len: usize,
+    sighash_type: TapSighashType,
+) -> usize {
+    // outpoint, empty script sig and sequence
+    const INPUT_SIZE: usize = 36 + 1 + 4;
+    // value, script length and a P2TR script pubkey
+    const P2TR_OUTPUT_SIZE: usize = 8 + 1 + 34;
+    // number of witness elements and the length prefixed schnorr signature
+    let key_spend_witness_size = 1 + 1 + schnorr_signature_len(sighash_type);
+
+    // version, input and output counts and locktime
+    let base_size = 4
+        + varint_len(num_inputs)
+        + num_inputs * INPUT_SIZE
+        + varint_len(num_outputs)
+        + num_outputs * P2TR_OUTPUT_SIZE
+        + 4;
+
+    // segwit marker and flag
+    let mut witness_size = 2 + num_inputs * key_spend_witness_size;
+    if has_tapscript_reveal && num_inputs == 1 {
+        witness_size +=
+            varint_len(script_len) + script_len + varint_len(control_block_len) + control_block_len;
+    }
+
+    (base_size * 4 + witness_size + 3) / 4
+}
+
+// Length of a schnorr signature in a witness, the sighash byte is omitted for the default type
+fn schnorr_signature_len(sighash_type: TapSighashType) -> usize {
+    match sighash_type {
+        TapSighashType::Default => SCHNORR_SIGNATURE_SIZE,
+        _ => SCHNORR_SIGNATURE_SIZE + 1,
+    }
+}
+
+// Length of the compact size encoding of `n`
+fn varint_len(n: usize) -> usize {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+// Estimates the virtual size of the signed transaction,
+// failing if the transaction would be above the standard weight limit
+fn try_get_size(
+    inputs: &[TxIn],
+    outputs: &[TxOut],
+    script: Option<&ScriptBuf>,
+    control_block: Option<&ControlBlock>,
+) -> Result<usize, anyhow::Error> {
+    try_get_size_with_sighash(
+        inputs,
+        outputs,
+        script,
+        control_block,
+        TapSighashType::Default,
+    )
+}
+
+// Same as `try_get_size`, with the inputs signed under `sighash_type`
+fn try_get_size_with_sighash(
+    inputs: &[TxIn],
+    outputs: &[TxOut],
+    script: Option<&ScriptBuf>,
+    control_block: Option<&ControlBlock>,
+    sighash_type: TapSighashType,
+) -> Result<usize, anyhow::Error> {
     let mut tx = Transaction {
         input: inputs.to_owned(),
         output: outputs.to_owned(),
@@ -55,28 +239,51 @@ fn get_size(
         version: bitcoin::transaction::Version(2),
     };
 
-    for i in 0..tx.input.len() {
-        tx.input[i].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
-        );
+    let signature = bitcoin::taproot::Signature {
+        signature: Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])?,
+        sighash_type,
+    }
+    .to_vec();
+    for input in tx.input.iter_mut() {
+        input.witness.push(&signature);
+    }
+
+    if let ([input], Some(script), Some(control_block)) =
+        (tx.input.as_mut_slice(), script, control_block)
+    {
+        input.witness.push(script);
+        input.witness.push(control_block.serialize());
     }
 
-    #[allow(clippy::unnecessary_unwrap)]
-    if tx.input.len() == 1 && script.is_some() && control_block.is_some() {
-        tx.input[0].witness.push(script.unwrap());
-        tx.input[0].witness.push(control_block.unwrap().serialize());
+    let weight = tx.weight();
+    if weight.to_wu() > MAX_STANDARD_TX_WEIGHT as u64 {
+        return Err(anyhow!(
+            "transaction weight {} is above the standard limit {}, split the data into smaller chunks",
+            weight,
+            MAX_STANDARD_TX_WEIGHT
+        ));
     }
 
-    tx.vsize()
+    Ok(tx.vsize())
+}
+
+/// Error returned when the available UTXOs cannot cover a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CoinSelectionError {
+    /// The UTXOs (including a required one, if any) sum up to `available`
+    /// sats, which is less than the `required` amount.
+    #[error("not enough UTXOs")]
+    InsufficientFunds { required: u64, available: u64 },
 }
 
+// `allowed_script_types` restricts the UTXOs chosen next to the required one, if given
 fn choose_utxos(
     required_utxo: Option<UTXO>,
     utxos: &[UTXO],
     mut amount: u64,
-) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
+    allowed_script_types: Option<&[ScriptType]>,
+) -> Result<(Vec<UTXO>, u64), CoinSelectionError> {
+    let required = amount;
     let mut chosen_utxos = vec![];
     let mut sum = 0;
 
@@ -92,7 +299,22 @@ fn choose_utxos(
         amount -= sum;
     }
 
-    let mut bigger_utxos: Vec<&UTXO> = utxos.iter().filter(|utxo| utxo.amount >= amount).collect();
+    let utxos: Vec<&UTXO> = utxos
+        .iter()
+        .filter(|utxo| {
+            allowed_script_types.map_or(true, |allowed_script_types| {
+                utxo.script_type().map_or(false, |script_type| {
+                    allowed_script_types.contains(&script_type)
+                })
+            })
+        })
+        .collect();
+
+    let mut bigger_utxos: Vec<&UTXO> = utxos
+        .iter()
+        .copied()
+        .filter(|utxo| utxo.amount >= amount)
+        .collect();
 
     if !bigger_utxos.is_empty() {
         // sort vec by amount (small first)
@@ -106,8 +328,11 @@ fn choose_utxos(
 
         Ok((chosen_utxos, sum))
     } else {
-        let mut smaller_utxos: Vec<&UTXO> =
-            utxos.iter().filter(|utxo| utxo.amount < amount).collect();
+        let mut smaller_utxos: Vec<&UTXO> = utxos
+            .iter()
+            .copied()
+            .filter(|utxo| utxo.amount < amount)
+            .collect();
 
         // sort vec by amount (large first)
         smaller_utxos.sort_by(|a, b| b.amount.cmp(&a.amount));
@@ -122,51 +347,174 @@ fn choose_utxos(
         }
 
         if sum < amount {
-            return Err(anyhow!("not enough UTXOs"));
+            return Err(CoinSelectionError::InsufficientFunds {
+                required,
+                available: sum,
+            });
         }
 
         Ok((chosen_utxos, sum))
     }
 }
 
-#[instrument(level = "trace", skip(utxos), err)]
-fn build_commit_transaction(
+/// Chooses UTXOs worth at least `amount` from the set returned by `utxos_provider`.
+/// UTXOs may be locked by concurrent builds in the meantime, so on insufficient funds the set
+/// is fetched again and selection is retried up to `retries` times, waiting `backoff` before
+/// the first retry and twice as long before each next one.
+/// Returns the last error once the retries are exhausted.
+pub fn select_utxos_with_retry(
+    utxos_provider: impl Fn() -> Vec<UTXO>,
+    amount: u64,
+    retries: u32,
+    backoff: Duration,
+) -> Result<(Vec<UTXO>, u64), CoinSelectionError> {
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        match choose_utxos(None, &utxos_provider(), amount, None) {
+            Err(e) if attempt < retries => {
+                trace!(attempt, error = %e, "Retrying UTXO selection");
+                std::thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "trace", skip(utxos, rng), err)]
+fn build_commit_transaction<R: RngCore>(
     prev_tx: Option<TxWithId>, // reuse outputs to add commit tx order
-    mut utxos: Vec<UTXO>,
+    utxos: Vec<UTXO>,
     recipient: Address,
     change_address: Address,
     output_value: u64,
-    fee_rate: f64,
+    dust_threshold: u64,
+    fee_rate: FeeRate,
+    randomize_outputs: bool,
+    locktime: Option<LockTime>,
+    allowed_script_types: Option<&[ScriptType]>,
+    rng: &mut R,
 ) -> Result<Transaction, anyhow::Error> {
+    build_commit_transaction_with_stats(
+        prev_tx,
+        utxos,
+        recipient,
+        change_address,
+        output_value,
+        dust_threshold,
+        fee_rate,
+        randomize_outputs,
+        locktime,
+        allowed_script_types,
+        rng,
+    )
+    .map(|(tx, stats)| {
+        trace!(
+            iterations = stats.iterations,
+            final_vsize = stats.final_vsize,
+            "Built commit transaction"
+        );
+        tx
+    })
+}
+
+/// How [`build_commit_transaction_with_stats`] settled on a commit transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildStats {
+    /// Number of size estimation iterations after the first guess
+    pub iterations: u32,
+    /// Virtual size the fee was computed for
+    pub final_vsize: usize,
+}
+
+// Value of the change output a transaction spending `sum` from `inputs` can afford next to
+// `recipient_output` while paying `fee_rate` for its size with the change output,
+// or None if that change would be dust
+fn affordable_change(
+    inputs: &[TxIn],
+    recipient_output: &TxOut,
+    change_address: &Address,
+    sum: u64,
+    fee_rate: FeeRate,
+    dust_threshold: u64,
+) -> Result<Option<u64>, anyhow::Error> {
+    let outputs = [
+        recipient_output.clone(),
+        TxOut {
+            value: Amount::from_sat(dust_threshold),
+            script_pubkey: change_address.script_pubkey(),
+        },
+    ];
+    let fee = fee_rate.fee_for_vsize(try_get_size(inputs, &outputs, None, None)?);
+    Ok(sum
+        .checked_sub(recipient_output.value.to_sat() + fee)
+        .filter(|change| *change >= dust_threshold))
+}
+
+/// Builds an unsigned commit transaction paying `output_value` to `recipient` at `fee_rate`,
+/// spending the single output of `prev_tx` if given, and reports how long it took to settle
+/// on the transaction, for diagnostics.
+/// Change of at least `dust_threshold` goes to `change_address`, at a random position if
+/// `randomize_outputs` is set. Only UTXOs of `allowed_script_types` are chosen, if given.
+#[allow(clippy::too_many_arguments)]
+pub fn build_commit_transaction_with_stats<R: RngCore>(
+    prev_tx: Option<TxWithId>,
+    mut utxos: Vec<UTXO>,
+    recipient: Address,
+    change_address: Address,
+    output_value: u64,
+    dust_threshold: u64,
+    fee_rate: FeeRate,
+    randomize_outputs: bool,
+    locktime: Option<LockTime>,
+    allowed_script_types: Option<&[ScriptType]>,
+    rng: &mut R,
+) -> Result<(Transaction, BuildStats), anyhow::Error> {
+    let dummy_input = TxIn {
+        previous_output: OutPoint {
+            txid: Txid::from_byte_array([0; 32]),
+            vout: 0,
+        },
+        script_sig: script::Builder::new().into_script(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+    };
+    let recipient_output = TxOut {
+        script_pubkey: recipient.clone().script_pubkey(),
+        value: Amount::from_sat(output_value),
+    };
+
     // get single input single output transaction size
     let size = get_size(
-        &[TxIn {
-            previous_output: OutPoint {
-                txid: Txid::from_byte_array([0; 32]),
-                vout: 0,
-            },
-            script_sig: script::Builder::new().into_script(),
-            witness: Witness::new(),
-            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        }],
-        &[TxOut {
-            script_pubkey: recipient.clone().script_pubkey(),
-            value: Amount::from_sat(output_value),
-        }],
+        &[dummy_input.clone()],
+        &[recipient_output.clone()],
         None,
         None,
     );
 
+    // outputs spent by the previous transaction, the commit tx depends on it and can't spend them
+    let prev_tx_spent_outpoints: Vec<OutPoint> = prev_tx
+        .iter()
+        .flat_map(|tx| tx.tx.input.iter().map(|input| input.previous_output))
+        .collect();
+
     // fields other then tx_id, vout, script_pubkey and amount are not really important.
-    let required_utxo = prev_tx.map(|tx| UTXO {
-        tx_id: tx.id,
-        vout: 0,
-        script_pubkey: tx.tx.output[0].script_pubkey.to_hex_string(),
-        address: None,
-        amount: tx.tx.output[0].value.to_sat(),
-        confirmations: 0,
-        spendable: true,
-        solvable: true,
+    // the previous transaction is a reveal, which has a single output to continue from.
+    let required_utxo = prev_tx.map(|tx| {
+        let output = &tx.tx.output[0];
+        UTXO {
+            tx_id: tx.id,
+            vout: 0,
+            script_pubkey: output.script_pubkey.to_hex_string(),
+            address: None,
+            amount: output.value.to_sat(),
+            confirmations: 0,
+            spendable: true,
+            solvable: true,
+        }
     });
 
     if let Some(req_utxo) = &required_utxo {
@@ -176,6 +524,33 @@ fn build_commit_transaction(
         utxos.retain(|utxo| !(utxo.vout == req_utxo.vout && utxo.tx_id == req_utxo.tx_id));
     }
 
+    // when chaining, the required utxo is always spent, usually together with
+    // a funding utxo, and the leftover goes to a change output.
+    // start from that shape so the first guess is already close.
+    let size = match &required_utxo {
+        Some(req_utxo) => {
+            let mut inputs = vec![TxIn {
+                previous_output: OutPoint {
+                    txid: req_utxo.tx_id,
+                    vout: req_utxo.vout,
+                },
+                ..dummy_input.clone()
+            }];
+            if req_utxo.amount < output_value + fee_rate.fee_for_vsize(size) {
+                inputs.push(dummy_input);
+            }
+            let outputs = [
+                recipient_output,
+                TxOut {
+                    script_pubkey: change_address.script_pubkey(),
+                    value: Amount::from_sat(dust_threshold),
+                },
+            ];
+            get_size(&inputs, &outputs, None, None)
+        }
+        None => size,
+    };
+
     let mut iteration = 0;
     let mut last_size = size;
 
@@ -186,21 +561,38 @@ fn build_commit_transaction(
                 warn!("Too many iterations choosing UTXOs");
             }
         }
-        let fee = ((last_size as f64) * fee_rate).ceil() as u64;
+        let fee = fee_rate.fee_for_vsize(last_size);
 
         let input_total = output_value + fee;
 
-        let (chosen_utxos, sum) = choose_utxos(required_utxo.clone(), &utxos, input_total)?;
-        let has_change = (sum - input_total) >= REVEAL_OUTPUT_AMOUNT;
+        let (chosen_utxos, sum) = choose_utxos(
+            required_utxo.clone(),
+            &utxos,
+            input_total,
+            allowed_script_types,
+        )?;
+        if let Some(utxo) = chosen_utxos.iter().find(|utxo| {
+            prev_tx_spent_outpoints.contains(&OutPoint {
+                txid: utxo.tx_id,
+                vout: utxo.vout,
+            })
+        }) {
+            return Err(anyhow!(
+                "UTXO {}:{} is already spent by the previous transaction, spending it again would be a conflicting double spend",
+                utxo.tx_id,
+                utxo.vout
+            ));
+        }
+        let has_change = (sum - input_total) >= dust_threshold;
         let direct_return = !has_change;
 
-        let outputs = if !has_change {
+        let mut outputs = if !has_change {
             vec![TxOut {
                 value: Amount::from_sat(output_value),
                 script_pubkey: recipient.script_pubkey(),
             }]
         } else {
-            vec![
+            let mut outputs = vec![
                 TxOut {
                     value: Amount::from_sat(output_value),
                     script_pubkey: recipient.script_pubkey(),
@@ -209,7 +601,12 @@ fn build_commit_transaction(
                     value: Amount::from_sat(sum - input_total),
                     script_pubkey: change_address.script_pubkey(),
                 },
-            ]
+            ];
+            // don't make the change output identifiable by its position
+            if randomize_outputs && rng.gen::<bool>() {
+                outputs.swap(0, 1);
+            }
+            outputs
         };
 
         let inputs: Vec<_> = chosen_utxos
@@ -221,24 +618,44 @@ fn build_commit_transaction(
                 },
                 script_sig: script::Builder::new().into_script(),
                 witness: Witness::new(),
+                // signals RBF and, being below 0xFFFFFFFE, also enforces the absolute
+                // locktime. only relative locktimes are disabled by it.
                 sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
             })
             .collect();
 
         if direct_return {
+            // the fee was estimated for `last_size` and not for this transaction,
+            // don't donate the overpayment if it is enough for a change output
+            if let Some(change) = affordable_change(
+                &inputs,
+                &outputs[0],
+                &change_address,
+                sum,
+                fee_rate,
+                dust_threshold,
+            )? {
+                outputs.push(TxOut {
+                    value: Amount::from_sat(change),
+                    script_pubkey: change_address.script_pubkey(),
+                });
+                if randomize_outputs && rng.gen::<bool>() {
+                    outputs.swap(0, 1);
+                }
+            }
             break Transaction {
-                lock_time: LockTime::ZERO,
+                lock_time: locktime.unwrap_or(LockTime::ZERO),
                 version: bitcoin::transaction::Version(2),
                 input: inputs,
                 output: outputs,
             };
         }
 
-        let size = get_size(&inputs, &outputs, None, None);
+        let size = try_get_size(&inputs, &outputs, None, None)?;
 
         if size == last_size {
             break Transaction {
-                lock_time: LockTime::ZERO,
+                lock_time: locktime.unwrap_or(LockTime::ZERO),
                 version: bitcoin::transaction::Version(2),
                 input: inputs,
                 output: outputs,
@@ -249,7 +666,225 @@ fn build_commit_transaction(
         iteration += 1;
     };
 
-    Ok(tx)
+    Ok((
+        tx,
+        BuildStats {
+            iterations: iteration,
+            final_vsize: last_size,
+        },
+    ))
+}
+
+/// Builds an unsigned transaction sweeping up to `max_inputs` of the smallest
+/// `utxos` into a single output paying `destination`, net of the fee.
+pub fn build_consolidation_transaction(
+    mut utxos: Vec<UTXO>,
+    destination: Address,
+    fee_rate: FeeRate,
+    max_inputs: usize,
+) -> Result<Transaction, anyhow::Error> {
+    utxos.sort_by_key(|utxo| utxo.amount);
+    utxos.truncate(max_inputs);
+
+    if utxos.is_empty() {
+        return Err(anyhow!("no UTXOs to consolidate"));
+    }
+
+    let input_total: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+
+    let inputs: Vec<_> = utxos
+        .iter()
+        .map(|u| TxIn {
+            previous_output: OutPoint {
+                txid: u.tx_id,
+                vout: u.vout,
+            },
+            script_sig: script::Builder::new().into_script(),
+            witness: Witness::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        })
+        .collect();
+    let mut outputs = vec![TxOut {
+        value: Amount::from_sat(input_total),
+        script_pubkey: destination.script_pubkey(),
+    }];
+
+    let fee = fee_rate.fee_for_vsize(try_get_size(&inputs, &outputs, None, None)?);
+    if fee >= input_total {
+        return Err(anyhow!(
+            "consolidated value {} does not cover the fee {}",
+            input_total,
+            fee
+        ));
+    }
+    outputs[0].value = Amount::from_sat(input_total - fee);
+
+    Ok(Transaction {
+        lock_time: LockTime::ZERO,
+        version: bitcoin::transaction::Version(2),
+        input: inputs,
+        output: outputs,
+    })
+}
+
+/// OP_RETURN outputs carrying more data than this are not relayed by default.
+pub const MAX_OP_RETURN_DATA_LEN: usize = 80;
+
+/// Builds an unsigned transaction posting `data` in an OP_RETURN output, funded by `utxos`
+/// and returning the excess to `change_address` if it is above dust.
+///
+/// Unlike the commit and reveal pair of [`create_inscription_transactions`], this is a single
+/// transaction whose inputs are plain key path spends, a 64 byte signature each, so there is no
+/// reveal script, control block, commit output or second transaction to pay for.
+/// In exchange the data sits outside the witness, where a byte weighs 4 WU instead of 1 WU,
+/// there is no envelope to carry the rollup name, signature or compression scheme, and the data
+/// is capped at [`MAX_OP_RETURN_DATA_LEN`] bytes. Only worth it for small commitments.
+/// Only the taproot UTXOs among `utxos` fund it, the inputs are sized as their key path spends.
+pub fn create_keypath_commitment(
+    data: &[u8],
+    utxos: Vec<UTXO>,
+    change_address: Address,
+    dust_threshold: u64,
+    fee_rate: FeeRate,
+    network: Network,
+    min_relay_fee_rate: FeeRate,
+) -> Result<Transaction, anyhow::Error> {
+    check_min_relay_fee_rate(fee_rate, min_relay_fee_rate)?;
+    check_address_network("change address", &change_address, network)?;
+    if data.len() > MAX_OP_RETURN_DATA_LEN {
+        return Err(anyhow!(
+            "commitment of {} bytes is above the OP_RETURN limit of {} bytes",
+            data.len(),
+            MAX_OP_RETURN_DATA_LEN
+        ));
+    }
+
+    let commitment_output = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: ScriptBuf::new_op_return(PushBytesBuf::try_from(data.to_vec())?),
+    };
+    let dummy_input = TxIn {
+        previous_output: OutPoint::null(),
+        script_sig: ScriptBuf::new(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+    };
+
+    let mut last_size = try_get_size(
+        &[dummy_input.clone()],
+        &[commitment_output.clone()],
+        None,
+        None,
+    )?;
+    loop {
+        let (chosen_utxos, sum) = choose_utxos(
+            None,
+            &utxos,
+            fee_rate.fee_for_vsize(last_size),
+            Some(&[ScriptType::P2tr]),
+        )?;
+        let inputs: Vec<_> = chosen_utxos
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: OutPoint {
+                    txid: utxo.tx_id,
+                    vout: utxo.vout,
+                },
+                ..dummy_input.clone()
+            })
+            .collect();
+
+        let mut outputs = vec![commitment_output.clone()];
+        if let Some(change) = affordable_change(
+            &inputs,
+            &commitment_output,
+            &change_address,
+            sum,
+            fee_rate,
+            dust_threshold,
+        )? {
+            outputs.push(TxOut {
+                value: Amount::from_sat(change),
+                script_pubkey: change_address.script_pubkey(),
+            });
+        }
+
+        // more inputs may have been chosen than the size was estimated with
+        let size = try_get_size(&inputs, &outputs, None, None)?;
+        if sum >= fee_rate.fee_for_vsize(size) {
+            return Ok(Transaction {
+                lock_time: LockTime::ZERO,
+                version: bitcoin::transaction::Version(2),
+                input: inputs,
+                output: outputs,
+            });
+        }
+        last_size = size;
+    }
+}
+
+// Fails if `address` can't be used on `network`, `role` names the address in the error
+fn check_address_network(
+    role: &str,
+    address: &Address,
+    network: Network,
+) -> Result<(), anyhow::Error> {
+    if !address.as_unchecked().is_valid_for_network(network) {
+        return Err(anyhow!(
+            "{} {} is not valid for network {}",
+            role,
+            address,
+            network
+        ));
+    }
+    Ok(())
+}
+
+// Returns the output of `tx` paying to `change_address` as an unconfirmed UTXO,
+// so it can be spent before `tx` confirms
+fn find_change_utxo(tx: &Transaction, change_address: &Address) -> Option<UTXO> {
+    let vout = find_output(tx, change_address)?;
+    let output = &tx.output[vout as usize];
+    Some(UTXO {
+        tx_id: tx.compute_txid(),
+        vout,
+        address: Some(change_address.as_unchecked().clone()),
+        script_pubkey: output.script_pubkey.to_hex_string(),
+        amount: output.value.to_sat(),
+        confirmations: 0,
+        spendable: true,
+        solvable: true,
+    })
+}
+
+// Outpoints spent by `tx` and by the transactions of `ancestors` it descends from
+fn spent_by_ancestry(tx: &Transaction, ancestors: &[TxWithId]) -> Vec<OutPoint> {
+    let mut spent = vec![];
+    let mut descendants = vec![tx];
+    while let Some(tx) = descendants.pop() {
+        for input in &tx.input {
+            if spent.contains(&input.previous_output) {
+                continue;
+            }
+            spent.push(input.previous_output);
+            if let Some(parent) = ancestors
+                .iter()
+                .find(|ancestor| ancestor.id == input.previous_output.txid)
+            {
+                descendants.push(&parent.tx);
+            }
+        }
+    }
+    spent
+}
+
+/// Returns the index of the first output of `tx` paying to `address`.
+fn find_output(tx: &Transaction, address: &Address) -> Option<u32> {
+    let script_pubkey = address.script_pubkey();
+    tx.output
+        .iter()
+        .position(|output| output.script_pubkey == script_pubkey)
+        .map(|vout| vout as u32)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -259,14 +894,25 @@ fn build_reveal_transaction(
     input_vout: u32,
     recipient: Address,
     output_value: u64,
-    fee_rate: f64,
+    dust_threshold: u64,
+    fee_rate: FeeRate,
     reveal_script: &ScriptBuf,
     control_block: &ControlBlock,
+    sighash_type: TapSighashType,
+    change_address: Option<Address>,
+    extra_outputs: &[TxOut],
 ) -> Result<Transaction, anyhow::Error> {
-    let outputs: Vec<TxOut> = vec![TxOut {
+    let mut outputs: Vec<TxOut> = vec![TxOut {
         value: Amount::from_sat(output_value),
         script_pubkey: recipient.script_pubkey(),
     }];
+    // e.g. an anchor for a later fee bump, paid for by the input as well
+    outputs.extend_from_slice(extra_outputs);
+    let output_total = output_value
+        + extra_outputs
+            .iter()
+            .map(|output| output.value.to_sat())
+            .sum::<u64>();
 
     let inputs = vec![TxIn {
         previous_output: OutPoint {
@@ -278,18 +924,49 @@ fn build_reveal_transaction(
         sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
     }];
 
-    let size = get_size(&inputs, &outputs, Some(reveal_script), Some(control_block));
+    let size = try_get_size_with_sighash(
+        &inputs,
+        &outputs,
+        Some(reveal_script),
+        Some(control_block),
+        sighash_type,
+    )?;
 
-    let fee = ((size as f64) * fee_rate).ceil() as u64;
+    let fee = fee_rate.fee_for_vsize(size);
 
-    let input_total = output_value + fee;
+    let input_total = output_total + fee;
 
-    if input_utxo.value < Amount::from_sat(REVEAL_OUTPUT_AMOUNT)
+    if input_utxo.value < Amount::from_sat(dust_threshold)
         || input_utxo.value < Amount::from_sat(input_total)
     {
         return Err(anyhow::anyhow!("input UTXO not big enough"));
     }
 
+    // return the excess instead of leaving it to the miners, if it is above dust
+    if let Some(change_address) = change_address {
+        let mut outputs_with_change = outputs.clone();
+        outputs_with_change.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: change_address.script_pubkey(),
+        });
+        let size = try_get_size_with_sighash(
+            &inputs,
+            &outputs_with_change,
+            Some(reveal_script),
+            Some(control_block),
+            sighash_type,
+        )?;
+        let input_total = output_total + fee_rate.fee_for_vsize(size);
+
+        let change = input_utxo.value.to_sat().saturating_sub(input_total);
+        if change >= dust_threshold {
+            if let Some(change_output) = outputs_with_change.last_mut() {
+                change_output.value = Amount::from_sat(change);
+            }
+            outputs = outputs_with_change;
+        }
+    }
+
     let tx = Transaction {
         lock_time: LockTime::ZERO,
         version: bitcoin::transaction::Version(2),
@@ -309,6 +986,14 @@ pub struct TxWithId {
     pub tx: Transaction,
 }
 
+impl TxWithId {
+    /// The reveal script exactly as the builder pushed it to the witness of a reveal tx,
+    /// e.g. for verifiers recomputing its tapleaf hash. None if the tx isn't a tapscript spend.
+    pub fn reveal_script(&self) -> Option<&Script> {
+        self.tx.input.first()?.witness.tapscript()
+    }
+}
+
 impl fmt::Debug for TxWithId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TxWithId")
@@ -318,282 +1003,1373 @@ impl fmt::Debug for TxWithId {
     }
 }
 
-// TODO: parametrize hardness
-// so tests are easier
-// Creates the inscription transactions (commit and reveal)
-#[allow(clippy::too_many_arguments)]
-#[instrument(level = "trace", skip_all, err)]
-pub fn create_inscription_transactions(
+/// Reveal tx ids are 32 bytes long, a longer prefix can never match.
+pub const MAX_REVEAL_TX_PREFIX_LEN: usize = 32;
+
+/// Fails if an extra envelope tag is empty, which would read as the body tag, or duplicated.
+fn check_extra_tags(extra_tags: &[(Vec<u8>, Vec<u8>)]) -> Result<(), anyhow::Error> {
+    for (i, (tag, _)) in extra_tags.iter().enumerate() {
+        if tag.as_slice() == BODY_TAG {
+            return Err(anyhow!("extra envelope tags can't be empty"));
+        }
+        if extra_tags[..i].iter().any(|(other, _)| other == tag) {
+            return Err(anyhow!(
+                "extra envelope tag {} is duplicated",
+                hex::encode(tag)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether the leading `bits` bits of `hash` are zero, always false if `hash` is shorter.
+pub fn has_leading_zero_bits(hash: &[u8], bits: u32) -> bool {
+    let (bytes, rest) = ((bits / 8) as usize, bits % 8);
+    if hash.len() < bytes + usize::from(rest > 0) {
+        return false;
+    }
+    hash[..bytes].iter().all(|byte| *byte == 0) && (rest == 0 || hash[bytes] >> (8 - rest) == 0)
+}
+
+/// Longest reveal tx prefix accepted by the inscription builders unless configured otherwise.
+/// Mining a 4 byte prefix already takes around 2^32 attempts.
+pub const DEFAULT_MAX_REVEAL_TX_PREFIX_LEN: usize = 4;
+
+/// Fails if `prefix` is longer than `max_len` bytes, or longer than a tx id.
+pub fn check_reveal_tx_prefix(prefix: &[u8], max_len: usize) -> Result<(), anyhow::Error> {
+    let max_len = max_len.min(MAX_REVEAL_TX_PREFIX_LEN);
+    if prefix.len() > max_len {
+        return Err(anyhow!(
+            "reveal tx prefix of {} bytes is longer than the maximum of {} bytes",
+            prefix.len(),
+            max_len
+        ));
+    }
+    Ok(())
+}
+
+/// Expected number of reveal transactions hashed before one whose id starts with
+/// `prefix` is found. Every prefix byte must match, so each one multiplies the
+/// search space by 256.
+pub fn expected_nonce_attempts(prefix: &[u8]) -> f64 {
+    256f64.powi(prefix.len() as i32)
+}
+
+/// Sats the UTXOs funding an inscription of a `body_len` bytes body must cover,
+/// the value of the commit output plus the commit fee.
+/// Assumes P2TR reveal recipient and change addresses, a single funding UTXO with change,
+/// an envelope without tags and no reveal prefix, tags and a prefix make the reveal script
+/// a few bytes longer.
+pub fn required_input_value(
     rollup_name: &str,
-    body: Vec<u8>,
-    signature: Vec<u8>,
-    sequencer_public_key: Vec<u8>,
-    prev_tx: Option<TxWithId>,
-    utxos: Vec<UTXO>,
-    recipient: Address,
+    body_len: usize,
     reveal_value: u64,
-    commit_fee_rate: f64,
-    reveal_fee_rate: f64,
-    network: Network,
-    reveal_tx_prefix: &[u8],
-) -> Result<(Transaction, TxWithId), anyhow::Error> {
-    // Create commit key
+    commit_fee_rate: FeeRate,
+    reveal_fee_rate: FeeRate,
+) -> Result<u64, anyhow::Error> {
     let secp256k1 = Secp256k1::new();
-    let key_pair = UntweakedKeypair::new(&secp256k1, &mut rand::thread_rng());
+    // only the sizes of the keys, the signature and the body matter
+    let key_pair = UntweakedKeypair::from_secret_key(&secp256k1, &SecretKey::from_slice(&[1; 32])?);
     let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+    // the sequencer signs with a compact signature and a compressed public key
+    let envelopes = [(vec![0; body_len], vec![0; 64])];
+    let reveal_script =
+        RevealScriptTemplate::new(&public_key, rollup_name, &envelopes, &[0; 33], None, &[])
+            .reveal_script(0);
+    let taproot_spend_info = TaprootBuilder::new()
+        .add_leaf(0, reveal_script.clone())
+        .expect("Cannot add reveal script to taptree")
+        .finalize(&secp256k1, public_key)
+        .expect("Cannot finalize taptree");
+    let control_block = taproot_spend_info
+        .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+        .expect("Cannot create control block");
+    let p2tr_output = TxOut {
+        script_pubkey: ScriptBuf::new_p2tr(&secp256k1, public_key, None),
+        value: Amount::from_sat(reveal_value),
+    };
 
-    // start creating inscription content
-    let reveal_script_builder = script::Builder::new()
-        .push_x_only_key(&public_key)
-        .push_opcode(OP_CHECKSIG)
-        .push_opcode(OP_FALSE)
-        .push_opcode(OP_IF)
-        .push_slice(PushBytesBuf::from(ROLLUP_NAME_TAG))
-        .push_slice(
-            PushBytesBuf::try_from(rollup_name.as_bytes().to_vec())
-                .expect("Cannot push rollup name"),
-        )
-        .push_slice(PushBytesBuf::from(SIGNATURE_TAG))
-        .push_slice(PushBytesBuf::try_from(signature).expect("Cannot push signature"))
-        .push_slice(PushBytesBuf::from(PUBLICKEY_TAG))
-        .push_slice(
-            PushBytesBuf::try_from(sequencer_public_key).expect("Cannot push sequencer public key"),
-        )
-        .push_slice(PushBytesBuf::from(RANDOM_TAG));
-    // This envelope is not finished yet. The random number will be added later and followed by the body
-
-    // Start loop to find a 'nonce' i.e. random number that makes the reveal tx hash starting with zeros given length
-    let mut nonce: i64 = 0;
-    loop {
-        if nonce % 10000 == 0 {
-            trace!(nonce, "Trying to find commit & reveal nonce");
-            if nonce > 65536 {
-                warn!("Too many iterations finding nonce");
-            }
-        }
-        let utxos = utxos.clone();
-        let recipient = recipient.clone();
-        // ownerships are moved to the loop
-        let mut reveal_script_builder = reveal_script_builder.clone();
+    let dummy_input = TxIn {
+        previous_output: OutPoint::null(),
+        script_sig: ScriptBuf::new(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+    };
+    let commit_value = commit_output_value(
+        &dummy_input,
+        std::slice::from_ref(&p2tr_output),
+        &reveal_script,
+        &control_block,
+        TapSighashType::Default,
+        reveal_fee_rate,
+    )?;
+    let commit_fee = commit_fee_rate.fee_for_vsize(try_get_size(
+        &[dummy_input],
+        &[p2tr_output.clone(), p2tr_output],
+        None,
+        None,
+    )?);
+    Ok(commit_value + commit_fee)
+}
 
-        // push first random number and body tag
-        reveal_script_builder = reveal_script_builder
-            .push_int(nonce)
-            .push_slice(PushBytesBuf::from(BODY_TAG));
+/// Smallest UTXO value that funds a standalone reveal of `reveal_script` paying `output_value`
+/// to a P2TR recipient at `fee_rate` without change, the threshold below which
+/// `build_reveal_transaction` fails with the default dust threshold.
+pub fn min_reveal_input_value(
+    output_value: u64,
+    fee_rate: FeeRate,
+    reveal_script: &ScriptBuf,
+    control_block: &ControlBlock,
+) -> u64 {
+    let input = TxIn {
+        previous_output: OutPoint::null(),
+        script_sig: ScriptBuf::new(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+    };
+    // only the size of the recipient script matters
+    let output = TxOut {
+        value: Amount::from_sat(output_value),
+        script_pubkey: ScriptBuf::new_p2tr(
+            &Secp256k1::verification_only(),
+            control_block.internal_key,
+            None,
+        ),
+    };
+    let size = get_size(
+        &[input],
+        &[output],
+        Some(reveal_script),
+        Some(control_block),
+    );
 
-        // push body in chunks of 520 bytes
-        for chunk in body.chunks(520) {
-            reveal_script_builder = reveal_script_builder.push_slice(
-                PushBytesBuf::try_from(chunk.to_vec()).expect("Cannot push body chunk"),
-            );
-        }
-        // push end if
-        reveal_script_builder = reveal_script_builder.push_opcode(OP_ENDIF);
+    (output_value + fee_rate.fee_for_vsize(size)).max(DUST_THRESHOLD)
+}
 
-        // finalize reveal script
-        let reveal_script = reveal_script_builder.into_script();
+// Value the commit output must have for the reveal tx to pay its fee and `reveal_outputs`
+fn commit_output_value(
+    reveal_input: &TxIn,
+    reveal_outputs: &[TxOut],
+    reveal_script: &ScriptBuf,
+    control_block: &ControlBlock,
+    reveal_sighash_type: TapSighashType,
+    reveal_fee_rate: FeeRate,
+) -> Result<u64, anyhow::Error> {
+    let fee = reveal_fee_rate.fee_for_vsize(try_get_size_with_sighash(
+        std::slice::from_ref(reveal_input),
+        reveal_outputs,
+        Some(reveal_script),
+        Some(control_block),
+        reveal_sighash_type,
+    )?);
+    Ok(fee
+        + reveal_outputs
+            .iter()
+            .map(|output| output.value.to_sat())
+            .sum::<u64>())
+}
 
-        // create spend info for tapscript
-        let taproot_spend_info = TaprootBuilder::new()
-            .add_leaf(0, reveal_script.clone())
-            .expect("Cannot add reveal script to taptree")
-            .finalize(&secp256k1, public_key)
-            .expect("Cannot finalize taptree");
+// The encryption tag is written by `InscriptionBuilder::encryption`, declaring it as an extra tag
+// would mark a plaintext body as encrypted.
+fn check_unreserved_extra_tags(extra_tags: &[(Vec<u8>, Vec<u8>)]) -> Result<(), anyhow::Error> {
+    if extra_tags
+        .iter()
+        .any(|(tag, _)| tag.as_slice() == ENCRYPTION_TAG)
+    {
+        return Err(anyhow!(
+            "extra envelope tag {} is reserved for the encryption scheme",
+            hex::encode(ENCRYPTION_TAG)
+        ));
+    }
+    Ok(())
+}
 
-        // create control block for tapscript
-        let control_block = taproot_spend_info
-            .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
-            .expect("Cannot create control block");
+// TODO: parametrize hardness
+// so tests are easier
+// Creates the inscription transactions (commit and reveal)
+// Every other option is left at its default, set them through `InscriptionBuilder`.
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "trace", skip_all, err)]
+pub fn create_inscription_transactions(
+    rollup_name: &str,
+    body: Vec<u8>,
+    signature: Vec<u8>,
+    sequencer_public_key: Vec<u8>,
+    prev_tx: Option<TxWithId>,
+    utxos: Vec<UTXO>,
+    change_address: Address,
+    reveal_recipient: Address,
+    reveal_value: u64,
+    dust_threshold: u64,
+    commit_fee_rate: FeeRate,
+    reveal_fee_rate: FeeRate,
+    network: Network,
+    reveal_tx_prefix: &[u8],
+) -> Result<(Transaction, TxWithId, Option<UTXO>), anyhow::Error> {
+    create_inscription_transactions_with_rng(
+        rollup_name,
+        body,
+        signature,
+        sequencer_public_key,
+        prev_tx,
+        utxos,
+        change_address,
+        reveal_recipient,
+        reveal_value,
+        dust_threshold,
+        commit_fee_rate,
+        reveal_fee_rate,
+        network,
+        reveal_tx_prefix,
+        &mut rand::thread_rng(),
+    )
+}
 
-        // create commit tx address
-        let commit_tx_address = Address::p2tr(
-            &secp256k1,
-            public_key,
-            taproot_spend_info.merkle_root(),
-            network,
-        );
+// Same as `create_inscription_transactions`, but the commit key and the auxiliary randomness
+// of the reveal signature are drawn from `rng`, so a seeded rng gives reproducible transactions.
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "trace", skip_all, err)]
+pub fn create_inscription_transactions_with_rng<R: RngCore + CryptoRng>(
+    rollup_name: &str,
+    body: Vec<u8>,
+    signature: Vec<u8>,
+    sequencer_public_key: Vec<u8>,
+    prev_tx: Option<TxWithId>,
+    utxos: Vec<UTXO>,
+    change_address: Address,
+    reveal_recipient: Address,
+    reveal_value: u64,
+    dust_threshold: u64,
+    commit_fee_rate: FeeRate,
+    reveal_fee_rate: FeeRate,
+    network: Network,
+    reveal_tx_prefix: &[u8],
+    rng: &mut R,
+) -> Result<(Transaction, TxWithId, Option<UTXO>), anyhow::Error> {
+    InscriptionBuilder::new()
+        .rollup_name(rollup_name)
+        .body(body)
+        .signature(signature)
+        .sequencer_public_key(sequencer_public_key)
+        .prev_tx(prev_tx)
+        .utxos(utxos)
+        .change_address(change_address)
+        .reveal_recipient(reveal_recipient)
+        .reveal_value(reveal_value)
+        .dust_threshold(dust_threshold)
+        .fee_rates(commit_fee_rate, reveal_fee_rate)
+        .network(network)
+        .reveal_prefix(reveal_tx_prefix)
+        .build_with_rng(rng)
+}
 
-        let commit_value = (get_size(
-            &[TxIn {
-                previous_output: OutPoint {
-                    txid: Txid::from_byte_array([0; 32]),
-                    vout: 0,
-                },
-                script_sig: script::Builder::new().into_script(),
-                witness: Witness::new(),
-                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-            }],
-            &[TxOut {
-                script_pubkey: recipient.clone().script_pubkey(),
-                value: Amount::from_sat(reveal_value),
-            }],
-            Some(&reveal_script),
-            Some(&control_block),
-        ) as f64
-            * reveal_fee_rate
-            + reveal_value as f64)
-            .ceil() as u64;
+// Creates a single commit and reveal pair inscribing one envelope per body.
+// Each body is signed separately, `signatures` must be in the same order as `bodies`.
+// Fails if the reveal transaction would be too large, in which case the bodies should be
+// split over multiple inscriptions.
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "trace", skip_all, err)]
+pub fn create_batched_inscription(
+    rollup_name: &str,
+    bodies: Vec<Vec<u8>>,
+    signatures: Vec<Vec<u8>>,
+    sequencer_public_key: Vec<u8>,
+    prev_tx: Option<TxWithId>,
+    utxos: Vec<UTXO>,
+    change_address: Address,
+    reveal_recipient: Address,
+    reveal_value: u64,
+    dust_threshold: u64,
+    commit_fee_rate: FeeRate,
+    reveal_fee_rate: FeeRate,
+    network: Network,
+    reveal_tx_prefix: &[u8],
+) -> Result<(Transaction, TxWithId, Option<UTXO>), anyhow::Error> {
+    if bodies.is_empty() || bodies.len() != signatures.len() {
+        return Err(anyhow!(
+            "expected a signature for each of the bodies, got {} bodies and {} signatures",
+            bodies.len(),
+            signatures.len()
+        ));
+    }
 
-        // build commit tx
-        let unsigned_commit_tx = build_commit_transaction(
-            prev_tx.clone(),
-            utxos,
-            commit_tx_address.clone(),
-            recipient.clone(),
-            commit_value,
-            commit_fee_rate,
-        )?;
+    let envelopes: Vec<_> = bodies.into_iter().zip(signatures).collect();
+    create_envelope_transactions(
+        rollup_name,
+        &envelopes,
+        None,
+        sequencer_public_key,
+        prev_tx,
+        &[],
+        utxos,
+        change_address,
+        reveal_recipient,
+        None,
+        &[],
+        reveal_value,
+        dust_threshold,
+        commit_fee_rate,
+        reveal_fee_rate,
+        FeeRate::MIN_RELAY,
+        network,
+        reveal_tx_prefix,
+        DEFAULT_MAX_REVEAL_TX_PREFIX_LEN,
+        0,
+        false,
+        None,
+        TapSighashType::Default,
+        &[],
+        None,
+        &mut rand::thread_rng(),
+    )
+}
 
-        let output_to_reveal = unsigned_commit_tx.output[0].clone();
+/// Operator configuration of the inscriptions sent to the DA layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitcoinDaConfig {
+    /// Value of the reveal transaction output
+    pub reveal_output_amount: u64,
+    /// Prefix the reveal transaction id is mined to start with
+    pub default_reveal_prefix: Vec<u8>,
+    /// Longest reveal prefix accepted, each byte takes 256 times as many attempts to mine
+    pub max_reveal_tx_prefix_len: usize,
+    pub commit_fee_rate: FeeRate,
+    pub reveal_fee_rate: FeeRate,
+    /// Fee rate below which the nodes of the network don't relay transactions,
+    /// their `-minrelaytxfee`
+    pub min_relay_fee_rate: FeeRate,
+    pub network: Network,
+    /// Whether envelopes declare their version and compression scheme. Full nodes and provers
+    /// released before the tags reject such envelopes, so only enable once all of them upgraded.
+    pub envelope_tags: bool,
+    /// Whether the commit change output is placed at a random position
+    pub randomize_commit_outputs: bool,
+    /// Script types of the wallet UTXOs that may fund the commit transaction, any if None
+    pub allowed_script_types: Option<Vec<ScriptType>>,
+}
 
-        let mut reveal_tx = build_reveal_transaction(
-            output_to_reveal.clone(),
-            unsigned_commit_tx.compute_txid(),
-            0,
-            recipient,
-            reveal_value,
+impl BitcoinDaConfig {
+    /// Creates a config with a [`REVEAL_OUTPUT_AMOUNT`] reveal output and no reveal prefix.
+    pub fn new(network: Network, commit_fee_rate: FeeRate, reveal_fee_rate: FeeRate) -> Self {
+        Self {
+            reveal_output_amount: REVEAL_OUTPUT_AMOUNT,
+            default_reveal_prefix: vec![],
+            max_reveal_tx_prefix_len: DEFAULT_MAX_REVEAL_TX_PREFIX_LEN,
+            commit_fee_rate,
             reveal_fee_rate,
-            &reveal_script,
-            &control_block,
-        )?;
+            min_relay_fee_rate: FeeRate::MIN_RELAY,
+            network,
+            envelope_tags: false,
+            randomize_commit_outputs: false,
+            allowed_script_types: None,
+        }
+    }
+}
 
-        let reveal_tx_id = reveal_tx.compute_txid();
-        let reveal_hash = reveal_tx_id.as_raw_hash().to_byte_array();
+/// Builder behind [`create_inscription_transactions`], and the only way to set the options the
+/// free functions leave at their defaults.
+/// Everything except the previous transaction and its ancestors, the reveal prefix and
+/// difficulty, the reveal value, the compression scheme, output randomization and the commit
+/// locktime must be set before calling [`Self::build`].
+#[derive(Debug, Clone)]
+pub struct InscriptionBuilder {
+    rollup_name: Option<String>,
+    body: Option<Vec<u8>>,
+    compression: CompressionScheme,
+    signature: Option<Vec<u8>>,
+    sequencer_public_key: Option<Vec<u8>>,
+    prev_tx: Option<TxWithId>,
+    prev_tx_ancestors: Vec<TxWithId>,
+    utxos: Option<Vec<UTXO>>,
+    change_address: Option<Address>,
+    reveal_recipient: Option<Address>,
+    reveal_change_address: Option<Address>,
+    reveal_extra_outputs: Vec<TxOut>,
+    reveal_value: u64,
+    dust_threshold: u64,
+    fee_rates: Option<(FeeRate, FeeRate)>,
+    min_relay_fee_rate: FeeRate,
+    network: Option<Network>,
+    reveal_prefix: Vec<u8>,
+    max_reveal_prefix_len: usize,
+    reveal_difficulty_bits: u32,
+    randomize_commit_outputs: bool,
+    commit_locktime: Option<LockTime>,
+    reveal_sighash_type: TapSighashType,
+    extra_tags: Vec<(Vec<u8>, Vec<u8>)>,
+    encryption: Option<(EncryptionScheme, RedactedKey)>,
+    signing_key: Option<SecretKey>,
+    envelope_tags: bool,
+    allowed_script_types: Option<Vec<ScriptType>>,
+}
 
-        // check if first N bytes equal to the given prefix
-        if reveal_hash.starts_with(reveal_tx_prefix) {
-            // start signing reveal tx
-            let mut sighash_cache = SighashCache::new(&mut reveal_tx);
+/// Encryption key of the builder, kept out of its debug output
+#[derive(Clone)]
+struct RedactedKey([u8; 32]);
 
-            // create data to sign
-            let signature_hash = sighash_cache
-                .taproot_script_spend_signature_hash(
-                    0,
-                    &Prevouts::All(&[output_to_reveal]),
-                    TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
-                    bitcoin::sighash::TapSighashType::Default,
-                )
-                .expect("Cannot create hash for signature");
+impl fmt::Debug for RedactedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RedactedKey(..)")
+    }
+}
 
-            // sign reveal tx data
-            let signature = secp256k1.sign_schnorr_with_rng(
-                &secp256k1::Message::from_digest_slice(signature_hash.as_byte_array())
-                    .expect("should be cryptographically secure hash"),
-                &key_pair,
-                &mut rand::thread_rng(),
-            );
+impl Default for InscriptionBuilder {
+    fn default() -> Self {
+        Self {
+            rollup_name: None,
+            body: None,
+            compression: CompressionScheme::Brotli,
+            signature: None,
+            sequencer_public_key: None,
+            prev_tx: None,
+            prev_tx_ancestors: vec![],
+            utxos: None,
+            change_address: None,
+            reveal_recipient: None,
+            reveal_change_address: None,
+            reveal_extra_outputs: vec![],
+            reveal_value: REVEAL_OUTPUT_AMOUNT,
+            dust_threshold: DUST_THRESHOLD,
+            fee_rates: None,
+            min_relay_fee_rate: FeeRate::MIN_RELAY,
+            network: None,
+            reveal_prefix: vec![],
+            max_reveal_prefix_len: DEFAULT_MAX_REVEAL_TX_PREFIX_LEN,
+            reveal_difficulty_bits: 0,
+            randomize_commit_outputs: false,
+            commit_locktime: None,
+            reveal_sighash_type: TapSighashType::Default,
+            extra_tags: vec![],
+            encryption: None,
+            signing_key: None,
+            envelope_tags: false,
+            allowed_script_types: None,
+        }
+    }
+}
 
-            // add signature to witness and finalize reveal tx
-            let witness = sighash_cache.witness_mut(0).unwrap();
-            witness.push(signature.as_ref());
-            witness.push(reveal_script);
-            witness.push(&control_block.serialize());
+impl InscriptionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-            // check if inscription locked to the correct address
-            let recovery_key_pair =
-                key_pair.tap_tweak(&secp256k1, taproot_spend_info.merkle_root());
-            let (x_only_pub_key, _parity) = recovery_key_pair.to_inner().x_only_public_key();
-            assert_eq!(
-                Address::p2tr_tweaked(
-                    TweakedPublicKey::dangerous_assume_tweaked(x_only_pub_key),
-                    network,
-                ),
-                commit_tx_address
-            );
+    pub fn rollup_name(mut self, rollup_name: impl Into<String>) -> Self {
+        self.rollup_name = Some(rollup_name.into());
+        self
+    }
 
-            return Ok((
-                unsigned_commit_tx,
-                TxWithId {
-                    id: reveal_tx_id,
-                    tx: reveal_tx,
-                },
-            ));
-        }
+    /// The blob already encoded with the declared [`Self::compression`] scheme, whether or not
+    /// it is encrypted
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = Some(body);
+        self
+    }
 
-        nonce += 1;
+    /// Defaults to [`CompressionScheme::Brotli`], other schemes need [`Self::envelope_tags`]
+    pub fn compression(mut self, compression: CompressionScheme) -> Self {
+        self.compression = compression;
+        self
     }
-}
 
-pub fn write_reveal_tx(tx: &[u8], tx_id: String) {
-    let reveal_tx_file = File::create(format!("reveal_{}.tx", tx_id)).unwrap();
-    let mut reveal_tx_writer = BufWriter::new(reveal_tx_file);
-    reveal_tx_writer.write_all(tx).unwrap();
-}
+    /// Writes the version and compression tags into the envelope, needed to declare a
+    /// compression scheme other than brotli or extra tags. Defaults to false, the format
+    /// read by full nodes and provers released before the tags, see [`BitcoinDaConfig`]
+    pub fn envelope_tags(mut self, envelope_tags: bool) -> Self {
+        self.envelope_tags = envelope_tags;
+        self
+    }
 
-#[cfg(test)]
-mod tests {
-    use core::str::FromStr;
+    /// Signs the body as inscribed, under the declared schemes, with `private_key` when building,
+    /// taking precedence over [`Self::signature`] and [`Self::sequencer_public_key`].
+    /// Encrypted bodies can only be signed this way, as their ciphertext is only known then.
+    pub fn sign_with(mut self, private_key: &SecretKey) -> Self {
+        self.signing_key = Some(*private_key);
+        self
+    }
 
-    use bitcoin::hashes::Hash;
-    use bitcoin::secp256k1::constants::SCHNORR_SIGNATURE_SIZE;
-    use bitcoin::secp256k1::schnorr::Signature;
-    use bitcoin::taproot::ControlBlock;
-    use bitcoin::{Address, Amount, ScriptBuf, TxOut, Txid};
+    pub fn signature(mut self, signature: Vec<u8>) -> Self {
+        self.signature = Some(signature);
+        self
+    }
 
-    use crate::helpers::compression::{compress_blob, decompress_blob};
-    use crate::helpers::parsers::parse_transaction;
-    use crate::spec::utxo::UTXO;
-    use crate::REVEAL_OUTPUT_AMOUNT;
+    pub fn sequencer_public_key(mut self, sequencer_public_key: Vec<u8>) -> Self {
+        self.sequencer_public_key = Some(sequencer_public_key);
+        self
+    }
 
-    #[test]
-    fn compression_decompression() {
-        let blob = std::fs::read("test_data/blob.txt").unwrap();
+    pub fn prev_tx(mut self, prev_tx: Option<TxWithId>) -> Self {
+        self.prev_tx = prev_tx;
+        self
+    }
 
-        // compress and measure time
-        let time = std::time::Instant::now();
-        let compressed_blob = compress_blob(&blob);
-        println!("compression time: {:?}", time.elapsed());
+    /// Unconfirmed transactions the previous transaction descends from, UTXOs spent
+    /// by any of them are rejected. Defaults to none
+    pub fn prev_tx_ancestors(mut self, prev_tx_ancestors: Vec<TxWithId>) -> Self {
+        self.prev_tx_ancestors = prev_tx_ancestors;
+        self
+    }
 
-        // decompress and measure time
-        let time = std::time::Instant::now();
-        let decompressed_blob = decompress_blob(&compressed_blob);
-        println!("decompression time: {:?}", time.elapsed());
+    pub fn utxos(mut self, utxos: Vec<UTXO>) -> Self {
+        self.utxos = Some(utxos);
+        self
+    }
 
-        assert_eq!(blob, decompressed_blob);
+    pub fn change_address(mut self, change_address: Address) -> Self {
+        self.change_address = Some(change_address);
+        self
+    }
 
-        // size
-        println!("blob size: {}", blob.len());
-        println!("compressed blob size: {}", compressed_blob.len());
-        println!(
-            "compression ratio: {}",
-            (blob.len() as f64) / (compressed_blob.len() as f64)
-        );
+    pub fn reveal_recipient(mut self, reveal_recipient: Address) -> Self {
+        self.reveal_recipient = Some(reveal_recipient);
+        self
     }
 
-    #[test]
-    fn write_reveal_tx() {
-        let tx = vec![100, 100, 100];
-        let tx_id = "test_tx".to_string();
+    /// Receives the excess of the commit output over the reveal value and fee as a second
+    /// reveal output, if it is not dust. Defaults to none, leaving the excess to the miners
+    pub fn reveal_change_address(mut self, reveal_change_address: Option<Address>) -> Self {
+        self.reveal_change_address = reveal_change_address;
+        self
+    }
 
-        super::write_reveal_tx(tx.as_slice(), tx_id);
+    /// Outputs added to the reveal tx after the one to the reveal recipient, e.g. an anchor
+    /// for a later fee bump. The commit output pays for them. Defaults to none
+    pub fn reveal_extra_outputs(mut self, reveal_extra_outputs: Vec<TxOut>) -> Self {
+        self.reveal_extra_outputs = reveal_extra_outputs;
+        self
+    }
 
-        let file = std::fs::read("reveal_test_tx.tx").unwrap();
+    /// Defaults to [`REVEAL_OUTPUT_AMOUNT`]
+    pub fn reveal_value(mut self, reveal_value: u64) -> Self {
+        self.reveal_value = reveal_value;
+        self
+    }
 
-        assert_eq!(tx, file);
+    /// Change below this value is left to the miners. Defaults to [`DUST_THRESHOLD`]
+    pub fn dust_threshold(mut self, dust_threshold: u64) -> Self {
+        self.dust_threshold = dust_threshold;
+        self
+    }
 
-        std::fs::remove_file("reveal_test_tx.tx").unwrap();
+    pub fn fee_rates(mut self, commit_fee_rate: FeeRate, reveal_fee_rate: FeeRate) -> Self {
+        self.fee_rates = Some((commit_fee_rate, reveal_fee_rate));
+        self
     }
 
-    #[allow(clippy::type_complexity)]
-    fn get_mock_data() -> (&'static str, Vec<u8>, Vec<u8>, Vec<u8>, Address, Vec<UTXO>) {
-        let rollup_name = "test_rollup";
-        let body = vec![100; 1000];
-        let signature = vec![100; 64];
-        let sequencer_public_key = vec![100; 33];
-        let address =
-            Address::from_str("bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9")
-                .unwrap()
-                .require_network(bitcoin::Network::Bitcoin)
-                .unwrap();
-        let utxos = vec![
-            UTXO {
-                tx_id: Txid::from_str(
-                    "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
-                )
-                .unwrap(),
-                vout: 0,
-                address: Some(
-                    Address::from_str(
+    /// Fails the build if either fee rate is below it. Defaults to [`FeeRate::MIN_RELAY`]
+    pub fn min_relay_fee_rate(mut self, min_relay_fee_rate: FeeRate) -> Self {
+        self.min_relay_fee_rate = min_relay_fee_rate;
+        self
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Defaults to no prefix
+    pub fn reveal_prefix(mut self, reveal_prefix: &[u8]) -> Self {
+        self.reveal_prefix = reveal_prefix.to_vec();
+        self
+    }
+
+    /// Longest reveal prefix, and most difficulty bytes, the build accepts, at most
+    /// [`MAX_REVEAL_TX_PREFIX_LEN`]. Defaults to [`DEFAULT_MAX_REVEAL_TX_PREFIX_LEN`]
+    pub fn max_reveal_prefix_len(mut self, max_reveal_prefix_len: usize) -> Self {
+        self.max_reveal_prefix_len = max_reveal_prefix_len;
+        self
+    }
+
+    /// Number of leading bits of the reveal tx hash that must be zero, on top of the
+    /// reveal prefix. Allows tuning the mining effort more finely than whole prefix bytes.
+    /// Defaults to 0
+    pub fn reveal_tx_difficulty_bits(mut self, bits: u32) -> Self {
+        self.reveal_difficulty_bits = bits;
+        self
+    }
+
+    /// Defaults to false
+    pub fn randomize_commit_outputs(mut self, randomize_commit_outputs: bool) -> Self {
+        self.randomize_commit_outputs = randomize_commit_outputs;
+        self
+    }
+
+    /// Only funds the commit transaction with UTXOs of these script types, e.g. to keep
+    /// legacy outputs the wallet can't sign for out of it. The output of the previous
+    /// transaction is spent regardless. Defaults to any script type
+    pub fn allowed_script_types(mut self, allowed_script_types: Option<Vec<ScriptType>>) -> Self {
+        self.allowed_script_types = allowed_script_types;
+        self
+    }
+
+    /// Sets the reveal value, the reveal prefix, the fee rates and the network from `config`
+    pub fn config(self, config: &BitcoinDaConfig) -> Self {
+        self.reveal_value(config.reveal_output_amount)
+            .reveal_prefix(&config.default_reveal_prefix)
+            .max_reveal_prefix_len(config.max_reveal_tx_prefix_len)
+            .fee_rates(config.commit_fee_rate, config.reveal_fee_rate)
+            .min_relay_fee_rate(config.min_relay_fee_rate)
+            .network(config.network)
+            .envelope_tags(config.envelope_tags)
+            .randomize_commit_outputs(config.randomize_commit_outputs)
+            .allowed_script_types(config.allowed_script_types.clone())
+    }
+
+    /// Defaults to no locktime
+    pub fn commit_locktime(mut self, commit_locktime: Option<LockTime>) -> Self {
+        self.commit_locktime = commit_locktime;
+        self
+    }
+
+    /// Sighash type of the reveal signature, e.g. [`TapSighashType::AllPlusAnyoneCanPay`]
+    /// to allow attaching inputs to the reveal later. Defaults to [`TapSighashType::Default`]
+    pub fn reveal_sighash_type(mut self, reveal_sighash_type: TapSighashType) -> Self {
+        self.reveal_sighash_type = reveal_sighash_type;
+        self
+    }
+
+    /// Metadata pushed into the envelope as tag and value pairs, e.g. a version,
+    /// returned by the parser in `ParsedInscription::extra_tags`.
+    /// Tags must be non-empty and unique. Defaults to no extra tags
+    pub fn extra_tags(mut self, extra_tags: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        self.extra_tags = extra_tags;
+        self
+    }
+
+    /// Encrypts the body with `key` under `encryption`, which needs [`Self::envelope_tags`] and
+    /// [`Self::sign_with`]. The body is still the blob encoded with the declared compression
+    /// scheme, so it is compressed before it is encrypted, as ciphertext doesn't compress. The
+    /// nonce is drawn from the rng of the build, and the signature is over the encrypted body
+    /// so that it can be verified without the key. Defaults to no encryption
+    pub fn encryption(mut self, encryption: EncryptionScheme, key: [u8; 32]) -> Self {
+        self.encryption = Some((encryption, RedactedKey(key)));
+        self
+    }
+
+    pub fn build(self) -> Result<(Transaction, TxWithId, Option<UTXO>), anyhow::Error> {
+        self.build_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Same as [`Self::build`], see [`create_inscription_transactions_with_rng`]
+    pub fn build_with_rng<R: RngCore + CryptoRng>(
+        self,
+        rng: &mut R,
+    ) -> Result<(Transaction, TxWithId, Option<UTXO>), anyhow::Error> {
+        let (commit_fee_rate, reveal_fee_rate) = self.fee_rates.context("fee rates not set")?;
+        let compression = if self.envelope_tags {
+            Some(self.compression)
+        } else if self.compression == CompressionScheme::Brotli {
+            None
+        } else {
+            return Err(anyhow!(
+                "the {:?} compression scheme can only be declared with envelope tags",
+                self.compression
+            ));
+        };
+        check_unreserved_extra_tags(&self.extra_tags)?;
+        let mut extra_tags = self.extra_tags;
+        let body = self.body.context("body not set")?;
+        let (encryption, body) = match self.encryption {
+            Some((encryption, key)) => (encryption, encryption.encrypt(&key.0, &body, rng)),
+            None => (EncryptionScheme::None, body),
+        };
+        if encryption != EncryptionScheme::None {
+            extra_tags.push((ENCRYPTION_TAG.to_vec(), vec![encryption.to_byte()]));
+        }
+        let (signature, sequencer_public_key) = match self.signing_key {
+            Some(private_key) => {
+                sign_body_with_private_key(&body, self.compression, encryption, &private_key)?
+            }
+            None if encryption != EncryptionScheme::None => {
+                return Err(anyhow!("encrypted bodies must be signed with `sign_with`"));
+            }
+            None => (
+                self.signature.context("signature not set")?,
+                self.sequencer_public_key
+                    .context("sequencer public key not set")?,
+            ),
+        };
+        create_envelope_transactions(
+            &self.rollup_name.context("rollup name not set")?,
+            &[(body, signature)],
+            compression,
+            sequencer_public_key,
+            self.prev_tx,
+            &self.prev_tx_ancestors,
+            self.utxos.context("utxos not set")?,
+            self.change_address.context("change address not set")?,
+            self.reveal_recipient.context("reveal recipient not set")?,
+            self.reveal_change_address,
+            &self.reveal_extra_outputs,
+            self.reveal_value,
+            self.dust_threshold,
+            commit_fee_rate,
+            reveal_fee_rate,
+            self.min_relay_fee_rate,
+            self.network.context("network not set")?,
+            &self.reveal_prefix,
+            self.max_reveal_prefix_len,
+            self.reveal_difficulty_bits,
+            self.randomize_commit_outputs,
+            self.commit_locktime,
+            self.reveal_sighash_type,
+            &extra_tags,
+            self.allowed_script_types.as_deref(),
+            rng,
+        )
+    }
+}
+
+// Pushes an envelope with the given body and signature to the reveal script.
+// Without a compression scheme, the envelope has neither a version nor a compression tag and is
+// read as brotli compressed, the format every deployed parser accepts. Otherwise both tags are
+// pushed, and extra tags follow as tag and value pairs right before the body tag.
+#[allow(clippy::too_many_arguments)]
+fn push_envelope(
+    reveal_script_builder: script::Builder,
+    rollup_name: &str,
+    signature: &[u8],
+    sequencer_public_key: &[u8],
+    nonce: i64,
+    compression: Option<CompressionScheme>,
+    extra_tags: &[(Vec<u8>, Vec<u8>)],
+    body: &[u8],
+) -> script::Builder {
+    let mut reveal_script_builder = reveal_script_builder
+        .push_opcode(OP_FALSE)
+        .push_opcode(OP_IF)
+        .push_slice(PushBytesBuf::from(ROLLUP_NAME_TAG))
+        .push_slice(
+            PushBytesBuf::try_from(rollup_name.as_bytes().to_vec())
+                .expect("Cannot push rollup name"),
+        );
+    if compression.is_some() {
+        reveal_script_builder = reveal_script_builder
+            .push_slice(PushBytesBuf::from(VERSION_TAG))
+            .push_slice(PushBytesBuf::from(&[ENVELOPE_VERSION]));
+    }
+    reveal_script_builder = reveal_script_builder
+        .push_slice(PushBytesBuf::from(SIGNATURE_TAG))
+        .push_slice(PushBytesBuf::try_from(signature.to_vec()).expect("Cannot push signature"))
+        .push_slice(PushBytesBuf::from(PUBLICKEY_TAG))
+        .push_slice(
+            PushBytesBuf::try_from(sequencer_public_key.to_vec())
+                .expect("Cannot push sequencer public key"),
+        )
+        .push_slice(PushBytesBuf::from(RANDOM_TAG))
+        .push_int(nonce);
+
+    if let Some(compression) = compression {
+        reveal_script_builder = reveal_script_builder
+            .push_slice(PushBytesBuf::from(COMPRESSION_TAG))
+            .push_slice(PushBytesBuf::from(&[compression.to_byte()]));
+        for (tag, value) in extra_tags {
+            reveal_script_builder = reveal_script_builder
+                .push_slice(PushBytesBuf::try_from(tag.clone()).expect("Cannot push extra tag"))
+                .push_slice(
+                    PushBytesBuf::try_from(value.clone()).expect("Cannot push extra tag value"),
+                );
+        }
+    }
+    reveal_script_builder = reveal_script_builder.push_slice(PushBytesBuf::from(BODY_TAG));
+
+    // push body in chunks of 520 bytes
+    for chunk in body.chunks(520) {
+        reveal_script_builder = reveal_script_builder
+            .push_slice(PushBytesBuf::try_from(chunk.to_vec()).expect("Cannot push body chunk"));
+    }
+    // push end if
+    reveal_script_builder.push_opcode(OP_ENDIF)
+}
+
+/// The reveal script split around its only nonce dependent part, the first envelope.
+/// The commit key and the remaining envelopes are serialized once, so that each nonce attempt
+/// only pushes the first envelope.
+struct RevealScriptTemplate<'a> {
+    head: Vec<u8>,
+    rollup_name: &'a str,
+    first_envelope: &'a (Vec<u8>, Vec<u8>),
+    sequencer_public_key: &'a [u8],
+    compression: Option<CompressionScheme>,
+    extra_tags: &'a [(Vec<u8>, Vec<u8>)],
+    tail: Vec<u8>,
+}
+
+impl<'a> RevealScriptTemplate<'a> {
+    fn new(
+        public_key: &XOnlyPublicKey,
+        rollup_name: &'a str,
+        envelopes: &'a [(Vec<u8>, Vec<u8>)],
+        sequencer_public_key: &'a [u8],
+        compression: Option<CompressionScheme>,
+        extra_tags: &'a [(Vec<u8>, Vec<u8>)],
+    ) -> Self {
+        let (first_envelope, rest) = envelopes
+            .split_first()
+            .expect("At least one envelope is required");
+
+        let head = script::Builder::new()
+            .push_x_only_key(public_key)
+            .push_opcode(OP_CHECKSIG)
+            .into_bytes();
+
+        // the random number is in the first envelope only
+        let tail = rest
+            .iter()
+            .fold(script::Builder::new(), |builder, (body, signature)| {
+                push_envelope(
+                    builder,
+                    rollup_name,
+                    signature,
+                    sequencer_public_key,
+                    0,
+                    compression,
+                    extra_tags,
+                    body,
+                )
+            })
+            .into_bytes();
+
+        Self {
+            head,
+            rollup_name,
+            first_envelope,
+            sequencer_public_key,
+            compression,
+            extra_tags,
+            tail,
+        }
+    }
+
+    fn reveal_script(&self, nonce: i64) -> ScriptBuf {
+        let (body, signature) = self.first_envelope;
+        let mut reveal_script = push_envelope(
+            script::Builder::from(self.head.clone()),
+            self.rollup_name,
+            signature,
+            self.sequencer_public_key,
+            nonce,
+            self.compression,
+            self.extra_tags,
+            body,
+        )
+        .into_bytes();
+        reveal_script.extend_from_slice(&self.tail);
+        ScriptBuf::from_bytes(reveal_script)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_envelope_transactions<R: RngCore + CryptoRng>(
+    rollup_name: &str,
+    envelopes: &[(Vec<u8>, Vec<u8>)],
+    compression: Option<CompressionScheme>,
+    sequencer_public_key: Vec<u8>,
+    prev_tx: Option<TxWithId>,
+    prev_tx_ancestors: &[TxWithId],
+    utxos: Vec<UTXO>,
+    change_address: Address,
+    reveal_recipient: Address,
+    reveal_change_address: Option<Address>,
+    reveal_extra_outputs: &[TxOut],
+    reveal_value: u64,
+    dust_threshold: u64,
+    commit_fee_rate: FeeRate,
+    reveal_fee_rate: FeeRate,
+    min_relay_fee_rate: FeeRate,
+    network: Network,
+    reveal_tx_prefix: &[u8],
+    max_reveal_tx_prefix_len: usize,
+    reveal_tx_difficulty_bits: u32,
+    randomize_commit_outputs: bool,
+    commit_locktime: Option<LockTime>,
+    reveal_sighash_type: TapSighashType,
+    extra_tags: &[(Vec<u8>, Vec<u8>)],
+    allowed_script_types: Option<&[ScriptType]>,
+    rng: &mut R,
+) -> Result<(Transaction, TxWithId, Option<UTXO>), anyhow::Error> {
+    // fail before mining, the nonce loop below would never end otherwise
+    check_reveal_tx_prefix(reveal_tx_prefix, max_reveal_tx_prefix_len)?;
+    let max_reveal_tx_difficulty_bits = max_reveal_tx_prefix_len.min(MAX_REVEAL_TX_PREFIX_LEN) * 8;
+    if reveal_tx_difficulty_bits as usize > max_reveal_tx_difficulty_bits {
+        return Err(anyhow!(
+            "reveal tx difficulty of {} bits is above the maximum of {} bits",
+            reveal_tx_difficulty_bits,
+            max_reveal_tx_difficulty_bits
+        ));
+    }
+    check_min_relay_fee_rate(commit_fee_rate, min_relay_fee_rate)?;
+    check_min_relay_fee_rate(reveal_fee_rate, min_relay_fee_rate)?;
+    check_extra_tags(extra_tags)?;
+    if compression.is_none() && !extra_tags.is_empty() {
+        return Err(anyhow!(
+            "extra envelope tags require envelope tags to be written"
+        ));
+    }
+
+    check_address_network("change address", &change_address, network)?;
+    check_address_network("reveal recipient", &reveal_recipient, network)?;
+    if let Some(reveal_change_address) = &reveal_change_address {
+        check_address_network("reveal change address", reveal_change_address, network)?;
+    }
+
+    // the commit tx descends from the previous transaction, so it can't spend
+    // anything the previous transaction or its unconfirmed ancestors already spent
+    if let Some(prev_tx) = &prev_tx {
+        let spent = spent_by_ancestry(&prev_tx.tx, prev_tx_ancestors);
+        if let Some(utxo) = utxos.iter().find(|utxo| {
+            spent.contains(&OutPoint {
+                txid: utxo.tx_id,
+                vout: utxo.vout,
+            })
+        }) {
+            return Err(anyhow!(
+                "UTXO {}:{} is already spent by the previous transaction or its ancestors, spending it again would be a conflicting double spend",
+                utxo.tx_id,
+                utxo.vout
+            ));
+        }
+    }
+
+    // when chaining, the output of the previous transaction may fund the commit on its own
+    if prev_tx.is_none() {
+        if utxos.is_empty() {
+            return Err(anyhow!("no UTXOs to fund the inscription"));
+        }
+        // every body byte weighs at least a quarter vbyte in the reveal witness
+        let body_len: usize = envelopes.iter().map(|(body, _)| body.len()).sum();
+        let required = reveal_value + reveal_fee_rate.fee_for_vsize(body_len / 4);
+        let available = utxos.iter().map(|utxo| utxo.amount).sum();
+        if available < required {
+            return Err(CoinSelectionError::InsufficientFunds {
+                required,
+                available,
+            }
+            .into());
+        }
+    }
+
+    // Create commit key
+    let secp256k1 = Secp256k1::new();
+    let key_pair = UntweakedKeypair::new(&secp256k1, rng);
+    let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+
+    // start creating inscription content
+    let reveal_script_template = RevealScriptTemplate::new(
+        &public_key,
+        rollup_name,
+        envelopes,
+        &sequencer_public_key,
+        compression,
+        extra_tags,
+    );
+
+    // the reveal tx spends a single input to the recipient, only its witness depends on the nonce
+    let reveal_input = TxIn {
+        previous_output: OutPoint {
+            txid: Txid::from_byte_array([0; 32]),
+            vout: 0,
+        },
+        script_sig: script::Builder::new().into_script(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+    };
+    // the extra outputs are funded by the commit output as well
+    let reveal_outputs: Vec<TxOut> = std::iter::once(TxOut {
+        script_pubkey: reveal_recipient.script_pubkey(),
+        value: Amount::from_sat(reveal_value),
+    })
+    .chain(reveal_extra_outputs.iter().cloned())
+    .collect();
+
+    // Start loop to find a 'nonce' i.e. random number that makes the reveal tx hash starting with zeros given length
+    let mut nonce: i64 = 0;
+    loop {
+        if nonce % 10000 == 0 {
+            trace!(nonce, "Trying to find commit & reveal nonce");
+            if nonce > 65536 {
+                warn!("Too many iterations finding nonce");
+            }
+        }
+        let utxos = utxos.clone();
+
+        let reveal_script = reveal_script_template.reveal_script(nonce);
+
+        // create spend info for tapscript
+        let taproot_spend_info = TaprootBuilder::new()
+            .add_leaf(0, reveal_script.clone())
+            .expect("Cannot add reveal script to taptree")
+            .finalize(&secp256k1, public_key)
+            .expect("Cannot finalize taptree");
+
+        // create control block for tapscript
+        let control_block = taproot_spend_info
+            .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+            .expect("Cannot create control block");
+
+        // create commit tx address
+        let commit_tx_address = Address::p2tr(
+            &secp256k1,
+            public_key,
+            taproot_spend_info.merkle_root(),
+            network,
+        );
+
+        let commit_value = commit_output_value(
+            &reveal_input,
+            &reveal_outputs,
+            &reveal_script,
+            &control_block,
+            reveal_sighash_type,
+            reveal_fee_rate,
+        )?;
+
+        // build commit tx
+        let unsigned_commit_tx = build_commit_transaction(
+            prev_tx.clone(),
+            utxos,
+            commit_tx_address.clone(),
+            change_address.clone(),
+            commit_value,
+            dust_threshold,
+            commit_fee_rate,
+            randomize_commit_outputs,
+            commit_locktime,
+            allowed_script_types,
+            rng,
+        )?;
+
+        let commit_vout = find_output(&unsigned_commit_tx, &commit_tx_address)
+            .expect("Commit tx must pay to the commit address");
+        let output_to_reveal = unsigned_commit_tx.output[commit_vout as usize].clone();
+
+        let mut reveal_tx = build_reveal_transaction(
+            output_to_reveal.clone(),
+            unsigned_commit_tx.compute_txid(),
+            commit_vout,
+            reveal_recipient.clone(),
+            reveal_value,
+            dust_threshold,
+            reveal_fee_rate,
+            &reveal_script,
+            &control_block,
+            reveal_sighash_type,
+            reveal_change_address.clone(),
+            reveal_extra_outputs,
+        )?;
+
+        let reveal_tx_id = reveal_tx.compute_txid();
+        let reveal_hash = reveal_tx_id.as_raw_hash().to_byte_array();
+
+        // check if first N bytes equal to the given prefix and the difficulty is met
+        if reveal_hash.starts_with(reveal_tx_prefix)
+            && has_leading_zero_bits(&reveal_hash, reveal_tx_difficulty_bits)
+        {
+            // start signing reveal tx
+            let mut sighash_cache = SighashCache::new(&mut reveal_tx);
+
+            // create data to sign
+            let signature_hash = sighash_cache
+                .taproot_script_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&[output_to_reveal]),
+                    TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
+                    reveal_sighash_type,
+                )
+                .expect("Cannot create hash for signature");
+
+            // sign reveal tx data
+            let signature = secp256k1.sign_schnorr_with_rng(
+                &secp256k1::Message::from_digest_slice(signature_hash.as_byte_array())
+                    .expect("should be cryptographically secure hash"),
+                &key_pair,
+                rng,
+            );
+
+            // add signature to witness and finalize reveal tx
+            // the sighash byte is only appended if it is not the default one
+            let signature = bitcoin::taproot::Signature {
+                signature,
+                sighash_type: reveal_sighash_type,
+            };
+            let witness = sighash_cache.witness_mut(0).unwrap();
+            witness.push(signature.to_vec());
+            witness.push(reveal_script);
+            witness.push(&control_block.serialize());
+
+            // check if inscription locked to the correct address
+            let recovery_key_pair =
+                key_pair.tap_tweak(&secp256k1, taproot_spend_info.merkle_root());
+            let (x_only_pub_key, _parity) = recovery_key_pair.to_inner().x_only_public_key();
+            assert_eq!(
+                Address::p2tr_tweaked(
+                    TweakedPublicKey::dangerous_assume_tweaked(x_only_pub_key),
+                    network,
+                ),
+                commit_tx_address
+            );
+
+            let change_utxo = find_change_utxo(&unsigned_commit_tx, &change_address);
+
+            return Ok((
+                unsigned_commit_tx,
+                TxWithId {
+                    id: reveal_tx_id,
+                    tx: reveal_tx,
+                },
+                change_utxo,
+            ));
+        }
+
+        nonce += 1;
+    }
+}
+
+/// Signs the inputs of a commit transaction spending P2WPKH or P2TR (key path) outputs.
+/// `utxos` must contain the outputs spent by `tx`, and `keys` maps their script pubkeys to private keys.
+pub fn sign_commit_inputs(
+    tx: &mut Transaction,
+    utxos: &[UTXO],
+    keys: &HashMap<ScriptBuf, SecretKey>,
+) -> Result<(), anyhow::Error> {
+    let secp256k1 = Secp256k1::new();
+
+    let prevouts = tx
+        .input
+        .iter()
+        .map(|input| {
+            let utxo = utxos
+                .iter()
+                .find(|utxo| {
+                    utxo.tx_id == input.previous_output.txid
+                        && utxo.vout == input.previous_output.vout
+                })
+                .ok_or(anyhow!("missing UTXO for input {}", input.previous_output))?;
+            Ok(TxOut {
+                value: Amount::from_sat(utxo.amount),
+                script_pubkey: utxo.script_buf()?,
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    let mut sighash_cache = SighashCache::new(&mut *tx);
+    for (index, prevout) in prevouts.iter().enumerate() {
+        let private_key = keys.get(&prevout.script_pubkey).ok_or(anyhow!(
+            "no key for script pubkey {}",
+            prevout.script_pubkey
+        ))?;
+
+        let witness = if prevout.script_pubkey.is_p2wpkh() {
+            let signature_hash = sighash_cache.p2wpkh_signature_hash(
+                index,
+                &prevout.script_pubkey,
+                prevout.value,
+                EcdsaSighashType::All,
+            )?;
+            let signature = secp256k1.sign_ecdsa(
+                &secp256k1::Message::from_digest(signature_hash.to_byte_array()),
+                private_key,
+            );
+            Witness::p2wpkh(
+                &bitcoin::ecdsa::Signature::sighash_all(signature),
+                &secp256k1::PublicKey::from_secret_key(&secp256k1, private_key),
+            )
+        } else if prevout.script_pubkey.is_p2tr() {
+            let signature_hash = sighash_cache.taproot_key_spend_signature_hash(
+                index,
+                &Prevouts::All(&prevouts),
+                TapSighashType::Default,
+            )?;
+            let key_pair = UntweakedKeypair::from_secret_key(&secp256k1, private_key)
+                .tap_tweak(&secp256k1, None)
+                .to_inner();
+            let signature = secp256k1.sign_schnorr_with_rng(
+                &secp256k1::Message::from_digest(signature_hash.to_byte_array()),
+                &key_pair,
+                &mut rand::thread_rng(),
+            );
+            Witness::p2tr_key_spend(&bitcoin::taproot::Signature {
+                signature,
+                sighash_type: TapSighashType::Default,
+            })
+        } else {
+            return Err(anyhow!(
+                "unsupported script pubkey {}",
+                prevout.script_pubkey
+            ));
+        };
+
+        *sighash_cache
+            .witness_mut(index)
+            .expect("input index is in bounds") = witness;
+    }
+
+    Ok(())
+}
+
+pub fn write_reveal_tx(tx: &[u8], tx_id: String) {
+    let reveal_tx_file = File::create(format!("reveal_{}.tx", tx_id)).unwrap();
+    let mut reveal_tx_writer = BufWriter::new(reveal_tx_file);
+    reveal_tx_writer.write_all(tx).unwrap();
+}
+
+/// Commit and reveal transactions of an inscription, ready to be broadcast.
+#[derive(Debug, Clone)]
+pub struct InscriptionPackage {
+    pub commit: Transaction,
+    pub reveal: TxWithId,
+}
+
+impl InscriptionPackage {
+    pub fn new(commit: Transaction, reveal: TxWithId) -> Self {
+        Self { commit, reveal }
+    }
+
+    /// Consensus serialized commit and reveal transactions as hex, e.g. to push them
+    /// through a block explorer.
+    pub fn to_hex(&self) -> (String, String) {
+        (
+            bitcoin::consensus::encode::serialize_hex(&self.commit),
+            bitcoin::consensus::encode::serialize_hex(&self.reveal.tx),
+        )
+    }
+
+    /// Same as [`Self::to_hex`], but paired with the transaction ids in the order the
+    /// transactions must be broadcast in. The reveal spends the commit, so the commit goes first.
+    pub fn to_hex_in_broadcast_order(&self) -> [(Txid, String); 2] {
+        let (commit_hex, reveal_hex) = self.to_hex();
+        [
+            (self.commit.compute_txid(), commit_hex),
+            (self.reveal.id, reveal_hex),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::script::Instruction;
+    use bitcoin::secp256k1::constants::SCHNORR_SIGNATURE_SIZE;
+    use bitcoin::secp256k1::schnorr::Signature;
+    use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+    use bitcoin::sighash::{EcdsaSighashType, SighashCache, TapSighashType};
+    use bitcoin::taproot::ControlBlock;
+    use bitcoin::{
+        Address, Amount, CompressedPublicKey, OutPoint, ScriptBuf, Sequence, TxIn, TxOut, Txid,
+        Witness,
+    };
+
+    use super::{
+        sign_blob_with_private_key, sign_body_with_private_key, BitcoinDaConfig,
+        CoinSelectionError, FeeRate, InscriptionBuilder, InscriptionPackage, TxWithId,
+    };
+    use crate::helpers::compression::{compress_blob, decompress_blob, CompressionScheme};
+    use crate::helpers::encryption::{EncryptionScheme, NONCE_LEN};
+    use crate::helpers::parsers::{
+        parse_batched_transaction, parse_transaction, parse_transaction_with_reveal_value,
+        reveal_commitment_address, verify_reveal_commitment, ParserError,
+    };
+    use crate::helpers::test_signer::{deterministic_keypair, sign_and_build_inscription};
+    use crate::helpers::{COMPRESSION_TAG, VERSION_TAG};
+    use crate::spec::utxo::{ScriptType, UTXO};
+    use crate::{DUST_THRESHOLD, REVEAL_OUTPUT_AMOUNT};
+
+    #[test]
+    fn compression_decompression() {
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
+
+        // compress and measure time
+        let time = std::time::Instant::now();
+        let compressed_blob = compress_blob(&blob);
+        println!("compression time: {:?}", time.elapsed());
+
+        // decompress and measure time
+        let time = std::time::Instant::now();
+        let decompressed_blob = decompress_blob(&compressed_blob).unwrap();
+        println!("decompression time: {:?}", time.elapsed());
+
+        assert_eq!(blob, decompressed_blob);
+
+        // size
+        println!("blob size: {}", blob.len());
+        println!("compressed blob size: {}", compressed_blob.len());
+        println!(
+            "compression ratio: {}",
+            (blob.len() as f64) / (compressed_blob.len() as f64)
+        );
+    }
+
+    #[test]
+    fn sign_blob_with_private_key_returns_compact_signature() {
+        let (signature, public_key) = super::sign_blob_with_private_key(
+            &[1, 2, 3],
+            &SecretKey::from_slice(&[5; 32]).unwrap(),
+        )
+        .unwrap();
+
+        // compact signature and compressed public key
+        assert_eq!(signature.len(), 64);
+        assert_eq!(public_key.len(), 33);
+        assert!(PublicKey::from_slice(&public_key).is_ok());
+    }
+
+    #[test]
+    fn write_reveal_tx() {
+        let tx = vec![100, 100, 100];
+        let tx_id = "test_tx".to_string();
+
+        super::write_reveal_tx(tx.as_slice(), tx_id);
+
+        let file = std::fs::read("reveal_test_tx.tx").unwrap();
+
+        assert_eq!(tx, file);
+
+        std::fs::remove_file("reveal_test_tx.tx").unwrap();
+    }
+
+    #[test]
+    fn inscription_package_hex_round_trip() {
+        let (commit, reveal, _) = mock_builder().build().unwrap();
+        let package = InscriptionPackage::new(commit.clone(), reveal.clone());
+
+        let (commit_hex, reveal_hex) = package.to_hex();
+        let decode = |tx_hex: &str| -> bitcoin::Transaction {
+            bitcoin::consensus::deserialize(&hex::decode(tx_hex).unwrap()).unwrap()
+        };
+        assert_eq!(decode(&commit_hex), commit);
+        assert_eq!(decode(&reveal_hex), reveal.tx);
+
+        let [(first_id, first_hex), (second_id, second_hex)] = package.to_hex_in_broadcast_order();
+        assert_eq!(first_id, commit.compute_txid());
+        assert_eq!(first_hex, commit_hex);
+        assert_eq!(second_id, reveal.id);
+        assert_eq!(second_hex, reveal_hex);
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn get_mock_data() -> (&'static str, Vec<u8>, Vec<u8>, Vec<u8>, Address, Vec<UTXO>) {
+        let rollup_name = "test_rollup";
+        let body = vec![100; 1000];
+        let signature = vec![100; 64];
+        let sequencer_public_key = vec![100; 33];
+        let address =
+            Address::from_str("bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let utxos = vec![
+            UTXO {
+                tx_id: Txid::from_str(
+                    "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+                )
+                .unwrap(),
+                vout: 0,
+                address: Some(
+                    Address::from_str(
                         "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
                     )
                     .unwrap(),
@@ -621,10 +2397,417 @@ mod tests {
                 confirmations: 100,
                 spendable: true,
                 solvable: true,
-            },
-            UTXO {
+            },
+            UTXO {
+                tx_id: Txid::from_str(
+                    "4dbe3c10ee0d6bf16f9417c68b81e963b5bccef3924bbcb0885c9ea841912325",
+                )
+                .unwrap(),
+                vout: 0,
+                address: Some(
+                    Address::from_str(
+                        "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+                    )
+                    .unwrap(),
+                ),
+                script_pubkey: address.script_pubkey().to_hex_string(),
+                amount: 10_000,
+                confirmations: 100,
+                spendable: true,
+                solvable: true,
+            },
+        ];
+
+        (
+            rollup_name,
+            body,
+            signature,
+            sequencer_public_key,
+            address,
+            utxos,
+        )
+    }
+
+    // Builder inscribing the mock data, ready to build
+    fn mock_builder() -> InscriptionBuilder {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        InscriptionBuilder::new()
+            .rollup_name(rollup_name)
+            .body(body)
+            .signature(signature)
+            .sequencer_public_key(sequencer_public_key)
+            .utxos(utxos)
+            .change_address(address.clone())
+            .reveal_recipient(address)
+            .fee_rates(
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+            )
+            .network(bitcoin::Network::Bitcoin)
+    }
+
+    #[test]
+    fn choose_utxos() {
+        let (_, _, _, _, _, utxos) = get_mock_data();
+
+        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 105_000, None).unwrap();
+
+        assert_eq!(sum, 1_000_000);
+        assert_eq!(chosen_utxos.len(), 1);
+        assert_eq!(chosen_utxos[0], utxos[0]);
+
+        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 1_005_000, None).unwrap();
+
+        assert_eq!(sum, 1_100_000);
+        assert_eq!(chosen_utxos.len(), 2);
+        assert_eq!(chosen_utxos[0], utxos[0]);
+        assert_eq!(chosen_utxos[1], utxos[1]);
+
+        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 100_000, None).unwrap();
+
+        assert_eq!(sum, 100_000);
+        assert_eq!(chosen_utxos.len(), 1);
+        assert_eq!(chosen_utxos[0], utxos[1]);
+
+        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 90_000, None).unwrap();
+
+        assert_eq!(sum, 100_000);
+        assert_eq!(chosen_utxos.len(), 1);
+        assert_eq!(chosen_utxos[0], utxos[1]);
+
+        let res = super::choose_utxos(None, &utxos, 100_000_000, None);
+
+        assert!(res.is_err());
+        assert_eq!(format!("{}", res.unwrap_err()), "not enough UTXOs");
+    }
+
+    #[test]
+    fn choose_utxos_insufficient_funds() {
+        let (_, _, _, _, _, utxos) = get_mock_data();
+        let total: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+
+        let err = super::choose_utxos(None, &utxos, 100_000_000, None).unwrap_err();
+        assert_eq!(
+            err,
+            CoinSelectionError::InsufficientFunds {
+                required: 100_000_000,
+                available: total,
+            }
+        );
+
+        let required_utxo = utxos[0].clone();
+        let err = super::choose_utxos(Some(required_utxo.clone()), &utxos[1..], 100_000_000, None)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            CoinSelectionError::InsufficientFunds {
+                required: 100_000_000,
+                available: total,
+            }
+        );
+    }
+
+    #[test]
+    fn choose_utxos_allowed_script_types() {
+        let (_, _, _, _, _, mut utxos) = get_mock_data();
+        // the big UTXO is P2PKH, the others P2TR
+        utxos[0].script_pubkey = "76a914b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d88ac".to_string();
+        assert_eq!(utxos[0].script_type(), Some(ScriptType::P2pkh));
+        assert_eq!(utxos[1].script_type(), Some(ScriptType::P2tr));
+
+        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 105_000, None).unwrap();
+        assert_eq!(sum, 1_000_000);
+        assert_eq!(chosen_utxos, vec![utxos[0].clone()]);
+
+        let (chosen_utxos, sum) =
+            super::choose_utxos(None, &utxos, 105_000, Some(&[ScriptType::P2tr])).unwrap();
+        assert_eq!(sum, 110_000);
+        assert_eq!(chosen_utxos, vec![utxos[1].clone(), utxos[2].clone()]);
+
+        let err =
+            super::choose_utxos(None, &utxos, 200_000, Some(&[ScriptType::P2tr])).unwrap_err();
+        assert_eq!(
+            err,
+            CoinSelectionError::InsufficientFunds {
+                required: 200_000,
+                available: 110_000,
+            }
+        );
+    }
+
+    #[test]
+    fn inscription_builder_allowed_script_types() {
+        let (_, _, _, _, _, mut utxos) = get_mock_data();
+        // only the big UTXO is P2TR
+        for utxo in &mut utxos[1..] {
+            utxo.script_pubkey = "76a914b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d88ac".to_string();
+        }
+        let outpoint = |utxo: &UTXO| OutPoint {
+            txid: utxo.tx_id,
+            vout: utxo.vout,
+        };
+
+        // the smallest sufficient UTXO is chosen by default
+        let (commit, _, _) = mock_builder().utxos(utxos.clone()).build().unwrap();
+        assert_eq!(commit.input.len(), 1);
+        assert_eq!(commit.input[0].previous_output, outpoint(&utxos[2]));
+
+        let config = BitcoinDaConfig {
+            allowed_script_types: Some(vec![ScriptType::P2tr]),
+            ..BitcoinDaConfig::new(
+                bitcoin::Network::Bitcoin,
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+            )
+        };
+        let (commit, _, _) = mock_builder()
+            .utxos(utxos.clone())
+            .config(&config)
+            .build()
+            .unwrap();
+        assert_eq!(commit.input.len(), 1);
+        assert_eq!(commit.input[0].previous_output, outpoint(&utxos[0]));
+
+        let err = mock_builder()
+            .utxos(utxos)
+            .allowed_script_types(Some(vec![ScriptType::P2wpkh]))
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CoinSelectionError>(),
+            Some(CoinSelectionError::InsufficientFunds { available: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn build_commit_transaction() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let mut tx = super::build_commit_transaction(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        tx.input[0].witness.push(
+            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                .unwrap()
+                .as_ref(),
+        );
+
+        // 154 vB * 8 sat/vB = 1232 sats
+        // 5_000 + 1232 = 6232
+        // input: 10000
+        // outputs: 5_000 + 3.768
+        assert_eq!(tx.vsize(), 154);
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
+        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+        assert_eq!(tx.output[1].value, Amount::from_sat(3_768));
+        assert_eq!(tx.output[1].script_pubkey, address.script_pubkey());
+
+        let mut tx = super::build_commit_transaction(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(45.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        tx.input[0].witness.push(
+            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                .unwrap()
+                .as_ref(),
+        );
+
+        // 111 vB * 45 sat/vB = 4.995 sats
+        // 5_000 + 4928 = 9995
+        // input: 10000
+        // outputs: 5_000
+        assert_eq!(tx.vsize(), 111);
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
+        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+
+        let mut tx = super::build_commit_transaction(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(32.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        tx.input[0].witness.push(
+            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                .unwrap()
+                .as_ref(),
+        );
+
+        // you expect
+        // 154 vB * 32 sat/vB = 4.928 sats
+        // 5_000 + 4928 = 9928
+        // input: 10000
+        // outputs: 5_000 72
+        // instead do
+        // input: 10000
+        // outputs: 5_000
+        // so size is actually 111
+        assert_eq!(tx.vsize(), 111);
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
+        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+
+        let mut tx = super::build_commit_transaction(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            1_050_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(5.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        tx.input[0].witness.push(
+            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                .unwrap()
+                .as_ref(),
+        );
+        tx.input[1].witness.push(
+            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                .unwrap()
+                .as_ref(),
+        );
+
+        // 212 vB * 5 sat/vB = 1060 sats
+        // 1_050_000 + 1060 = 1_051_060
+        // inputs: 1_000_000 100_000
+        // outputs: 1_050_000 8940
+        assert_eq!(tx.vsize(), 212);
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0].value, Amount::from_sat(1_050_000));
+        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+        assert_eq!(tx.output[1].value, Amount::from_sat(48940));
+        assert_eq!(tx.output[1].script_pubkey, address.script_pubkey());
+
+        let prev_tx = tx;
+        let prev_tx_id = prev_tx.compute_txid();
+        let tx = super::build_commit_transaction(
+            Some(super::TxWithId {
+                id: prev_tx_id,
+                tx: prev_tx.clone(),
+            }),
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            100_000_000_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(32.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        );
+
+        assert!(tx.is_err());
+        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+
+        let prev_utxos: Vec<UTXO> = prev_tx
+            .output
+            .iter()
+            .enumerate()
+            .map(|(i, o)| UTXO {
+                tx_id: prev_tx_id,
+                vout: i as u32,
+                script_pubkey: o.script_pubkey.to_hex_string(),
+                address: None,
+                confirmations: 0,
+                amount: o.value.to_sat(),
+                spendable: true,
+                solvable: true,
+            })
+            .collect();
+        let prev_utxo = utxos.clone().into_iter().chain(prev_utxos).collect();
+
+        let tx = super::build_commit_transaction(
+            Some(super::TxWithId {
+                id: prev_tx_id,
+                tx: prev_tx,
+            }),
+            prev_utxo,
+            recipient.clone(),
+            address.clone(),
+            50000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(32.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output.txid, prev_tx_id);
+
+        let tx = super::build_commit_transaction(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            100_000_000_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(32.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        );
+
+        assert!(tx.is_err());
+        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+
+        let tx = super::build_commit_transaction(
+            None,
+            vec![UTXO {
                 tx_id: Txid::from_str(
-                    "4dbe3c10ee0d6bf16f9417c68b81e963b5bccef3924bbcb0885c9ea841912325",
+                    "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
                 )
                 .unwrap(),
                 vout: 0,
@@ -635,275 +2818,976 @@ mod tests {
                     .unwrap(),
                 ),
                 script_pubkey: address.script_pubkey().to_hex_string(),
-                amount: 10_000,
+                amount: 152,
                 confirmations: 100,
                 spendable: true,
                 solvable: true,
-            },
-        ];
+            }],
+            recipient.clone(),
+            address.clone(),
+            100_000_000_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(32.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        );
+
+        assert!(tx.is_err());
+        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+    }
+
+    #[test]
+    fn expected_nonce_attempts() {
+        assert_eq!(super::expected_nonce_attempts(&[]), 1.0);
+        assert_eq!(super::expected_nonce_attempts(&[0]), 256.0);
+        assert_eq!(super::expected_nonce_attempts(&[0, 0]), 65536.0);
+    }
+
+    #[test]
+    fn fee_rate_units() {
+        let per_vb = FeeRate::from_sat_per_vb(12.0).unwrap();
+        let per_kvb = FeeRate::from_sat_per_kvb(12_000.0).unwrap();
+        let btc_per_kvb = FeeRate::from_btc_per_kvb(0.00012).unwrap();
+
+        assert_eq!(per_vb, per_kvb);
+        assert_eq!(per_vb, btc_per_kvb);
+        for vsize in [1, 150, 1_000, 99_999] {
+            assert_eq!(per_vb.fee_for_vsize(vsize), per_kvb.fee_for_vsize(vsize));
+            assert_eq!(
+                per_vb.fee_for_vsize(vsize),
+                btc_per_kvb.fee_for_vsize(vsize)
+            );
+        }
+        assert_eq!(per_vb.fee_for_vsize(150), 1_800);
+
+        // fractional rates are rounded up per transaction
+        assert_eq!(
+            FeeRate::from_sat_per_kvb(1_500.0).unwrap().fee_for_vsize(3),
+            5
+        );
+
+        assert_eq!(FeeRate::from_sat_per_vb(0.0).unwrap().fee_for_vsize(150), 0);
+        assert_eq!(
+            FeeRate::from_sat_per_vb(-1.0),
+            Err(super::InvalidFeeRate(-1.0))
+        );
+        assert!(FeeRate::from_sat_per_vb(f64::NAN).is_err());
+        assert!(FeeRate::from_sat_per_kvb(f64::INFINITY).is_err());
+        assert!(FeeRate::from_btc_per_kvb(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn chaining_with_randomized_commit_outputs() {
+        let (_, _, _, _, address, _) = get_mock_data();
+
+        let builder = mock_builder().randomize_commit_outputs(true);
+
+        let mut commit_positions = [0; 2];
+        for _ in 0..64 {
+            let (commit, reveal, _) = builder.clone().build().unwrap();
+
+            // the reveal spends the commit output wherever it ended up
+            assert_eq!(commit.output.len(), 2);
+            let commit_outpoint = reveal.tx.input[0].previous_output;
+            assert_eq!(commit_outpoint.txid, commit.compute_txid());
+            let vout = commit_outpoint.vout as usize;
+            assert_ne!(commit.output[vout].script_pubkey, address.script_pubkey());
+            assert_eq!(
+                commit.output[1 - vout].script_pubkey,
+                address.script_pubkey()
+            );
+            commit_positions[vout] += 1;
+
+            // the next commit continues the chain from the single reveal output
+            let (next_commit, _, _) = builder
+                .clone()
+                .prev_tx(Some(reveal.clone()))
+                .build()
+                .unwrap();
+            assert_eq!(
+                next_commit.input[0].previous_output,
+                OutPoint {
+                    txid: reveal.id,
+                    vout: 0,
+                }
+            );
+        }
+
+        // both orderings occur
+        assert!(commit_positions[0] > 0);
+        assert!(commit_positions[1] > 0);
+    }
+
+    #[test]
+    fn build_commit_transaction_locktime() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+
+        let locktime = LockTime::from_height(850_000).unwrap();
+        let tx = super::build_commit_transaction(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            Some(locktime),
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        assert_eq!(tx.lock_time, locktime);
+        assert!(tx.is_explicitly_rbf());
+        assert!(tx
+            .input
+            .iter()
+            .all(|input| input.sequence.enables_absolute_lock_time()));
+
+        let tx = super::build_commit_transaction(
+            None,
+            utxos,
+            recipient,
+            address,
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        assert_eq!(tx.lock_time, LockTime::ZERO);
+    }
+
+    #[test]
+    fn build_commit_transaction_chained_iterations() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+
+        let (prev_tx, unchained_stats) = super::build_commit_transaction_with_stats(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        let prev_tx_id = prev_tx.compute_txid();
+        // the UTXO spent by the previous commit is gone
+        let unspent_utxos = utxos
+            .iter()
+            .filter(|utxo| {
+                prev_tx
+                    .input
+                    .iter()
+                    .all(|input| input.previous_output.txid != utxo.tx_id)
+            })
+            .cloned()
+            .collect();
+        let (tx, chained_stats) = super::build_commit_transaction_with_stats(
+            Some(super::TxWithId {
+                id: prev_tx_id,
+                tx: prev_tx,
+            }),
+            unspent_utxos,
+            recipient.clone(),
+            address.clone(),
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.input[0].previous_output.txid, prev_tx_id);
+        assert_eq!(tx.output.len(), 2);
+        assert!(chained_stats.iterations < unchained_stats.iterations);
+    }
+
+    #[test]
+    fn affordable_change_of_direct_return() {
+        let (_, _, _, _, address, _) = get_mock_data();
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let inputs = [TxIn::default()];
+        let recipient_output = TxOut {
+            value: Amount::from_sat(5_000),
+            script_pubkey: recipient.script_pubkey(),
+        };
+        let affordable_change = |sum, fee_rate| {
+            super::affordable_change(
+                &inputs,
+                &recipient_output,
+                &address,
+                sum,
+                FeeRate::from_sat_per_vb(fee_rate).unwrap(),
+                DUST_THRESHOLD,
+            )
+            .unwrap()
+        };
+
+        // a fee estimated for two inputs: 169 vB * 45 sat/vB = 7_605 sats
+        // leaves 100 sats, too little for change, so the single input transaction
+        // of 111 vB would overpay by 58 vB * 45 sat/vB = 2_610 sats.
+        // with change it is 154 vB * 45 sat/vB = 6_930 sats
+        // 12_705 - 5_000 - 6_930 = 775
+        assert_eq!(affordable_change(12_705, 45.0), Some(775));
+
+        // 154 vB * 32 sat/vB = 4_928 sats leave a change of 72 sats,
+        // which is dust so the overpayment stays with the fee
+        assert_eq!(affordable_change(10_000, 32.0), None);
+        // 154 vB * 8 sat/vB = 1_232 sats
+        assert_eq!(affordable_change(10_000, 8.0), Some(3_768));
+        // less than the recipient output and the fee
+        assert_eq!(affordable_change(5_000, 8.0), None);
+    }
+
+    #[test]
+    fn build_commit_transaction_stats() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(8.0).unwrap();
+
+        let (tx, stats) = super::build_commit_transaction_with_stats(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address,
+            5_000,
+            DUST_THRESHOLD,
+            fee_rate,
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        assert!(
+            stats.iterations <= 3,
+            "took {} iterations",
+            stats.iterations
+        );
+        let size = super::get_size(&tx.input, &tx.output, None, None);
+        assert_eq!(stats.final_vsize, size);
+
+        let input_value: u64 = tx
+            .input
+            .iter()
+            .map(|input| {
+                utxos
+                    .iter()
+                    .find(|utxo| utxo.tx_id == input.previous_output.txid)
+                    .unwrap()
+                    .amount
+            })
+            .sum();
+        let output_value: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+        assert_eq!(
+            input_value - output_value,
+            fee_rate.fee_for_vsize(stats.final_vsize)
+        );
+    }
+
+    #[test]
+    fn build_commit_transaction_rejects_utxo_spent_by_prev_tx() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+
+        let prev_tx = super::build_commit_transaction(
+            None,
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        let spent = prev_tx.input[0].previous_output;
+
+        // the prev tx output alone can't pay the fee, and the smallest UTXO
+        // that can is the one the prev tx already spends
+        let err = super::build_commit_transaction(
+            Some(super::TxWithId {
+                id: prev_tx.compute_txid(),
+                tx: prev_tx,
+            }),
+            utxos,
+            recipient,
+            address,
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().starts_with(&format!(
+            "UTXO {}:{} is already spent",
+            spent.txid, spent.vout
+        )));
+    }
+
+    #[test]
+    fn keypath_commitment_has_smaller_witness() {
+        let (rollup_name, _, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let data = [7u8; 32];
+        let fee_rate = FeeRate::from_sat_per_vb(8.0).unwrap();
+
+        let mut tx = super::create_keypath_commitment(
+            &data,
+            utxos.clone(),
+            address.clone(),
+            DUST_THRESHOLD,
+            fee_rate,
+            bitcoin::Network::Bitcoin,
+            FeeRate::MIN_RELAY,
+        )
+        .unwrap();
 
-        (
+        assert_eq!(tx.output[0].value, Amount::ZERO);
+        assert!(tx.output[0].script_pubkey.is_op_return());
+        assert_eq!(&tx.output[0].script_pubkey.as_bytes()[2..], data.as_slice());
+
+        // key path spends only carry a signature
+        for input in tx.input.iter_mut() {
+            input.witness.push([0; SCHNORR_SIGNATURE_SIZE]);
+        }
+        let input_total: u64 = tx
+            .input
+            .iter()
+            .map(|input| {
+                utxos
+                    .iter()
+                    .find(|utxo| utxo.tx_id == input.previous_output.txid)
+                    .unwrap()
+                    .amount
+            })
+            .sum();
+        let output_total: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+        assert!(input_total - output_total >= tx.vsize() as u64 * 8);
+
+        let (commit, reveal, _) = super::create_inscription_transactions(
             rollup_name,
-            body,
+            data.to_vec(),
             signature,
             sequencer_public_key,
-            address,
+            None,
             utxos,
+            address.clone(),
+            address,
+            REVEAL_OUTPUT_AMOUNT,
+            DUST_THRESHOLD,
+            fee_rate,
+            fee_rate,
+            bitcoin::Network::Bitcoin,
+            &[],
         )
+        .unwrap();
+
+        assert!(tx.input[0].witness.size() < reveal.tx.input[0].witness.size());
+        // the commit is unsigned here, which only makes the tapscript path look cheaper
+        assert!(tx.vsize() < commit.vsize() + reveal.tx.vsize());
     }
 
     #[test]
-    fn choose_utxos() {
-        let (_, _, _, _, _, utxos) = get_mock_data();
+    fn keypath_commitment_rejects_large_data() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
 
-        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 105_000).unwrap();
+        let result = super::create_keypath_commitment(
+            &[0; super::MAX_OP_RETURN_DATA_LEN + 1],
+            utxos,
+            address,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            FeeRate::MIN_RELAY,
+        );
+        assert!(result.is_err());
+    }
 
-        assert_eq!(sum, 1_000_000);
-        assert_eq!(chosen_utxos.len(), 1);
-        assert_eq!(chosen_utxos[0], utxos[0]);
+    #[test]
+    fn keypath_commitment_only_spends_taproot_utxos() {
+        let (_, _, _, _, address, mut utxos) = get_mock_data();
+        // the big UTXO is P2PKH, which a key path signature can't spend
+        utxos[0].script_pubkey = "76a914b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d88ac".to_string();
 
-        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 1_005_000).unwrap();
+        let tx = super::create_keypath_commitment(
+            &[7; 32],
+            utxos.clone(),
+            address,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            FeeRate::MIN_RELAY,
+        )
+        .unwrap();
+        assert!(tx
+            .input
+            .iter()
+            .all(|input| input.previous_output.txid != utxos[0].tx_id));
+    }
 
-        assert_eq!(sum, 1_100_000);
-        assert_eq!(chosen_utxos.len(), 2);
-        assert_eq!(chosen_utxos[0], utxos[0]);
-        assert_eq!(chosen_utxos[1], utxos[1]);
+    #[test]
+    fn select_utxos_with_retry_refetches_utxos() {
+        let (_, _, _, _, _, utxos) = get_mock_data();
 
-        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 100_000).unwrap();
+        let calls = std::cell::Cell::new(0);
+        let provider = || {
+            calls.set(calls.get() + 1);
+            // the UTXOs are locked by another build for the first two attempts
+            if calls.get() <= 2 {
+                vec![]
+            } else {
+                utxos.clone()
+            }
+        };
 
-        assert_eq!(sum, 100_000);
-        assert_eq!(chosen_utxos.len(), 1);
-        assert_eq!(chosen_utxos[0], utxos[1]);
+        let (chosen, sum) =
+            super::select_utxos_with_retry(provider, 50_000, 2, Duration::ZERO).unwrap();
+        assert_eq!(calls.get(), 3);
+        assert!(sum >= 50_000);
+        assert_eq!(sum, chosen.iter().map(|utxo| utxo.amount).sum::<u64>());
 
-        let (chosen_utxos, sum) = super::choose_utxos(None, &utxos, 90_000).unwrap();
+        calls.set(0);
+        assert_eq!(
+            super::select_utxos_with_retry(provider, 50_000, 1, Duration::ZERO),
+            Err(CoinSelectionError::InsufficientFunds {
+                required: 50_000,
+                available: 0
+            })
+        );
+        assert_eq!(calls.get(), 2);
+    }
 
-        assert_eq!(sum, 100_000);
-        assert_eq!(chosen_utxos.len(), 1);
-        assert_eq!(chosen_utxos[0], utxos[1]);
+    #[test]
+    fn inscription_rejects_utxo_spent_by_prev_tx_ancestors() {
+        let (_, _, _, _, _, utxos) = get_mock_data();
 
-        let res = super::choose_utxos(None, &utxos, 100_000_000);
+        let builder = mock_builder();
+        let (commit, reveal, _) = builder.clone().build().unwrap();
+        let commit = super::TxWithId {
+            id: commit.compute_txid(),
+            tx: commit,
+        };
 
-        assert!(res.is_err());
-        assert_eq!(format!("{}", res.unwrap_err()), "not enough UTXOs");
+        // the reveal only spends the commit, the UTXOs the commit spent are found through it
+        let chained = builder.prev_tx(Some(reveal));
+        let err = chained
+            .clone()
+            .utxos(utxos.clone())
+            .prev_tx_ancestors(vec![commit.clone()])
+            .build()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("already spent by the previous transaction or its ancestors"));
+
+        let unspent_utxos: Vec<UTXO> = utxos
+            .into_iter()
+            .filter(|utxo| {
+                commit.tx.input.iter().all(|input| {
+                    input.previous_output
+                        != OutPoint {
+                            txid: utxo.tx_id,
+                            vout: utxo.vout,
+                        }
+                })
+            })
+            .collect();
+        chained
+            .utxos(unspent_utxos)
+            .prev_tx_ancestors(vec![commit])
+            .build()
+            .unwrap();
     }
 
     #[test]
-    fn build_commit_transaction() {
+    fn build_consolidation_transaction() {
         let (_, _, _, _, address, utxos) = get_mock_data();
 
-        let recipient =
-            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
-                .unwrap()
-                .require_network(bitcoin::Network::Bitcoin)
-                .unwrap();
-        let mut tx = super::build_commit_transaction(
-            None,
+        let tx = super::build_consolidation_transaction(
             utxos.clone(),
-            recipient.clone(),
             address.clone(),
-            5_000,
-            8.0,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            10,
         )
         .unwrap();
 
-        tx.input[0].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
-        );
+        assert_eq!(tx.input.len(), 3);
+        // smallest first
+        assert_eq!(tx.input[0].previous_output.txid, utxos[2].tx_id);
+        assert_eq!(tx.input[1].previous_output.txid, utxos[1].tx_id);
+        assert_eq!(tx.input[2].previous_output.txid, utxos[0].tx_id);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].script_pubkey, address.script_pubkey());
 
-        // 154 vB * 8 sat/vB = 1232 sats
-        // 5_000 + 1232 = 6232
-        // input: 10000
-        // outputs: 5_000 + 3.768
-        assert_eq!(tx.vsize(), 154);
-        assert_eq!(tx.input.len(), 1);
-        assert_eq!(tx.output.len(), 2);
-        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
-        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
-        assert_eq!(tx.output[1].value, Amount::from_sat(3_768));
-        assert_eq!(tx.output[1].script_pubkey, address.script_pubkey());
+        let input_total: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+        let size = super::get_size(&tx.input, &tx.output, None, None);
+        assert_eq!(
+            tx.output[0].value.to_sat(),
+            input_total - FeeRate::from_sat_per_vb(8.0).unwrap().fee_for_vsize(size)
+        );
 
-        let mut tx = super::build_commit_transaction(
-            None,
+        let tx = super::build_consolidation_transaction(
             utxos.clone(),
-            recipient.clone(),
             address.clone(),
-            5_000,
-            45.0,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            2,
         )
         .unwrap();
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.input[1].previous_output.txid, utxos[1].tx_id);
 
-        tx.input[0].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
+        // the fee is higher than the swept value
+        let tx = super::build_consolidation_transaction(
+            utxos.clone(),
+            address.clone(),
+            FeeRate::from_sat_per_vb(100.0).unwrap(),
+            1,
         );
+        assert!(tx.is_err());
+    }
 
-        // 111 vB * 45 sat/vB = 4.995 sats
-        // 5_000 + 4928 = 9995
-        // input: 10000
-        // outputs: 5_000
-        assert_eq!(tx.vsize(), 111);
-        assert_eq!(tx.input.len(), 1);
-        assert_eq!(tx.output.len(), 1);
-        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
-        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+    #[test]
+    fn create_batched_inscription() {
+        let (rollup_name, _, _, _, address, utxos) = get_mock_data();
 
-        let mut tx = super::build_commit_transaction(
+        let private_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let bodies = vec![vec![1; 100], vec![2; 1_000], vec![3; 10]];
+        let (signatures, public_keys): (Vec<_>, Vec<_>) = bodies
+            .iter()
+            .map(|body| {
+                sign_body_with_private_key(
+                    body,
+                    CompressionScheme::Brotli,
+                    EncryptionScheme::None,
+                    &private_key,
+                )
+                .unwrap()
+            })
+            .unzip();
+
+        let (_, reveal, _) = super::create_batched_inscription(
+            rollup_name,
+            bodies.clone(),
+            signatures.clone(),
+            public_keys[0].clone(),
             None,
             utxos.clone(),
-            recipient.clone(),
             address.clone(),
-            5_000,
-            32.0,
+            address.clone(),
+            546,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            &[0],
         )
         .unwrap();
+        assert!(reveal.id.as_byte_array().starts_with(&[0]));
+
+        let inscriptions = parse_batched_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(inscriptions.len(), 3);
+        for ((inscription, body), signature) in inscriptions.iter().zip(&bodies).zip(&signatures) {
+            assert_eq!(&inscription.body, body);
+            assert_eq!(&inscription.signature, signature);
+            assert_eq!(inscription.public_key, public_keys[0]);
+            assert!(inscription.get_sig_verified_hash().is_some());
+        }
 
-        tx.input[0].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
-        );
+        // a regular parse sees the first envelope
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(inscription.body, bodies[0]);
 
-        // you expect
-        // 154 vB * 32 sat/vB = 4.928 sats
-        // 5_000 + 4928 = 9928
-        // input: 10000
-        // outputs: 5_000 72
-        // instead do
-        // input: 10000
-        // outputs: 5_000
-        // so size is actually 111
-        assert_eq!(tx.vsize(), 111);
-        assert_eq!(tx.input.len(), 1);
-        assert_eq!(tx.output.len(), 1);
-        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
-        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+        // missing signatures
+        assert!(super::create_batched_inscription(
+            rollup_name,
+            bodies,
+            signatures[..2].to_vec(),
+            public_keys[0].clone(),
+            None,
+            utxos,
+            address.clone(),
+            address,
+            546,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            &[0],
+        )
+        .is_err());
+    }
 
-        let mut tx = super::build_commit_transaction(
+    #[test]
+    fn create_inscription_transactions_separate_recipients() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let reveal_recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+
+        let (commit, reveal, _) = super::create_inscription_transactions(
+            rollup_name,
+            body.clone(),
+            signature.clone(),
+            sequencer_public_key.clone(),
             None,
             utxos.clone(),
-            recipient.clone(),
             address.clone(),
-            1_050_000,
-            5.0,
+            reveal_recipient.clone(),
+            546,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            &[],
         )
         .unwrap();
 
-        tx.input[0].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
-        );
-        tx.input[1].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
+        assert_eq!(commit.output.len(), 2);
+        assert_eq!(commit.output[1].script_pubkey, address.script_pubkey());
+        assert_eq!(reveal.tx.output.len(), 1);
+        assert_eq!(
+            reveal.tx.output[0].script_pubkey,
+            reveal_recipient.script_pubkey()
         );
 
-        // 212 vB * 5 sat/vB = 1060 sats
-        // 1_050_000 + 1060 = 1_051_060
-        // inputs: 1_000_000 100_000
-        // outputs: 1_050_000 8940
-        assert_eq!(tx.vsize(), 212);
-        assert_eq!(tx.input.len(), 2);
-        assert_eq!(tx.output.len(), 2);
-        assert_eq!(tx.output[0].value, Amount::from_sat(1_050_000));
-        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
-        assert_eq!(tx.output[1].value, Amount::from_sat(48940));
-        assert_eq!(tx.output[1].script_pubkey, address.script_pubkey());
-
-        let prev_tx = tx;
-        let prev_tx_id = prev_tx.compute_txid();
-        let tx = super::build_commit_transaction(
-            Some(super::TxWithId {
-                id: prev_tx_id,
-                tx: prev_tx.clone(),
-            }),
-            utxos.clone(),
-            recipient.clone(),
-            address.clone(),
-            100_000_000_000,
-            32.0,
+        // the reveal recipient must be on the network of the inscription
+        let testnet_recipient = Address::p2wpkh(
+            &CompressedPublicKey(PublicKey::from_secret_key(
+                &Secp256k1::new(),
+                &SecretKey::from_slice(&[7; 32]).unwrap(),
+            )),
+            bitcoin::Network::Testnet,
         );
+        assert!(super::create_inscription_transactions(
+            rollup_name,
+            body,
+            signature,
+            sequencer_public_key,
+            None,
+            utxos,
+            address,
+            testnet_recipient,
+            546,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            &[],
+        )
+        .is_err());
+    }
 
-        assert!(tx.is_err());
-        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+    #[test]
+    fn create_inscription_transactions_network_mismatch() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
 
-        let prev_utxos: Vec<UTXO> = prev_tx
-            .output
-            .iter()
-            .enumerate()
-            .map(|(i, o)| UTXO {
-                tx_id: prev_tx_id,
-                vout: i as u32,
-                script_pubkey: o.script_pubkey.to_hex_string(),
-                address: None,
-                confirmations: 0,
-                amount: o.value.to_sat(),
-                spendable: true,
-                solvable: true,
-            })
-            .collect();
-        let prev_utxo = utxos.clone().into_iter().chain(prev_utxos).collect();
+        // mock addresses are mainnet ones
+        let err = super::create_inscription_transactions(
+            rollup_name,
+            body,
+            signature,
+            sequencer_public_key,
+            None,
+            utxos,
+            address.clone(),
+            address.clone(),
+            REVEAL_OUTPUT_AMOUNT,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
+            bitcoin::Network::Testnet,
+            &[],
+        )
+        .unwrap_err();
 
-        let tx = super::build_commit_transaction(
-            Some(super::TxWithId {
-                id: prev_tx_id,
-                tx: prev_tx,
-            }),
-            prev_utxo,
-            recipient.clone(),
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "change address {} is not valid for network testnet",
+                address
+            )
+        );
+    }
+
+    #[test]
+    fn create_inscription_transactions_change_utxo() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let (commit, _, change_utxo) = super::create_inscription_transactions(
+            rollup_name,
+            body,
+            signature,
+            sequencer_public_key,
+            None,
+            utxos,
             address.clone(),
-            50000,
-            32.0,
+            address.clone(),
+            REVEAL_OUTPUT_AMOUNT,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            &[],
         )
         .unwrap();
 
-        assert_eq!(tx.input.len(), 1);
-        assert_eq!(tx.input[0].previous_output.txid, prev_tx_id);
+        let change_utxo = change_utxo.unwrap();
+        assert_eq!(change_utxo.tx_id, commit.compute_txid());
+        let change_output = &commit.output[change_utxo.vout as usize];
+        assert_eq!(change_output.script_pubkey, address.script_pubkey());
+        assert_eq!(change_utxo.amount, change_output.value.to_sat());
 
+        // the change can be spent right away by the next commit
         let tx = super::build_commit_transaction(
             None,
-            utxos.clone(),
-            recipient.clone(),
+            vec![change_utxo.clone()],
             address.clone(),
-            100_000_000_000,
-            32.0,
+            address.clone(),
+            1_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(1.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output.txid, change_utxo.tx_id);
+        assert_eq!(tx.input[0].previous_output.vout, change_utxo.vout);
+    }
+
+    #[test]
+    fn reveal_commitment_is_verified() {
+        let (rollup_name, _, _, _, _, _) = get_mock_data();
+
+        let (commit, reveal, _) = mock_builder().reveal_value(546).build().unwrap();
+        let mut reveal = reveal.tx;
+
+        let inscription = parse_transaction(&reveal, rollup_name).unwrap();
+        assert_eq!(
+            verify_reveal_commitment(&reveal, &inscription, rollup_name),
+            Ok(true)
         );
 
-        assert!(tx.is_err());
-        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+        // the rebuilt address is the one the commit tx pays to
+        let commitment_address =
+            reveal_commitment_address(&reveal, bitcoin::Network::Bitcoin).unwrap();
+        assert_eq!(
+            commit.output[reveal.input[0].previous_output.vout as usize].script_pubkey,
+            commitment_address.script_pubkey()
+        );
 
-        let tx = super::build_commit_transaction(
+        // an envelope that doesn't match the inscription
+        let mut other_inscription = inscription.clone();
+        other_inscription.body.push(0);
+        assert_eq!(
+            verify_reveal_commitment(&reveal, &other_inscription, rollup_name),
+            Ok(false)
+        );
+
+        // flip the output key parity in the control block
+        let mut witness = reveal.input[0].witness.to_vec();
+        witness.last_mut().unwrap()[0] ^= 1;
+        reveal.input[0].witness = Witness::from_slice(&witness);
+        assert_eq!(
+            verify_reveal_commitment(&reveal, &inscription, rollup_name),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn reveal_script_matches_commitment() {
+        use bitcoin::key::TapTweak;
+        use bitcoin::taproot::{LeafVersion, TapLeafHash, TapNodeHash};
+        use bitcoin::XOnlyPublicKey;
+
+        let (commit, reveal, _) = mock_builder().reveal_value(546).build().unwrap();
+
+        let reveal_script = reveal.reveal_script().unwrap();
+        let control_block =
+            ControlBlock::decode(reveal.tx.input[0].witness.last().unwrap()).unwrap();
+        let commit_output = &commit.output[reveal.tx.input[0].previous_output.vout as usize];
+        // P2TR script pubkeys are OP_1 followed by a push of the output key
+        let output_key =
+            XOnlyPublicKey::from_slice(&commit_output.script_pubkey.as_bytes()[2..]).unwrap();
+
+        // the reveal script is the only leaf, its hash is the merkle root
+        let leaf_hash = TapLeafHash::from_script(reveal_script, LeafVersion::TapScript);
+        let secp = Secp256k1::verification_only();
+        let (tweaked_key, _parity) = control_block
+            .internal_key
+            .tap_tweak(&secp, Some(TapNodeHash::from(leaf_hash)));
+        assert_eq!(tweaked_key.to_inner(), output_key);
+        assert!(control_block.verify_taproot_commitment(&secp, output_key, reveal_script));
+
+        // commit txs are key path spends
+        assert!(TxWithId {
+            id: commit.compute_txid(),
+            tx: commit,
+        }
+        .reveal_script()
+        .is_none());
+    }
+
+    #[test]
+    fn sign_commit_inputs() {
+        let secp256k1 = Secp256k1::new();
+        let private_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let public_key = CompressedPublicKey(PublicKey::from_secret_key(&secp256k1, &private_key));
+        let address = Address::p2wpkh(&public_key, bitcoin::Network::Bitcoin);
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+
+        let utxos = vec![UTXO {
+            tx_id: Txid::from_str(
+                "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            )
+            .unwrap(),
+            vout: 0,
+            address: None,
+            script_pubkey: address.script_pubkey().to_hex_string(),
+            amount: 100_000,
+            confirmations: 100,
+            spendable: true,
+            solvable: true,
+        }];
+
+        let mut tx = super::build_commit_transaction(
             None,
-            vec![UTXO {
-                tx_id: Txid::from_str(
-                    "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
-                )
-                .unwrap(),
-                vout: 0,
-                address: Some(
-                    Address::from_str(
-                        "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
-                    )
-                    .unwrap(),
-                ),
-                script_pubkey: address.script_pubkey().to_hex_string(),
-                amount: 152,
-                confirmations: 100,
-                spendable: true,
-                solvable: true,
-            }],
+            utxos.clone(),
             recipient.clone(),
             address.clone(),
-            100_000_000_000,
-            32.0,
+            5_000,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            false,
+            None,
+            None,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        assert_eq!(tx.input.len(), 1);
+
+        // no key for the input script
+        let keys = HashMap::from([(recipient.script_pubkey(), private_key)]);
+        assert!(super::sign_commit_inputs(&mut tx, &utxos, &keys).is_err());
+
+        let keys = HashMap::from([(address.script_pubkey(), private_key)]);
+        super::sign_commit_inputs(&mut tx, &utxos, &keys).unwrap();
+
+        // verify the signature against the prevout
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 2);
+        let signature = bitcoin::ecdsa::Signature::from_slice(&witness[0]).unwrap();
+        assert_eq!(witness[1], public_key.to_bytes());
+
+        let signature_hash = SighashCache::new(&tx)
+            .p2wpkh_signature_hash(
+                0,
+                &address.script_pubkey(),
+                Amount::from_sat(100_000),
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        secp256k1
+            .verify_ecdsa(
+                &Message::from_digest(signature_hash.to_byte_array()),
+                &signature.signature,
+                &public_key.0,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn try_get_size_too_large() {
+        let (_, _, _, _, address, _) = get_mock_data();
+
+        let inputs = [TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            witness: Witness::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        }];
+        let outputs = [TxOut {
+            value: Amount::from_sat(REVEAL_OUTPUT_AMOUNT),
+            script_pubkey: address.script_pubkey(),
+        }];
+        let control_block = ControlBlock::decode(&[
+            193, 165, 246, 250, 6, 222, 28, 9, 130, 28, 217, 67, 171, 11, 229, 62, 48, 206, 219,
+            111, 155, 208, 6, 7, 119, 63, 146, 90, 227, 254, 231, 232, 249,
+        ])
+        .unwrap();
+
+        let script = ScriptBuf::from_bytes(vec![0; 1_000]);
+        assert!(
+            super::try_get_size(&inputs, &outputs, Some(&script), Some(&control_block)).is_ok()
         );
 
-        assert!(tx.is_err());
-        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+        let script = ScriptBuf::from_bytes(vec![0; 2_000_000]);
+        let size = super::try_get_size(&inputs, &outputs, Some(&script), Some(&control_block));
+        assert!(size.is_err());
     }
 
     #[test]
@@ -927,9 +3811,13 @@ mod tests {
             utxo.vout,
             address.clone(),
             REVEAL_OUTPUT_AMOUNT,
-            8.0,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
             &script,
             &control_block,
+            TapSighashType::Default,
+            None,
+            &[],
         )
         .unwrap();
 
@@ -956,9 +3844,13 @@ mod tests {
             utxo.vout,
             address.clone(),
             REVEAL_OUTPUT_AMOUNT,
-            75.0,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(75.0).unwrap(),
             &script,
             &control_block,
+            TapSighashType::Default,
+            None,
+            &[],
         );
 
         assert!(tx.is_err());
@@ -975,20 +3867,331 @@ mod tests {
             utxo.vout,
             address.clone(),
             9999,
-            1.0,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(1.0).unwrap(),
+            &script,
+            &control_block,
+            TapSighashType::Default,
+            None,
+            &[],
+        );
+
+        assert!(tx.is_err());
+        assert_eq!(format!("{}", tx.unwrap_err()), "input UTXO not big enough");
+
+        // under-funded inputs still fail with a change address
+        let tx = super::build_reveal_transaction(
+            TxOut {
+                value: Amount::from_sat(utxo.amount),
+                script_pubkey: ScriptBuf::from_hex(utxo.script_pubkey.as_str()).unwrap(),
+            },
+            utxo.tx_id,
+            utxo.vout,
+            address.clone(),
+            9999,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(1.0).unwrap(),
             &script,
             &control_block,
+            TapSighashType::Default,
+            Some(address.clone()),
+            &[],
         );
 
         assert!(tx.is_err());
         assert_eq!(format!("{}", tx.unwrap_err()), "input UTXO not big enough");
     }
+
+    #[test]
+    fn fee_rate_below_min_relay() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+        let expected = "fee rate 0.5 sat/vB is below the minimum relay fee rate of 1 sat/vB";
+        let low_fee_rate = FeeRate::from_sat_per_vb(0.5).unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(8.0).unwrap();
+
+        let builder = mock_builder();
+        let err = builder
+            .clone()
+            .fee_rates(low_fee_rate, fee_rate)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), expected);
+        let err = builder
+            .clone()
+            .fee_rates(fee_rate, low_fee_rate)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), expected);
+
+        // nodes of a network may relay cheaper transactions
+        let config = BitcoinDaConfig {
+            min_relay_fee_rate: FeeRate::from_sat_per_vb(0.1).unwrap(),
+            ..BitcoinDaConfig::new(bitcoin::Network::Bitcoin, low_fee_rate, low_fee_rate)
+        };
+        builder.config(&config).build().unwrap();
+
+        let err = super::create_keypath_commitment(
+            &[7; 32],
+            utxos,
+            address,
+            DUST_THRESHOLD,
+            low_fee_rate,
+            bitcoin::Network::Bitcoin,
+            FeeRate::MIN_RELAY,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+
+    #[test]
+    fn build_reveal_transaction_extra_outputs() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let utxo = utxos.first().unwrap();
+        let script = ScriptBuf::from_hex("62a58f2674fd840b6144bea2e63ebd35c16d7fd40252a2f28b2a01a648df356343e47976d7906a0e688bf5e134b6fd21bd365c016b57b1ace85cf30bf1206e27").unwrap();
+        let control_block = ControlBlock::decode(&[
+            193, 165, 246, 250, 6, 222, 28, 9, 130, 28, 217, 67, 171, 11, 229, 62, 48, 206, 219,
+            111, 155, 208, 6, 7, 119, 63, 146, 90, 227, 254, 231, 232, 249,
+        ])
+        .unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(8.0).unwrap();
+        let anchor = TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: address.script_pubkey(),
+        };
+
+        let build = |input_value: u64, extra_outputs: &[TxOut]| {
+            super::build_reveal_transaction(
+                TxOut {
+                    value: Amount::from_sat(input_value),
+                    script_pubkey: ScriptBuf::from_hex(utxo.script_pubkey.as_str()).unwrap(),
+                },
+                utxo.tx_id,
+                utxo.vout,
+                address.clone(),
+                REVEAL_OUTPUT_AMOUNT,
+                DUST_THRESHOLD,
+                fee_rate,
+                &script,
+                &control_block,
+                TapSighashType::Default,
+                None,
+                extra_outputs,
+            )
+        };
+
+        let tx = build(utxo.amount, &[anchor.clone()]).unwrap();
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0].value, Amount::from_sat(REVEAL_OUTPUT_AMOUNT));
+        assert_eq!(tx.output[1], anchor);
+
+        let size = super::get_size(&tx.input, &tx.output, Some(&script), Some(&control_block));
+        let required = REVEAL_OUTPUT_AMOUNT + 1_000 + fee_rate.fee_for_vsize(size);
+
+        assert!(build(required, &[anchor.clone()]).is_ok());
+        // enough for the inscription alone, but not for the anchor too
+        assert!(build(required - 1, &[]).is_ok());
+        assert_eq!(
+            format!("{}", build(required - 1, &[anchor]).unwrap_err()),
+            "input UTXO not big enough"
+        );
+    }
+
+    #[test]
+    fn inscription_builder_reveal_extra_outputs() {
+        let (_, _, _, _, address, _) = get_mock_data();
+        let anchor = TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: address.script_pubkey(),
+        };
+
+        let (commit, reveal, _) = mock_builder()
+            .reveal_extra_outputs(vec![anchor.clone()])
+            .build()
+            .unwrap();
+        assert_eq!(reveal.tx.output.len(), 2);
+        assert_eq!(
+            reveal.tx.output[0].value,
+            Amount::from_sat(REVEAL_OUTPUT_AMOUNT)
+        );
+        assert_eq!(reveal.tx.output[1], anchor);
+
+        // the commit output pays for the anchor and the larger reveal
+        let commit_vout = reveal.tx.input[0].previous_output.vout as usize;
+        let input_value = commit.output[commit_vout].value.to_sat();
+        assert_eq!(
+            input_value - REVEAL_OUTPUT_AMOUNT - 1_000,
+            FeeRate::from_sat_per_vb(10.0)
+                .unwrap()
+                .fee_for_vsize(reveal.tx.vsize())
+        );
+    }
+
+    #[test]
+    fn min_reveal_input_value_is_exact() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let utxo = utxos.first().unwrap();
+        let script = ScriptBuf::from_hex("62a58f2674fd840b6144bea2e63ebd35c16d7fd40252a2f28b2a01a648df356343e47976d7906a0e688bf5e134b6fd21bd365c016b57b1ace85cf30bf1206e27").unwrap();
+        let control_block = ControlBlock::decode(&[
+            193, 165, 246, 250, 6, 222, 28, 9, 130, 28, 217, 67, 171, 11, 229, 62, 48, 206, 219,
+            111, 155, 208, 6, 7, 119, 63, 146, 90, 227, 254, 231, 232, 249,
+        ])
+        .unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(8.0).unwrap();
+
+        let build = |input_value: u64| {
+            super::build_reveal_transaction(
+                TxOut {
+                    value: Amount::from_sat(input_value),
+                    script_pubkey: ScriptBuf::from_hex(utxo.script_pubkey.as_str()).unwrap(),
+                },
+                utxo.tx_id,
+                utxo.vout,
+                address.clone(),
+                REVEAL_OUTPUT_AMOUNT,
+                DUST_THRESHOLD,
+                fee_rate,
+                &script,
+                &control_block,
+                TapSighashType::Default,
+                None,
+                &[],
+            )
+        };
+
+        let min_value =
+            super::min_reveal_input_value(REVEAL_OUTPUT_AMOUNT, fee_rate, &script, &control_block);
+        assert!(min_value > REVEAL_OUTPUT_AMOUNT);
+
+        assert!(build(min_value).is_ok());
+        assert_eq!(
+            format!("{}", build(min_value - 1).unwrap_err()),
+            "input UTXO not big enough"
+        );
+    }
+
+    #[test]
+    fn build_reveal_transaction_with_change() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let change_address =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let utxo = utxos.first().unwrap();
+        let script = ScriptBuf::from_hex("62a58f2674fd840b6144bea2e63ebd35c16d7fd40252a2f28b2a01a648df356343e47976d7906a0e688bf5e134b6fd21bd365c016b57b1ace85cf30bf1206e27").unwrap();
+        let control_block = ControlBlock::decode(&[
+            193, 165, 246, 250, 6, 222, 28, 9, 130, 28, 217, 67, 171, 11, 229, 62, 48, 206, 219,
+            111, 155, 208, 6, 7, 119, 63, 146, 90, 227, 254, 231, 232, 249,
+        ])
+        .unwrap();
+
+        let mut tx = super::build_reveal_transaction(
+            TxOut {
+                value: Amount::from_sat(utxo.amount),
+                script_pubkey: ScriptBuf::from_hex(utxo.script_pubkey.as_str()).unwrap(),
+            },
+            utxo.tx_id,
+            utxo.vout,
+            address.clone(),
+            REVEAL_OUTPUT_AMOUNT,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            &script,
+            &control_block,
+            TapSighashType::Default,
+            Some(change_address.clone()),
+            &[],
+        )
+        .unwrap();
+
+        tx.input[0].witness.push([0; SCHNORR_SIGNATURE_SIZE]);
+        tx.input[0].witness.push(script.clone());
+        tx.input[0].witness.push(control_block.serialize());
+
+        let fee = tx.vsize() as u64 * 8;
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0].value, Amount::from_sat(REVEAL_OUTPUT_AMOUNT));
+        assert_eq!(tx.output[0].script_pubkey, address.script_pubkey());
+        assert_eq!(
+            tx.output[1].value,
+            Amount::from_sat(utxo.amount - REVEAL_OUTPUT_AMOUNT - fee)
+        );
+        assert_eq!(tx.output[1].script_pubkey, change_address.script_pubkey());
+
+        // no change output when the excess is dust
+        let tx = super::build_reveal_transaction(
+            TxOut {
+                value: Amount::from_sat(REVEAL_OUTPUT_AMOUNT + fee + 100),
+                script_pubkey: ScriptBuf::from_hex(utxo.script_pubkey.as_str()).unwrap(),
+            },
+            utxo.tx_id,
+            utxo.vout,
+            address.clone(),
+            REVEAL_OUTPUT_AMOUNT,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(8.0).unwrap(),
+            &script,
+            &control_block,
+            TapSighashType::Default,
+            Some(change_address),
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(tx.output.len(), 1);
+    }
+
+    #[test]
+    fn inscription_builder_reveal_change_address() {
+        let (_, _, _, _, address, _) = get_mock_data();
+        let change_address =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+
+        // the commit output covers the reveal exactly, there is nothing to return
+        let (commit, reveal, _) = mock_builder()
+            .reveal_change_address(Some(change_address.clone()))
+            .build()
+            .unwrap();
+        assert_eq!(reveal.tx.output.len(), 1);
+        assert_eq!(reveal.tx.output[0].script_pubkey, address.script_pubkey());
+        assert_eq!(
+            reveal.tx.input[0].previous_output.txid,
+            commit.compute_txid()
+        );
+
+        let testnet_address =
+            Address::from_str("tb1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24q7kef62")
+                .unwrap()
+                .require_network(bitcoin::Network::Testnet)
+                .unwrap();
+        let err = mock_builder()
+            .network(bitcoin::Network::Testnet)
+            .change_address(testnet_address.clone())
+            .reveal_recipient(testnet_address)
+            .reveal_change_address(Some(change_address.clone()))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "reveal change address {} is not valid for network testnet",
+                change_address
+            )
+        );
+    }
+
     #[test]
     fn create_inscription_transactions() {
         let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
 
         let tx_prefix = &[0u8];
-        let (commit, reveal) = super::create_inscription_transactions(
+        let (commit, reveal, _) = super::create_inscription_transactions(
             rollup_name,
             body.clone(),
             signature.clone(),
@@ -996,9 +4199,11 @@ mod tests {
             None,
             utxos.clone(),
             address.clone(),
+            address.clone(),
             546,
-            12.0,
-            10.0,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
             bitcoin::Network::Bitcoin,
             tx_prefix,
         )
@@ -1050,5 +4255,914 @@ mod tests {
             inscription.public_key, sequencer_public_key,
             "sequencer public key should be correct"
         );
+        assert_eq!(inscription.version, 1, "envelope should be version 1");
+    }
+
+    #[test]
+    fn inscription_builder_compression_scheme() {
+        let (rollup_name, _, _, _, _, _) = get_mock_data();
+        let blob = vec![3u8; 1000];
+        let compressed_blob = compress_blob(&blob);
+
+        for (body, compression) in [
+            (blob.clone(), CompressionScheme::None),
+            (compressed_blob, CompressionScheme::Brotli),
+        ] {
+            let (_, reveal, _) = mock_builder()
+                .body(body.clone())
+                .compression(compression)
+                .envelope_tags(true)
+                .reveal_value(546)
+                .build()
+                .unwrap();
+
+            let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+            assert_eq!(inscription.compression, compression);
+            assert_eq!(inscription.body, body);
+            assert_eq!(
+                inscription.compression.decode(&inscription.body).unwrap(),
+                blob
+            );
+        }
+    }
+
+    #[test]
+    fn inscription_builder() {
+        let (rollup_name, body, _, _, address, utxos) = get_mock_data();
+        let (signature, sequencer_public_key) =
+            sign_blob_with_private_key(&body, &SecretKey::from_slice(&[5; 32]).unwrap()).unwrap();
+
+        let (commit, reveal, _) = super::create_inscription_transactions(
+            rollup_name,
+            body.clone(),
+            signature.clone(),
+            sequencer_public_key.clone(),
+            None,
+            utxos.clone(),
+            address.clone(),
+            address.clone(),
+            546,
+            DUST_THRESHOLD,
+            FeeRate::from_sat_per_vb(12.0).unwrap(),
+            FeeRate::from_sat_per_vb(10.0).unwrap(),
+            bitcoin::Network::Bitcoin,
+            &[],
+        )
+        .unwrap();
+
+        let (built_commit, built_reveal, _) = mock_builder()
+            .signature(signature)
+            .sequencer_public_key(sequencer_public_key)
+            .reveal_value(546)
+            .build()
+            .unwrap();
+
+        // the commit key is random, so only the commitment output differs
+        assert_eq!(commit.input, built_commit.input);
+        assert_eq!(commit.output.len(), built_commit.output.len());
+        assert_eq!(commit.output[0].value, built_commit.output[0].value);
+        assert_eq!(commit.output[1], built_commit.output[1]);
+        assert_eq!(reveal.tx.output, built_reveal.tx.output);
+        assert_eq!(
+            parse_transaction(&reveal.tx, rollup_name).unwrap().body,
+            parse_transaction(&built_reveal.tx, rollup_name)
+                .unwrap()
+                .body
+        );
+        assert_eq!(
+            parse_transaction(&reveal.tx, rollup_name)
+                .unwrap()
+                .get_sig_verified_hash(),
+            parse_transaction(&built_reveal.tx, rollup_name)
+                .unwrap()
+                .get_sig_verified_hash()
+        );
+
+        let missing_fee_rates = InscriptionBuilder::new()
+            .rollup_name(rollup_name)
+            .body(vec![1])
+            .build();
+        assert_eq!(
+            missing_fee_rates.unwrap_err().to_string(),
+            "fee rates not set"
+        );
+    }
+
+    #[test]
+    fn inscription_builder_from_config() {
+        let (rollup_name, body, _, _, address, _) = get_mock_data();
+
+        let config = BitcoinDaConfig {
+            reveal_output_amount: 1_000,
+            default_reveal_prefix: vec![0],
+            ..BitcoinDaConfig::new(
+                bitcoin::Network::Bitcoin,
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+            )
+        };
+        assert_eq!(
+            BitcoinDaConfig::new(
+                bitcoin::Network::Bitcoin,
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+            )
+            .reveal_output_amount,
+            REVEAL_OUTPUT_AMOUNT
+        );
+
+        let (commit, reveal, _) = mock_builder().config(&config).build().unwrap();
+
+        assert!(reveal.id.as_raw_hash().to_byte_array().starts_with(&[0]));
+        assert_eq!(reveal.tx.output.len(), 1);
+        assert_eq!(reveal.tx.output[0].value, Amount::from_sat(1_000));
+        assert_eq!(reveal.tx.output[0].script_pubkey, address.script_pubkey());
+        assert_eq!(
+            reveal.tx.input[0].previous_output.txid,
+            commit.compute_txid()
+        );
+        assert_eq!(
+            parse_transaction(&reveal.tx, rollup_name).unwrap().body,
+            body
+        );
+    }
+
+    #[test]
+    fn required_input_value_matches_built_inscription() {
+        let (rollup_name, body, _, _, _, utxos) = get_mock_data();
+        let commit_fee_rate = FeeRate::from_sat_per_vb(12.0).unwrap();
+        let reveal_fee_rate = FeeRate::from_sat_per_vb(10.0).unwrap();
+
+        let (commit, _, change_utxo) = mock_builder()
+            .reveal_value(546)
+            .fee_rates(commit_fee_rate, reveal_fee_rate)
+            .build()
+            .unwrap();
+
+        let input_value: u64 = commit
+            .input
+            .iter()
+            .map(|input| {
+                utxos
+                    .iter()
+                    .find(|utxo| {
+                        utxo.tx_id == input.previous_output.txid
+                            && utxo.vout == input.previous_output.vout
+                    })
+                    .unwrap()
+                    .amount
+            })
+            .sum();
+        let consumed = input_value - change_utxo.unwrap().amount;
+
+        assert_eq!(
+            super::required_input_value(
+                rollup_name,
+                body.len(),
+                546,
+                commit_fee_rate,
+                reveal_fee_rate
+            )
+            .unwrap(),
+            consumed
+        );
+    }
+
+    #[test]
+    fn reveal_signed_with_sighash_type() {
+        use bitcoin::secp256k1::XOnlyPublicKey;
+        use bitcoin::sighash::Prevouts;
+        use bitcoin::taproot::{LeafVersion, TapLeafHash};
+
+        let (commit, reveal, _) = mock_builder()
+            .reveal_sighash_type(TapSighashType::SinglePlusAnyoneCanPay)
+            .build()
+            .unwrap();
+
+        let witness = &reveal.tx.input[0].witness;
+        let signature_bytes = witness.nth(0).unwrap();
+        assert_eq!(signature_bytes.len(), SCHNORR_SIGNATURE_SIZE + 1);
+        assert_eq!(
+            signature_bytes.last(),
+            Some(&(TapSighashType::SinglePlusAnyoneCanPay as u8))
+        );
+
+        let signature = bitcoin::taproot::Signature::from_slice(signature_bytes).unwrap();
+        assert_eq!(
+            signature.sighash_type,
+            TapSighashType::SinglePlusAnyoneCanPay
+        );
+
+        // the signature commits to the flagged sighash, with the key the reveal script checks
+        let reveal_script = ScriptBuf::from_bytes(witness.nth(1).unwrap().to_vec());
+        let public_key = XOnlyPublicKey::from_slice(&reveal_script.as_bytes()[1..33]).unwrap();
+        let spent_output = commit.output[reveal.tx.input[0].previous_output.vout as usize].clone();
+        let signature_hash = SighashCache::new(&reveal.tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[spent_output]),
+                TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
+                TapSighashType::SinglePlusAnyoneCanPay,
+            )
+            .unwrap();
+        Secp256k1::new()
+            .verify_schnorr(
+                &signature.signature,
+                &Message::from_digest(signature_hash.to_byte_array()),
+                &public_key,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn envelope_tags_are_opt_in() {
+        let (rollup_name, _, _, _, _, _) = get_mock_data();
+        let builder = mock_builder();
+        let pushes = |reveal: &TxWithId| {
+            reveal
+                .reveal_script()
+                .unwrap()
+                .instructions()
+                .filter_map(|instruction| match instruction.unwrap() {
+                    Instruction::PushBytes(bytes) => Some(bytes.as_bytes().to_vec()),
+                    Instruction::Op(_) => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // parsers predating the version and compression tags read envelopes without them
+        let (_, reveal, _) = builder.clone().build().unwrap();
+        assert!(!pushes(&reveal).contains(&VERSION_TAG.to_vec()));
+        assert!(!pushes(&reveal).contains(&COMPRESSION_TAG.to_vec()));
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(inscription.compression, CompressionScheme::Brotli);
+
+        // other schemes and extra tags can't be declared without the tags
+        assert!(builder
+            .clone()
+            .compression(CompressionScheme::None)
+            .build()
+            .is_err());
+        assert!(builder
+            .clone()
+            .extra_tags(vec![(b"version".to_vec(), b"2".to_vec())])
+            .build()
+            .is_err());
+
+        let (_, reveal, _) = builder
+            .compression(CompressionScheme::None)
+            .envelope_tags(true)
+            .build()
+            .unwrap();
+        assert!(pushes(&reveal).contains(&VERSION_TAG.to_vec()));
+        assert!(pushes(&reveal).contains(&COMPRESSION_TAG.to_vec()));
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(inscription.compression, CompressionScheme::None);
+    }
+
+    #[test]
+    fn config_envelope_tags_write_envelope_version() {
+        let (rollup_name, body, _, _, _, _) = get_mock_data();
+        let config = BitcoinDaConfig {
+            envelope_tags: true,
+            ..BitcoinDaConfig::new(
+                bitcoin::Network::Bitcoin,
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+            )
+        };
+
+        // the brotli body of the DA service, as it is sent once the tags are enabled
+        let (_, reveal, _) = mock_builder().config(&config).build().unwrap();
+        let pushes = reveal
+            .reveal_script()
+            .unwrap()
+            .instructions()
+            .filter_map(|instruction| match instruction.unwrap() {
+                Instruction::PushBytes(bytes) => Some(bytes.as_bytes().to_vec()),
+                Instruction::Op(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(pushes
+            .windows(2)
+            .any(|pair| pair == [VERSION_TAG.to_vec(), vec![1]]));
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(inscription.version, 1);
+        assert_eq!(inscription.compression, CompressionScheme::Brotli);
+        assert_eq!(inscription.body, body);
+    }
+
+    #[test]
+    fn signature_commits_to_compression_scheme() {
+        let (rollup_name, body, _, _, _, _) = get_mock_data();
+        let private_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let builder = mock_builder().envelope_tags(true);
+
+        let verify = |declared, signed| {
+            let (signature, public_key) =
+                sign_body_with_private_key(&body, signed, EncryptionScheme::None, &private_key)
+                    .unwrap();
+            let (_, reveal, _) = builder
+                .clone()
+                .compression(declared)
+                .signature(signature)
+                .sequencer_public_key(public_key)
+                .build()
+                .unwrap();
+            parse_transaction(&reveal.tx, rollup_name)
+                .unwrap()
+                .get_sig_verified_hash()
+        };
+
+        assert!(verify(CompressionScheme::Brotli, CompressionScheme::Brotli).is_some());
+        assert!(verify(CompressionScheme::None, CompressionScheme::None).is_some());
+        // a signed body can't be inscribed again under another scheme
+        assert!(verify(CompressionScheme::None, CompressionScheme::Brotli).is_none());
+        assert!(verify(CompressionScheme::Brotli, CompressionScheme::None).is_none());
+
+        // signing through the builder uses the declared scheme
+        let (_, reveal, _) = builder
+            .compression(CompressionScheme::None)
+            .sign_with(&private_key)
+            .build()
+            .unwrap();
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert!(inscription.get_sig_verified_hash().is_some());
+    }
+
+    #[test]
+    fn inscription_with_extra_tags() {
+        let (rollup_name, body, _, _, _, _) = get_mock_data();
+
+        let build = |extra_tags: Vec<(Vec<u8>, Vec<u8>)>| {
+            mock_builder()
+                .envelope_tags(true)
+                .extra_tags(extra_tags)
+                .build()
+        };
+
+        let (_, reveal, _) = build(vec![(b"version".to_vec(), b"2".to_vec())]).unwrap();
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(inscription.body, body);
+        assert_eq!(inscription.extra_tags.len(), 1);
+        assert_eq!(
+            inscription.extra_tags.get(b"version".as_slice()),
+            Some(&b"2".to_vec())
+        );
+
+        let (_, reveal, _) = build(vec![]).unwrap();
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert!(inscription.extra_tags.is_empty());
+
+        // an empty tag would be read as the start of the body
+        assert!(build(vec![(vec![], b"2".to_vec())]).is_err());
+        assert!(build(vec![
+            (b"version".to_vec(), b"2".to_vec()),
+            (b"version".to_vec(), b"3".to_vec()),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn inscription_with_encrypted_body() {
+        let (rollup_name, _, _, _, _, _) = get_mock_data();
+        let key = [7u8; 32];
+        let blob = vec![42; 2_000];
+
+        let builder = mock_builder()
+            .envelope_tags(true)
+            .encryption(EncryptionScheme::ChaCha20Poly1305, key);
+        let body = compress_blob(&blob);
+        let (_, reveal, _) = sign_and_build_inscription(builder.clone(), body.clone(), 42).unwrap();
+
+        // the signature is over the encrypted body, so it verifies without the key
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(inscription.encryption, EncryptionScheme::ChaCha20Poly1305);
+        assert_ne!(inscription.body, body);
+        assert_eq!(
+            inscription.public_key,
+            deterministic_keypair(42).1.serialize().to_vec()
+        );
+        assert!(inscription.get_sig_verified_hash().is_some());
+        assert!(verify_reveal_commitment(&reveal.tx, &inscription, rollup_name).unwrap());
+        // the encryption scheme is not exposed as an extra tag
+        assert!(inscription.extra_tags.is_empty());
+
+        // without the key the blob is the nonce and the ciphertext
+        assert_eq!(inscription.blob().unwrap(), inscription.body);
+        // the body is compressed, then encrypted
+        assert_eq!(
+            decompress_blob(&inscription.decrypted_body(&key).unwrap()).unwrap(),
+            blob
+        );
+        assert_eq!(
+            inscription.decrypted_body(&[8; 32]).unwrap_err(),
+            ParserError::DecryptionFailed
+        );
+
+        // the nonce is drawn from the rng of the build
+        let (_, other_reveal, _) =
+            sign_and_build_inscription(builder.clone(), body.clone(), 43).unwrap();
+        let other_inscription = parse_transaction(&other_reveal.tx, rollup_name).unwrap();
+        assert_ne!(
+            inscription.body[..NONCE_LEN],
+            other_inscription.body[..NONCE_LEN]
+        );
+        assert_eq!(
+            decompress_blob(&other_inscription.decrypted_body(&key).unwrap()).unwrap(),
+            blob
+        );
+
+        // the encryption tag can't be set by hand, nor declared without envelope tags
+        assert!(sign_and_build_inscription(
+            builder.clone().extra_tags(vec![(vec![7], vec![1])]),
+            body.clone(),
+            42
+        )
+        .is_err());
+        assert!(
+            sign_and_build_inscription(builder.clone().envelope_tags(false), body.clone(), 42)
+                .is_err()
+        );
+        // the ciphertext is only known when building, so a signature can't be given upfront
+        let (signature, public_key) = sign_body_with_private_key(
+            &body,
+            CompressionScheme::Brotli,
+            EncryptionScheme::ChaCha20Poly1305,
+            &deterministic_keypair(42).0,
+        )
+        .unwrap();
+        assert!(builder
+            .body(body)
+            .signature(signature)
+            .sequencer_public_key(public_key)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn blob_of_undecodable_body() {
+        let (rollup_name, _, _, _, _, _) = get_mock_data();
+        let blob: Vec<u8> = (0..2_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let compressed = compress_blob(&blob);
+
+        let (_, reveal, _) = mock_builder()
+            .body(compressed[..compressed.len() / 2].to_vec())
+            .build()
+            .unwrap();
+        let inscription = parse_transaction(&reveal.tx, rollup_name).unwrap();
+        assert_eq!(
+            inscription.blob().unwrap_err(),
+            ParserError::DecompressionFailed
+        );
+    }
+
+    #[test]
+    fn parse_rejects_tampered_reveal_value() {
+        let (rollup_name, body, _, _, _, _) = get_mock_data();
+
+        let (_, reveal, _) = mock_builder().build().unwrap();
+
+        let inscription =
+            parse_transaction_with_reveal_value(&reveal.tx, rollup_name, REVEAL_OUTPUT_AMOUNT)
+                .unwrap();
+        assert_eq!(inscription.body, body);
+
+        let mut tampered = reveal.tx.clone();
+        tampered.output[0].value = Amount::from_sat(REVEAL_OUTPUT_AMOUNT + 1);
+        assert_eq!(
+            parse_transaction_with_reveal_value(&tampered, rollup_name, REVEAL_OUTPUT_AMOUNT)
+                .unwrap_err(),
+            ParserError::UnexpectedRevealValue {
+                expected: REVEAL_OUTPUT_AMOUNT,
+                got: REVEAL_OUTPUT_AMOUNT + 1,
+            }
+        );
+        // without validation the envelope is still parsed
+        assert!(parse_transaction(&tampered, rollup_name).is_ok());
+
+        tampered.output.clear();
+        assert_eq!(
+            parse_transaction_with_reveal_value(&tampered, rollup_name, REVEAL_OUTPUT_AMOUNT)
+                .unwrap_err(),
+            ParserError::MissingOutput
+        );
+    }
+
+    #[test]
+    fn inscription_with_seeded_rng_is_reproducible() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let build = |seed| {
+            mock_builder()
+                .randomize_commit_outputs(true)
+                .build_with_rng(&mut StdRng::seed_from_u64(seed))
+                .unwrap()
+        };
+
+        // the commit output order is drawn from the same rng
+        for seed in 0..16 {
+            assert_eq!(build(seed).0, build(seed).0);
+        }
+
+        let (commit, reveal, _) = build(1);
+        let (same_commit, same_reveal, _) = build(1);
+        assert_eq!(commit, same_commit);
+        assert_eq!(reveal.id, same_reveal.id);
+        assert_eq!(
+            reveal.tx.input[0].witness.to_vec(),
+            same_reveal.tx.input[0].witness.to_vec()
+        );
+
+        let (_, other_reveal, _) = build(2);
+        assert_ne!(
+            reveal.tx.input[0].witness.to_vec(),
+            other_reveal.tx.input[0].witness.to_vec()
+        );
+    }
+
+    #[test]
+    fn create_inscription_transactions_rejects_long_prefix() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        for prefix in [vec![0u8; 33], vec![0u8; 5]] {
+            let result = super::create_inscription_transactions(
+                rollup_name,
+                body.clone(),
+                signature.clone(),
+                sequencer_public_key.clone(),
+                None,
+                utxos.clone(),
+                address.clone(),
+                address.clone(),
+                546,
+                DUST_THRESHOLD,
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+                bitcoin::Network::Bitcoin,
+                &prefix,
+            );
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "reveal tx prefix of {} bytes is longer than the maximum of 4 bytes",
+                    prefix.len()
+                )
+            );
+        }
+
+        // a lower maximum from the config
+        let config = BitcoinDaConfig {
+            max_reveal_tx_prefix_len: 1,
+            ..BitcoinDaConfig::new(
+                bitcoin::Network::Bitcoin,
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+            )
+        };
+        let builder = mock_builder().config(&config);
+        assert_eq!(
+            builder
+                .clone()
+                .reveal_prefix(&[0, 0])
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "reveal tx prefix of 2 bytes is longer than the maximum of 1 bytes"
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .reveal_tx_difficulty_bits(9)
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "reveal tx difficulty of 9 bits is above the maximum of 8 bits"
+        );
+        builder.reveal_prefix(&[0]).build().unwrap();
+
+        assert!(super::check_reveal_tx_prefix(&[0; 4], 4).is_ok());
+        // the hard cap applies whatever the configured maximum
+        assert!(super::check_reveal_tx_prefix(&[0; 32], 64).is_ok());
+        assert!(super::check_reveal_tx_prefix(&[0; 33], 64).is_err());
+    }
+
+    #[test]
+    fn create_inscription_transactions_rejects_unfunded_inscription() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let create = |utxos: Vec<UTXO>| {
+            super::create_inscription_transactions(
+                rollup_name,
+                body.clone(),
+                signature.clone(),
+                sequencer_public_key.clone(),
+                None,
+                utxos,
+                address.clone(),
+                address.clone(),
+                546,
+                DUST_THRESHOLD,
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+                bitcoin::Network::Bitcoin,
+                &[],
+            )
+        };
+
+        assert_eq!(
+            create(vec![]).unwrap_err().to_string(),
+            "no UTXOs to fund the inscription"
+        );
+
+        // 546 sats for the reveal output and 1000 / 4 vB * 10 sat/vB for the body
+        let small_utxos: Vec<_> = utxos
+            .iter()
+            .cloned()
+            .map(|utxo| UTXO {
+                amount: 1_000,
+                ..utxo
+            })
+            .collect();
+        let err = create(small_utxos).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CoinSelectionError>(),
+            Some(&CoinSelectionError::InsufficientFunds {
+                required: 3_046,
+                available: 3_000,
+            })
+        );
+
+        assert!(create(utxos).is_ok());
+    }
+
+    #[test]
+    fn reveal_pays_requested_fee_rate() {
+        let (commit, reveal, _) = mock_builder().build().unwrap();
+
+        let commit_vout = reveal.tx.input[0].previous_output.vout as usize;
+        let input_value = commit.output[commit_vout].value.to_sat();
+        let fee_rate = super::actual_fee_rate(&reveal.tx, input_value)
+            .unwrap()
+            .as_sat_per_vb();
+        // the fee is rounded up to whole sats
+        assert!(
+            (10.0..10.0 + 1.0 / reveal.tx.vsize() as f64 + 0.01).contains(&fee_rate),
+            "reveal pays {} sat/vB",
+            fee_rate
+        );
+
+        assert_eq!(super::actual_fee_rate(&reveal.tx, 0), None);
+    }
+
+    #[test]
+    fn inscription_with_reveal_tx_difficulty_bits() {
+        let builder = mock_builder();
+
+        let (_, reveal, _) = builder
+            .clone()
+            .reveal_tx_difficulty_bits(4)
+            .build()
+            .unwrap();
+        let reveal_hash = reveal.id.as_raw_hash().to_byte_array();
+        assert_eq!(reveal_hash[0] >> 4, 0);
+
+        let result = builder.reveal_tx_difficulty_bits(33).build();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "reveal tx difficulty of 33 bits is above the maximum of 32 bits"
+        );
+    }
+
+    #[test]
+    fn leading_zero_bits() {
+        assert!(super::has_leading_zero_bits(&[0xff], 0));
+        assert!(super::has_leading_zero_bits(&[], 0));
+        assert!(super::has_leading_zero_bits(&[0x0f], 4));
+        assert!(!super::has_leading_zero_bits(&[0x1f], 4));
+        assert!(super::has_leading_zero_bits(&[0x00, 0x0f, 0xff], 12));
+        assert!(!super::has_leading_zero_bits(&[0x00, 0x10, 0xff], 12));
+        assert!(super::has_leading_zero_bits(&[0x00, 0x00], 16));
+        assert!(!super::has_leading_zero_bits(&[0x00, 0x01], 16));
+        assert!(!super::has_leading_zero_bits(&[0x00], 12));
+    }
+
+    #[test]
+    fn build_commit_transaction_dust_threshold() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+
+        let recipient =
+            Address::from_str("bc1p2e37kuhnsdc5zvc8zlj2hn6awv3ruavak6ayc8jvpyvus59j3mwqwdt0zc")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let build = |dust_threshold| {
+            super::build_commit_transaction(
+                None,
+                utxos.clone(),
+                recipient.clone(),
+                address.clone(),
+                5_000,
+                dust_threshold,
+                FeeRate::from_sat_per_vb(8.0).unwrap(),
+                false,
+                None,
+                None,
+                &mut rand::thread_rng(),
+            )
+            .unwrap()
+        };
+
+        // 3_768 sats of change are above the default threshold
+        let tx = build(DUST_THRESHOLD);
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
+        assert_eq!(tx.output[1].value, Amount::from_sat(3_768));
+
+        // but not above a custom one, so they are left to the miners
+        let tx = build(4_000);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value, Amount::from_sat(5_000));
+        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+    }
+
+    #[test]
+    fn estimate_vsize_matches_get_size() {
+        use bitcoin::taproot::{LeafVersion, TaprootBuilder};
+
+        let (_, _, _, _, address, _) = get_mock_data();
+        let input = TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            witness: Witness::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        };
+        let output = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: address.script_pubkey(),
+        };
+
+        // commit transactions, a required utxo and funding utxos, with or without change
+        for num_inputs in 1..=8 {
+            for num_outputs in 1..=2 {
+                let size = super::get_size(
+                    &vec![input.clone(); num_inputs],
+                    &vec![output.clone(); num_outputs],
+                    None,
+                    None,
+                );
+                let estimate = super::estimate_vsize(
+                    num_inputs,
+                    num_outputs,
+                    false,
+                    0,
+                    0,
+                    TapSighashType::Default,
+                );
+                assert!(
+                    size.abs_diff(estimate) <= 1,
+                    "{num_inputs} inputs, {num_outputs} outputs: {size} vs {estimate}"
+                );
+            }
+        }
+
+        // reveal transactions
+        let secp = Secp256k1::new();
+        let key_pair = bitcoin::key::UntweakedKeypair::from_secret_key(
+            &secp,
+            &SecretKey::from_slice(&[7; 32]).unwrap(),
+        );
+        let (public_key, _) = key_pair.x_only_public_key();
+        for body_len in [0, 100, 1_000, 70_000] {
+            let envelopes = [(vec![1; body_len], vec![2; 64])];
+            let script = super::RevealScriptTemplate::new(
+                &public_key,
+                "test-rollup",
+                &envelopes,
+                &[3; 33],
+                Some(CompressionScheme::None),
+                &[],
+            )
+            .reveal_script(0);
+            let control_block = TaprootBuilder::new()
+                .add_leaf(0, script.clone())
+                .unwrap()
+                .finalize(&secp, public_key)
+                .unwrap()
+                .control_block(&(script.clone(), LeafVersion::TapScript))
+                .unwrap();
+
+            let size = super::get_size(
+                &[input.clone()],
+                &[output.clone()],
+                Some(&script),
+                Some(&control_block),
+            );
+            let estimate = super::estimate_vsize(
+                1,
+                1,
+                true,
+                script.len(),
+                control_block.serialize().len(),
+                TapSighashType::Default,
+            );
+            assert!(
+                size.abs_diff(estimate) <= 1,
+                "{body_len} bytes body: {size} vs {estimate}"
+            );
+        }
+    }
+
+    #[test]
+    fn reveal_size_counts_sighash_byte() {
+        let (commit, reveal, _) = mock_builder()
+            .reveal_sighash_type(TapSighashType::All)
+            .build()
+            .unwrap();
+        let witness = &reveal.tx.input[0].witness;
+        assert_eq!(witness.nth(0).unwrap().len(), SCHNORR_SIGNATURE_SIZE + 1);
+
+        // the estimate is exact once the sighash byte is counted
+        let script = ScriptBuf::from_bytes(witness.nth(1).unwrap().to_vec());
+        let control_block = ControlBlock::decode(witness.nth(2).unwrap()).unwrap();
+        let mut inputs = reveal.tx.input.clone();
+        inputs[0].witness = Witness::new();
+        let size = super::try_get_size_with_sighash(
+            &inputs,
+            &reveal.tx.output,
+            Some(&script),
+            Some(&control_block),
+            TapSighashType::All,
+        )
+        .unwrap();
+        assert_eq!(size, reveal.tx.vsize());
+        assert_eq!(
+            super::estimate_vsize(
+                1,
+                1,
+                true,
+                script.len(),
+                control_block.serialize().len(),
+                TapSighashType::All,
+            ),
+            reveal.tx.vsize()
+        );
+
+        // and the commit output pays the reveal fee for it
+        let commit_vout = reveal.tx.input[0].previous_output.vout as usize;
+        let input_value = commit.output[commit_vout].value.to_sat();
+        assert_eq!(
+            input_value - REVEAL_OUTPUT_AMOUNT,
+            FeeRate::from_sat_per_vb(10.0)
+                .unwrap()
+                .fee_for_vsize(reveal.tx.vsize())
+        );
+    }
+
+    #[test]
+    fn reveal_script_template_matches_full_script() {
+        let secp = Secp256k1::new();
+        let key_pair = bitcoin::key::UntweakedKeypair::from_secret_key(
+            &secp,
+            &SecretKey::from_slice(&[7; 32]).unwrap(),
+        );
+        let (public_key, _) = key_pair.x_only_public_key();
+        let envelopes = vec![
+            (vec![1; 600], vec![2; 64]),
+            (vec![3; 10], vec![4; 64]),
+            (vec![5; 1100], vec![6; 64]),
+        ];
+        let sequencer_public_key = vec![8; 33];
+        let extra_tags = vec![(b"version".to_vec(), b"2".to_vec())];
+
+        let template = super::RevealScriptTemplate::new(
+            &public_key,
+            "test-rollup",
+            &envelopes,
+            &sequencer_public_key,
+            Some(CompressionScheme::Brotli),
+            &extra_tags,
+        );
+
+        for nonce in [0, 1, 16, 17, 1_000_000] {
+            let mut builder = bitcoin::script::Builder::new()
+                .push_x_only_key(&public_key)
+                .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG);
+            for (i, (body, signature)) in envelopes.iter().enumerate() {
+                builder = super::push_envelope(
+                    builder,
+                    "test-rollup",
+                    signature,
+                    &sequencer_public_key,
+                    if i == 0 { nonce } else { 0 },
+                    Some(CompressionScheme::Brotli),
+                    &extra_tags,
+                    body,
+                );
+            }
+
+            assert_eq!(template.reveal_script(nonce), builder.into_script());
+        }
     }
 }
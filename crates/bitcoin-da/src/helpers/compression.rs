@@ -1,16 +1,75 @@
 use std::io::Write;
 
+use serde::{Deserialize, Serialize};
+use sov_rollup_interface::da::{
+    BLOB_COMPRESSION_BUFFER_SIZE, BLOB_COMPRESSION_LG_WINDOW_SIZE, BLOB_COMPRESSION_QUALITY,
+};
+
+use crate::helpers::parsers::ParserError;
+
+/// Compression scheme of an inscription body, declared in its envelope.
+/// Envelopes without a declared scheme are brotli compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum CompressionScheme {
+    /// The body is the raw blob
+    None = 0,
+    /// The body is compressed with [`compress_blob`]
+    Brotli = 1,
+}
+
+impl CompressionScheme {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::None),
+            1 => Some(Self::Brotli),
+            _ => None,
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    /// Encodes a blob into a body of this scheme
+    #[cfg(feature = "native")]
+    pub fn encode(self, blob: &[u8]) -> Vec<u8> {
+        match self {
+            Self::None => blob.to_vec(),
+            Self::Brotli => compress_blob(blob),
+        }
+    }
+
+    /// Returns the original blob of a body encoded with this scheme.
+    /// Fails if the body is not a valid encoding under the scheme.
+    pub fn decode(self, body: &[u8]) -> Result<Vec<u8>, ParserError> {
+        match self {
+            Self::None => Ok(body.to_vec()),
+            Self::Brotli => decompress_blob(body),
+        }
+    }
+}
+
 #[cfg(feature = "native")]
 pub fn compress_blob(blob: &[u8]) -> Vec<u8> {
     use brotli::CompressorWriter;
-    let mut writer = CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    let mut writer = CompressorWriter::new(
+        Vec::new(),
+        BLOB_COMPRESSION_BUFFER_SIZE,
+        BLOB_COMPRESSION_QUALITY,
+        BLOB_COMPRESSION_LG_WINDOW_SIZE,
+    );
     writer.write_all(blob).unwrap();
     writer.into_inner()
 }
 
-pub fn decompress_blob(blob: &[u8]) -> Vec<u8> {
+pub fn decompress_blob(blob: &[u8]) -> Result<Vec<u8>, ParserError> {
     use brotli::DecompressorWriter;
-    let mut writer = DecompressorWriter::new(Vec::new(), 4096);
-    writer.write_all(blob).unwrap();
-    writer.into_inner().expect("decompression failed")
+    let mut writer = DecompressorWriter::new(Vec::new(), BLOB_COMPRESSION_BUFFER_SIZE);
+    writer
+        .write_all(blob)
+        .map_err(|_| ParserError::DecompressionFailed)?;
+    writer
+        .into_inner()
+        .map_err(|_| ParserError::DecompressionFailed)
 }
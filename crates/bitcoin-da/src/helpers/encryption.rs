@@ -0,0 +1,73 @@
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Length of the nonce prepended to a ChaCha20-Poly1305 encrypted body
+pub const NONCE_LEN: usize = 12;
+
+/// Encryption scheme of an inscription body, declared in its envelope.
+/// Envelopes without a declared scheme are not encrypted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum EncryptionScheme {
+    /// The body is not encrypted
+    #[default]
+    None = 0,
+    /// The body is the nonce followed by the ChaCha20-Poly1305 ciphertext
+    /// of the compressed body, compressed first as ciphertext doesn't compress
+    ChaCha20Poly1305 = 1,
+}
+
+impl EncryptionScheme {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::None),
+            1 => Some(Self::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    /// Encrypts a body with this scheme under a fresh nonce drawn from `rng`
+    pub fn encrypt<R: RngCore + CryptoRng>(
+        self,
+        key: &[u8; 32],
+        body: &[u8],
+        rng: &mut R,
+    ) -> Vec<u8> {
+        match self {
+            Self::None => body.to_vec(),
+            Self::ChaCha20Poly1305 => {
+                let mut nonce = [0; NONCE_LEN];
+                rng.fill_bytes(&mut nonce);
+                let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+                let ciphertext = cipher
+                    .encrypt(Nonce::from_slice(&nonce), body)
+                    .expect("encryption failed");
+                let mut encrypted = nonce.to_vec();
+                encrypted.extend(ciphertext);
+                encrypted
+            }
+        }
+    }
+
+    /// Returns the plain body of a body encrypted with this scheme,
+    /// or `None` if it was not encrypted with the given key
+    pub fn decrypt(self, key: &[u8; 32], body: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::None => Some(body.to_vec()),
+            Self::ChaCha20Poly1305 => {
+                if body.len() < NONCE_LEN {
+                    return None;
+                }
+                let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+                let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+                cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+            }
+        }
+    }
+}
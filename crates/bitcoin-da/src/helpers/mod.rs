@@ -3,11 +3,20 @@ const ROLLUP_NAME_TAG: &[u8; 1] = &[1; 1];
 const SIGNATURE_TAG: &[u8; 1] = &[2; 1];
 const PUBLICKEY_TAG: &[u8; 1] = &[3; 1];
 const RANDOM_TAG: &[u8; 1] = &[4; 1];
+const COMPRESSION_TAG: &[u8; 1] = &[5; 1];
 const BODY_TAG: &[u8; 0] = &[];
+const VERSION_TAG: &[u8; 1] = &[6; 1];
+const ENCRYPTION_TAG: &[u8; 1] = &[7; 1];
+
+// Envelope format version written by the builders and accepted by the parser
+const ENVELOPE_VERSION: u8 = 1;
 
 #[cfg(feature = "native")]
 pub mod builders;
 pub mod compression;
+pub mod encryption;
 pub mod parsers;
+#[cfg(all(feature = "native", any(test, feature = "test-utils")))]
+pub mod test_signer;
 #[cfg(test)]
 pub mod test_utils;
@@ -1,8 +1,10 @@
 use core::iter::Peekable;
+use std::collections::BTreeMap;
 
 use bitcoin::blockdata::opcodes::all::{OP_ENDIF, OP_IF};
 use bitcoin::blockdata::script::{Instruction, Instructions};
-use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::hashes::{sha256, sha256d, Hash};
+use bitcoin::key::{TapTweak, TweakedPublicKey, XOnlyPublicKey};
 use bitcoin::opcodes::all::{
     OP_PUSHNUM_1, OP_PUSHNUM_10, OP_PUSHNUM_11, OP_PUSHNUM_12, OP_PUSHNUM_13, OP_PUSHNUM_14,
     OP_PUSHNUM_15, OP_PUSHNUM_16, OP_PUSHNUM_2, OP_PUSHNUM_3, OP_PUSHNUM_4, OP_PUSHNUM_5,
@@ -10,24 +12,66 @@ use bitcoin::opcodes::all::{
 };
 use bitcoin::opcodes::OP_FALSE;
 use bitcoin::secp256k1::{ecdsa, Message, Secp256k1};
-use bitcoin::{secp256k1, Script, Transaction};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TAPROOT_ANNEX_PREFIX};
+use bitcoin::{secp256k1, Address, Network, Script, Transaction};
 use serde::{Deserialize, Serialize};
 
-use super::{BODY_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG, SIGNATURE_TAG};
+use super::compression::CompressionScheme;
+use super::encryption::EncryptionScheme;
+use super::{
+    BODY_TAG, COMPRESSION_TAG, ENCRYPTION_TAG, ENVELOPE_VERSION, PUBLICKEY_TAG, RANDOM_TAG,
+    ROLLUP_NAME_TAG, SIGNATURE_TAG, VERSION_TAG,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedInscription {
     pub body: Vec<u8>,
     pub signature: Vec<u8>,
     pub public_key: Vec<u8>,
+    /// Format version declared in the envelope, envelopes without a version tag are version 1
+    #[serde(default = "default_envelope_version")]
+    pub version: u8,
+    /// Compression scheme of the body declared in the envelope
+    pub compression: CompressionScheme,
+    /// Encryption scheme of the body declared in the envelope, applied after compression
+    #[serde(default)]
+    pub encryption: EncryptionScheme,
+    /// Additional key-value tags found between the compression scheme and the body
+    #[serde(default)]
+    pub extra_tags: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// Hash of an envelope body signed by the sequencer. A brotli compressed, unencrypted body signs
+/// the double SHA256 of the body, as envelopes did before their schemes were declared.
+/// Any other body also commits to its schemes, so a signed body can't be inscribed again under
+/// a scheme it wasn't signed for. That hash is a single SHA256 over a 34 byte preimage, which
+/// can't be the 32 byte inner hash of a brotli body.
+pub fn signed_body_hash(
+    body: &[u8],
+    compression: CompressionScheme,
+    encryption: EncryptionScheme,
+) -> [u8; 32] {
+    let body_hash = sha256d::Hash::hash(body).to_byte_array();
+    if compression == CompressionScheme::Brotli && encryption == EncryptionScheme::None {
+        return body_hash;
+    }
+
+    let mut preimage = body_hash.to_vec();
+    preimage.extend([compression.to_byte(), encryption.to_byte()]);
+    sha256::Hash::hash(&preimage).to_byte_array()
+}
+
+fn default_envelope_version() -> u8 {
+    ENVELOPE_VERSION
 }
 
 impl ParsedInscription {
-    /// Verifies the signature of the inscription and returns the hash of the body
+    /// Verifies the signature of the inscription and returns the hash of the body,
+    /// see [`signed_body_hash`]
     pub fn get_sig_verified_hash(&self) -> Option<[u8; 32]> {
         let public_key = secp256k1::PublicKey::from_slice(&self.public_key);
         let signature = ecdsa::Signature::from_compact(&self.signature);
-        let hash = sha256d::Hash::hash(&self.body).to_byte_array();
+        let hash = signed_body_hash(&self.body, self.compression, self.encryption);
         let message = Message::from_digest_slice(&hash).unwrap(); // cannot fail
 
         let secp = Secp256k1::new();
@@ -43,6 +87,24 @@ impl ParsedInscription {
             None
         }
     }
+
+    /// Returns the body as it was before encryption, still compressed with the declared scheme.
+    /// The signature is over the encrypted body, check it before decrypting.
+    pub fn decrypted_body(&self, key: &[u8; 32]) -> Result<Vec<u8>, ParserError> {
+        self.encryption
+            .decrypt(key, &self.body)
+            .ok_or(ParserError::DecryptionFailed)
+    }
+
+    /// Returns the blob carried by the envelope. Encrypted bodies are returned as they are,
+    /// only holders of the key can decrypt and then decompress them.
+    /// Fails if the body doesn't decode under the declared compression scheme.
+    pub fn blob(&self) -> Result<Vec<u8>, ParserError> {
+        match self.encryption {
+            EncryptionScheme::None => self.compression.decode(&self.body),
+            EncryptionScheme::ChaCha20Poly1305 => Ok(self.body.clone()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -52,6 +114,28 @@ pub enum ParserError {
     EnvelopeHasIncorrectFormat,
     NonTapscriptWitness,
     IncorrectSignature,
+    InvalidControlBlock,
+    UnknownCompressionScheme,
+    /// The transaction has no inputs
+    MissingInput,
+    /// The script has a push running past its end
+    InvalidScript,
+    /// The envelope has the same extra tag more than once
+    DuplicateTag,
+    /// The envelope declares a format version this parser doesn't know
+    UnsupportedVersion(u8),
+    UnknownEncryptionScheme,
+    /// The body could not be decrypted with the given key
+    DecryptionFailed,
+    /// The body is not valid under its declared compression scheme
+    DecompressionFailed,
+    /// The reveal transaction has no output
+    MissingOutput,
+    /// The reveal output doesn't carry the value the builders put into it
+    UnexpectedRevealValue {
+        expected: u64,
+        got: u64,
+    },
 }
 
 pub fn parse_transaction(
@@ -63,14 +147,174 @@ pub fn parse_transaction(
     parse_relevant_inscriptions(&mut instructions, rollup_name)
 }
 
+/// Same as [`parse_transaction`] but also rejects reveals whose first output value differs
+/// from `expected_value`, usually `REVEAL_OUTPUT_AMOUNT`, so that envelopes in
+/// transactions not shaped like our reveals are not picked up.
+pub fn parse_transaction_with_reveal_value(
+    tx: &Transaction,
+    rollup_name: &str,
+    expected_value: u64,
+) -> Result<ParsedInscription, ParserError> {
+    let got = tx
+        .output
+        .first()
+        .ok_or(ParserError::MissingOutput)?
+        .value
+        .to_sat();
+    if got != expected_value {
+        return Err(ParserError::UnexpectedRevealValue {
+            expected: expected_value,
+            got,
+        });
+    }
+    parse_transaction(tx, rollup_name)
+}
+
+/// Returns the rollup name declared by the first envelope of the transaction without
+/// parsing the rest of the envelope, a cheap filter to run before [`parse_transaction`].
+pub fn peek_rollup_name(tx: &Transaction) -> Option<String> {
+    let script = tx.input.first()?.witness.tapscript()?;
+    let mut instructions = script.instructions();
+
+    let mut last_op = None;
+    while let Some(Ok(instruction)) = instructions.next() {
+        match instruction {
+            Instruction::Op(OP_IF) if last_op == Some(OP_FALSE) => {
+                // the envelope starts with the rollup name tag and the name
+                return match (instructions.next(), instructions.next()) {
+                    (
+                        Some(Ok(Instruction::PushBytes(tag))),
+                        Some(Ok(Instruction::PushBytes(name))),
+                    ) if tag.as_bytes() == ROLLUP_NAME_TAG => {
+                        String::from_utf8(name.as_bytes().to_vec()).ok()
+                    }
+                    _ => None,
+                };
+            }
+            // rust bitcoin pushes [] instead of op_false
+            Instruction::PushBytes(bytes) if bytes.is_empty() => last_op = Some(OP_FALSE),
+            Instruction::PushBytes(_) => last_op = None,
+            Instruction::Op(op) => last_op = Some(op),
+        }
+    }
+    None
+}
+
+/// Parses every envelope of a reveal transaction created with `create_batched_inscription`.
+/// `parse_transaction` only returns the first envelope.
+pub fn parse_batched_transaction(
+    tx: &Transaction,
+    rollup_name: &str,
+) -> Result<Vec<ParsedInscription>, ParserError> {
+    let script = get_script(tx)?;
+    let mut instructions = script.instructions().peekable();
+
+    let mut inscriptions = vec![];
+    // each call consumes instructions up to and including the end of an envelope
+    while instructions.peek().is_some() {
+        inscriptions.push(parse_relevant_inscriptions(&mut instructions, rollup_name)?);
+    }
+    Ok(inscriptions)
+}
+
 // Returns the script from the first input of the transaction
 fn get_script(tx: &Transaction) -> Result<&Script, ParserError> {
-    tx.input[0]
+    tx.input
+        .first()
+        .ok_or(ParserError::MissingInput)?
         .witness
         .tapscript()
         .ok_or(ParserError::NonTapscriptWitness)
 }
 
+// Returns the control block from the first input of the transaction
+fn get_control_block(tx: &Transaction) -> Result<ControlBlock, ParserError> {
+    let witness = &tx.input.first().ok_or(ParserError::MissingInput)?.witness;
+    // the control block follows the tapscript, unless an annex comes last
+    let control_block = match witness.last() {
+        Some(last) if witness.len() >= 2 && last.first() == Some(&TAPROOT_ANNEX_PREFIX) => {
+            witness.second_to_last()
+        }
+        last => last,
+    }
+    .filter(|_| witness.tapscript().is_some())
+    .ok_or(ParserError::NonTapscriptWitness)?;
+    ControlBlock::decode(control_block).map_err(|_| ParserError::InvalidControlBlock)
+}
+
+/// Rebuilds the taproot address the reveal transaction spends from, using its tapscript and control block.
+/// Auditors can compare it against the script of the commit output the reveal input points to.
+pub fn reveal_commitment_address(
+    tx: &Transaction,
+    network: Network,
+) -> Result<Address, ParserError> {
+    let script = get_script(tx)?;
+    let control_block = get_control_block(tx)?;
+
+    let output_key = single_leaf_output_key(script, control_block.internal_key);
+
+    Ok(Address::p2tr_tweaked(output_key, network))
+}
+
+/// Checks that the envelope of the reveal transaction matches `inscription` and that
+/// its control block commits to the envelope the way our builders create it:
+/// a single tapscript leaf whose internal key is the key checked by the script.
+/// Returns false for forged envelopes whose control block doesn't match.
+pub fn verify_reveal_commitment(
+    tx: &Transaction,
+    inscription: &ParsedInscription,
+    rollup_name: &str,
+) -> Result<bool, ParserError> {
+    let script = get_script(tx)?;
+    let control_block = get_control_block(tx)?;
+
+    let parsed = parse_relevant_inscriptions(&mut script.instructions().peekable(), rollup_name)?;
+    if parsed.body != inscription.body
+        || parsed.signature != inscription.signature
+        || parsed.public_key != inscription.public_key
+        || parsed.version != inscription.version
+        || parsed.compression != inscription.compression
+        || parsed.encryption != inscription.encryption
+        || parsed.extra_tags != inscription.extra_tags
+    {
+        return Ok(false);
+    }
+
+    // the envelope is the only leaf of the taptree
+    if control_block.leaf_version != LeafVersion::TapScript
+        || !control_block.merkle_branch.is_empty()
+    {
+        return Ok(false);
+    }
+
+    // the script starts with the key that is also the internal key
+    let checksig_key = match script.instructions().next() {
+        Some(Ok(Instruction::PushBytes(bytes))) => {
+            XOnlyPublicKey::from_slice(bytes.as_bytes()).ok()
+        }
+        _ => None,
+    };
+    if checksig_key != Some(control_block.internal_key) {
+        return Ok(false);
+    }
+
+    let output_key = single_leaf_output_key(script, control_block.internal_key);
+
+    Ok(control_block.verify_taproot_commitment(
+        &Secp256k1::verification_only(),
+        output_key.to_inner(),
+        script,
+    ))
+}
+
+// Computes the taproot output key of a taptree with `script` as its only leaf
+fn single_leaf_output_key(script: &Script, internal_key: XOnlyPublicKey) -> TweakedPublicKey {
+    let merkle_root = TapNodeHash::from(TapLeafHash::from_script(script, LeafVersion::TapScript));
+    let (output_key, _parity) =
+        internal_key.tap_tweak(&Secp256k1::verification_only(), Some(merkle_root));
+    output_key
+}
+
 // TODO: discuss removing tags
 // Parses the inscription from script if it is relevant to the rollup
 fn parse_relevant_inscriptions(
@@ -84,12 +328,31 @@ fn parse_relevant_inscriptions(
     let mut body: Vec<u8> = Vec::new();
     let mut signature: Vec<u8> = Vec::new();
     let mut public_key: Vec<u8> = Vec::new();
+    // envelopes created before the version tag was introduced are version 1
+    let mut version = None;
+    let mut has_version_tag = false;
+    // envelopes created before the compression tag was introduced are always compressed
+    let mut compression = CompressionScheme::Brotli;
+    let mut has_compression_tag = false;
+    // after the compression scheme, key-value tags may precede the (empty) body tag
+    let mut extra_tags = BTreeMap::new();
+    // the encryption scheme is declared with a reserved tag among the extra tags
+    let mut encryption = None;
+    let mut pending_tag: Option<Vec<u8>> = None;
+    let mut body_started = false;
 
     // this while loop is optimized for the least amount of iterations
     // for a strict envelope structure
     // nothing other than data pushes should be inside the envelope
     // the loop will break after the first envelope is parsed
-    while let Some(Ok(instruction)) = instructions.next() {
+    for instruction in instructions.by_ref() {
+        let instruction = match instruction {
+            Ok(instruction) => instruction,
+            // a truncated push would silently cut the envelope short
+            Err(_) if inside_envelope => return Err(ParserError::InvalidScript),
+            // witnesses that aren't a script at all have no envelope to parse
+            Err(_) => break,
+        };
         match instruction {
             Instruction::Op(OP_IF) => {
                 if last_op == Some(OP_FALSE) {
@@ -133,15 +396,36 @@ fn parse_relevant_inscriptions(
             }
             Instruction::PushBytes(bytes) => {
                 if inside_envelope {
+                    // the version tag and version are optional and sit between the rollup name
+                    // and the signature tag without moving the indices of the tags that follow
+                    if inside_envelope_index == 2 && version.is_none() {
+                        if has_version_tag {
+                            version = match bytes.as_bytes() {
+                                [ENVELOPE_VERSION] => Some(ENVELOPE_VERSION),
+                                [byte] => return Err(ParserError::UnsupportedVersion(*byte)),
+                                _ => return Err(ParserError::EnvelopeHasIncorrectFormat),
+                            };
+                            continue;
+                        } else if bytes.as_bytes() == VERSION_TAG {
+                            has_version_tag = true;
+                            continue;
+                        }
+                    }
+
                     // this looks ugly but we need to have least amount of
                     // iterations possible in a malicous case
                     // so if any of the conditions does not hold
                     // we return an error
+                    // the compression tag and scheme are optional and sit between the random
+                    // number and the body tag, moving the body tag from index 8 to index 10
+                    // or later if extra tags follow the compression scheme
                     if (inside_envelope_index == 0 && bytes.as_bytes() != ROLLUP_NAME_TAG)
                         || (inside_envelope_index == 2 && bytes.as_bytes() != SIGNATURE_TAG)
                         || (inside_envelope_index == 4 && bytes.as_bytes() != PUBLICKEY_TAG)
                         || (inside_envelope_index == 6 && bytes.as_bytes() != RANDOM_TAG)
-                        || (inside_envelope_index == 8 && bytes.as_bytes() != BODY_TAG)
+                        || (inside_envelope_index == 8
+                            && bytes.as_bytes() != BODY_TAG
+                            && bytes.as_bytes() != COMPRESSION_TAG)
                     {
                         return Err(ParserError::EnvelopeHasIncorrectFormat);
                     } else if inside_envelope_index == 1
@@ -152,7 +436,36 @@ fn parse_relevant_inscriptions(
                         signature.extend(bytes.as_bytes());
                     } else if inside_envelope_index == 5 {
                         public_key.extend(bytes.as_bytes());
-                    } else if inside_envelope_index >= 9 {
+                    } else if inside_envelope_index == 8 && bytes.as_bytes() == COMPRESSION_TAG {
+                        has_compression_tag = true;
+                    } else if has_compression_tag && inside_envelope_index == 9 {
+                        compression = match bytes.as_bytes() {
+                            [byte] => CompressionScheme::from_byte(*byte)
+                                .ok_or(ParserError::UnknownCompressionScheme)?,
+                            _ => return Err(ParserError::UnknownCompressionScheme),
+                        };
+                    } else if has_compression_tag && inside_envelope_index >= 10 {
+                        if body_started {
+                            body.extend(bytes.as_bytes());
+                        } else if let Some(tag) = pending_tag.take() {
+                            if tag == ENCRYPTION_TAG {
+                                let scheme = match bytes.as_bytes() {
+                                    [byte] => EncryptionScheme::from_byte(*byte)
+                                        .ok_or(ParserError::UnknownEncryptionScheme)?,
+                                    _ => return Err(ParserError::UnknownEncryptionScheme),
+                                };
+                                if encryption.replace(scheme).is_some() {
+                                    return Err(ParserError::DuplicateTag);
+                                }
+                            } else if extra_tags.insert(tag, bytes.as_bytes().to_vec()).is_some() {
+                                return Err(ParserError::DuplicateTag);
+                            }
+                        } else if bytes.as_bytes() == BODY_TAG {
+                            body_started = true;
+                        } else {
+                            pending_tag = Some(bytes.as_bytes().to_vec());
+                        }
+                    } else if !has_compression_tag && inside_envelope_index >= 9 {
                         body.extend(bytes.as_bytes());
                     }
 
@@ -180,6 +493,10 @@ fn parse_relevant_inscriptions(
         body,
         signature,
         public_key,
+        version: version.unwrap_or(ENVELOPE_VERSION),
+        compression,
+        encryption: encryption.unwrap_or_default(),
+        extra_tags,
     })
 }
 
@@ -203,12 +520,13 @@ mod tests {
     use bitcoin::opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_IF};
     use bitcoin::opcodes::{OP_FALSE, OP_TRUE};
     use bitcoin::script::{self, PushBytesBuf};
-    use bitcoin::Transaction;
+    use bitcoin::{Transaction, TxIn, Witness};
 
     use super::{
-        parse_relevant_inscriptions, BODY_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG,
-        SIGNATURE_TAG,
+        parse_relevant_inscriptions, peek_rollup_name, BODY_TAG, COMPRESSION_TAG, PUBLICKEY_TAG,
+        RANDOM_TAG, ROLLUP_NAME_TAG, SIGNATURE_TAG, VERSION_TAG,
     };
+    use crate::helpers::compression::CompressionScheme;
     use crate::helpers::parsers::{parse_transaction, ParserError};
 
     #[test]
@@ -242,6 +560,58 @@ mod tests {
         assert_eq!(result.body, vec![0u8; 128]);
         assert_eq!(result.signature, vec![0u8; 64]);
         assert_eq!(result.public_key, vec![0u8; 64]);
+        // no compression tag, the body is compressed
+        assert_eq!(result.compression, CompressionScheme::Brotli);
+    }
+
+    #[test]
+    fn compression_tag() {
+        let reveal_script = |compression: Vec<u8>| {
+            script::Builder::new()
+                .push_x_only_key(&XOnlyPublicKey::from_slice(&[1; 32]).unwrap())
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_int(0)
+                .push_slice(PushBytesBuf::try_from(COMPRESSION_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(compression).unwrap())
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![1u8; 128]).unwrap())
+                .push_opcode(OP_ENDIF)
+                .into_script()
+        };
+
+        for compression in [CompressionScheme::None, CompressionScheme::Brotli] {
+            let result = parse_relevant_inscriptions(
+                &mut reveal_script(vec![compression.to_byte()])
+                    .instructions()
+                    .peekable(),
+                "sov-btc",
+            )
+            .unwrap();
+
+            assert_eq!(result.body, vec![1u8; 128]);
+            assert_eq!(result.compression, compression);
+        }
+
+        let result = parse_relevant_inscriptions(
+            &mut reveal_script(vec![7]).instructions().peekable(),
+            "sov-btc",
+        );
+        assert_eq!(result.unwrap_err(), ParserError::UnknownCompressionScheme);
+
+        let result = parse_relevant_inscriptions(
+            &mut reveal_script(vec![0, 0]).instructions().peekable(),
+            "sov-btc",
+        );
+        assert_eq!(result.unwrap_err(), ParserError::UnknownCompressionScheme);
     }
 
     #[test]
@@ -394,6 +764,117 @@ mod tests {
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
     }
 
+    #[test]
+    fn extra_tags() {
+        let reveal_script = |tags: &[(&[u8], &[u8])]| {
+            let mut builder = script::Builder::new()
+                .push_x_only_key(&XOnlyPublicKey::from_slice(&[1; 32]).unwrap())
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_int(0)
+                .push_slice(PushBytesBuf::try_from(COMPRESSION_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::from([CompressionScheme::None.to_byte()]));
+            for (tag, value) in tags {
+                builder = builder
+                    .push_slice(PushBytesBuf::try_from(tag.to_vec()).unwrap())
+                    .push_slice(PushBytesBuf::try_from(value.to_vec()).unwrap());
+            }
+            builder
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![1u8; 128]).unwrap())
+                .push_opcode(OP_ENDIF)
+                .into_script()
+        };
+
+        let result = parse_relevant_inscriptions(
+            &mut reveal_script(&[(b"version", b"2"), (b"flags", b"")])
+                .instructions()
+                .peekable(),
+            "sov-btc",
+        )
+        .unwrap();
+        assert_eq!(result.body, vec![1u8; 128]);
+        assert_eq!(result.compression, CompressionScheme::None);
+        assert_eq!(result.extra_tags.len(), 2);
+        assert_eq!(result.extra_tags[b"version".as_slice()], b"2".to_vec());
+        assert_eq!(result.extra_tags[b"flags".as_slice()], Vec::<u8>::new());
+
+        let result = parse_relevant_inscriptions(
+            &mut reveal_script(&[]).instructions().peekable(),
+            "sov-btc",
+        )
+        .unwrap();
+        assert!(result.extra_tags.is_empty());
+
+        let result = parse_relevant_inscriptions(
+            &mut reveal_script(&[(b"version", b"2"), (b"version", b"3")])
+                .instructions()
+                .peekable(),
+            "sov-btc",
+        );
+        assert_eq!(result.unwrap_err(), ParserError::DuplicateTag);
+    }
+
+    #[test]
+    fn envelope_version() {
+        let reveal_script = |version: Option<Vec<u8>>| {
+            let mut builder = script::Builder::new()
+                .push_x_only_key(&XOnlyPublicKey::from_slice(&[1; 32]).unwrap())
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap());
+            if let Some(version) = version {
+                builder = builder
+                    .push_slice(PushBytesBuf::try_from(VERSION_TAG.to_vec()).unwrap())
+                    .push_slice(PushBytesBuf::try_from(version).unwrap());
+            }
+            builder
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_int(0)
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![1u8; 128]).unwrap())
+                .push_opcode(OP_ENDIF)
+                .into_script()
+        };
+        let parse = |version: Option<Vec<u8>>| {
+            parse_relevant_inscriptions(
+                &mut reveal_script(version).instructions().peekable(),
+                "sov-btc",
+            )
+        };
+
+        let result = parse(Some(vec![1])).unwrap();
+        assert_eq!(result.version, 1);
+        assert_eq!(result.body, vec![1u8; 128]);
+        assert_eq!(result.signature, vec![0u8; 64]);
+
+        // envelopes without a version tag are version 1
+        assert_eq!(parse(None).unwrap().version, 1);
+
+        assert_eq!(
+            parse(Some(vec![2])).unwrap_err(),
+            ParserError::UnsupportedVersion(2)
+        );
+        assert_eq!(
+            parse(Some(vec![1, 0])).unwrap_err(),
+            ParserError::EnvelopeHasIncorrectFormat
+        );
+    }
+
     #[test]
     fn non_parseable_tx() {
         let hex_tx = "020000000001013a66019bfcc719ba12586a83ebbb0b3debdc945f563cd64fd44c8044e3d3a1790100000000fdffffff028fa2aa060000000017a9147ba15d4e0d8334de3a68cf3687594e2d1ee5b00d879179e0090000000016001493c93ad222e57d65438545e048822ede2d418a3d0247304402202432e6c422b93705fbc57b350ea43e4ef9441c0907988eff051eaac807fc8cf2022046c92b540b5f04f8da11febb5d2a478aed1b8bc088e769da8b78fffcae8c9a9a012103e2991b47d9c788f55379f9ef519b642d79d7dfe0e7555ec5575ee934b2dca1223f5d0c00";
@@ -407,6 +888,45 @@ mod tests {
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
     }
 
+    #[test]
+    fn peek_rollup_name_of_inscription() {
+        let reveal_script = script::Builder::new()
+            .push_x_only_key(&XOnlyPublicKey::from_slice(&[1; 32]).unwrap())
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("test_rollup".as_bytes().to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let mut witness = Witness::new();
+        witness.push([0u8; 64]);
+        witness.push(reveal_script);
+        witness.push([0xc0u8; 33]);
+        let tx = Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                witness,
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+
+        // the rest of the envelope is malformed, but only the name is looked at
+        assert_eq!(peek_rollup_name(&tx), Some("test_rollup".to_string()));
+
+        // a plain P2WPKH payment
+        let hex_tx = "020000000001013a66019bfcc719ba12586a83ebbb0b3debdc945f563cd64fd44c8044e3d3a1790100000000fdffffff028fa2aa060000000017a9147ba15d4e0d8334de3a68cf3687594e2d1ee5b00d879179e0090000000016001493c93ad222e57d65438545e048822ede2d418a3d0247304402202432e6c422b93705fbc57b350ea43e4ef9441c0907988eff051eaac807fc8cf2022046c92b540b5f04f8da11febb5d2a478aed1b8bc088e769da8b78fffcae8c9a9a012103e2991b47d9c788f55379f9ef519b642d79d7dfe0e7555ec5575ee934b2dca1223f5d0c00";
+        let tx: Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(hex_tx).unwrap()).unwrap();
+
+        assert_eq!(peek_rollup_name(&tx), None);
+    }
+
     #[test]
     fn only_checksig() {
         let reveal_script = script::Builder::new()
@@ -534,4 +1054,114 @@ mod tests {
         assert_eq!(result.signature, vec![0u8; 64]);
         assert_eq!(result.public_key, vec![0u8; 64]);
     }
+
+    #[test]
+    fn transaction_without_inputs() {
+        let tx = Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+
+        assert_eq!(
+            parse_transaction(&tx, "sov-btc").unwrap_err(),
+            ParserError::MissingInput
+        );
+        assert_eq!(
+            super::reveal_commitment_address(&tx, bitcoin::Network::Bitcoin).unwrap_err(),
+            ParserError::MissingInput
+        );
+        assert_eq!(peek_rollup_name(&tx), None);
+    }
+
+    #[test]
+    fn truncated_push() {
+        let mut reveal_script = script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+            .push_int(0)
+            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![1u8; 128]).unwrap())
+            .into_bytes();
+        // OP_PUSHDATA2 claiming more bytes than there are left
+        reveal_script.extend([0x4d, 0xff, 0xff, 1, 2, 3]);
+        let reveal_script = bitcoin::ScriptBuf::from_bytes(reveal_script);
+
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+        assert_eq!(result.unwrap_err(), ParserError::InvalidScript);
+    }
+
+    fn transaction_with_witness(witness: Vec<Vec<u8>>) -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                witness: Witness::from_slice(&witness),
+                ..Default::default()
+            }],
+            output: vec![],
+        }
+    }
+
+    // starts most scripts with an envelope, so that random bytes reach the envelope parser
+    fn arbitrary_reveal_script() -> impl proptest::strategy::Strategy<Value = Vec<u8>> {
+        use proptest::prelude::*;
+
+        let envelope_start = script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+            .into_bytes();
+        (
+            any::<bool>(),
+            proptest::collection::vec(any::<u8>(), 0..1_000),
+        )
+            .prop_map(move |(with_envelope, bytes)| {
+                if with_envelope {
+                    [envelope_start.clone(), bytes].concat()
+                } else {
+                    bytes
+                }
+            })
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::test_runner::Config::with_cases(5_000))]
+
+        #[test]
+        fn parse_arbitrary_witness(
+            witness in proptest::collection::vec(proptest::collection::vec(proptest::prelude::any::<u8>(), 0..600), 0..5),
+        ) {
+            let tx = transaction_with_witness(witness);
+
+            // must return, never panic
+            let _ = parse_transaction(&tx, "sov-btc");
+            let _ = super::parse_batched_transaction(&tx, "sov-btc");
+            let _ = super::reveal_commitment_address(&tx, bitcoin::Network::Bitcoin);
+            let _ = peek_rollup_name(&tx);
+        }
+
+        #[test]
+        fn parse_arbitrary_reveal_script(
+            reveal_script in arbitrary_reveal_script(),
+            control_block in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..100),
+        ) {
+            let tx = transaction_with_witness(vec![vec![0; 64], reveal_script, control_block]);
+
+            let _ = parse_transaction(&tx, "sov-btc");
+            let _ = super::parse_batched_transaction(&tx, "sov-btc");
+            let _ = super::reveal_commitment_address(&tx, bitcoin::Network::Bitcoin);
+            let _ = peek_rollup_name(&tx);
+        }
+    }
 }
@@ -0,0 +1,108 @@
+//! Deterministic keys and signing for tests going through the inscribe, parse and verify path.
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::Transaction;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::helpers::builders::{InscriptionBuilder, TxWithId};
+use crate::spec::utxo::UTXO;
+
+/// Key pair derived from `seed`, the same seed always gives the same keys.
+pub fn deterministic_keypair(seed: u64) -> (SecretKey, PublicKey) {
+    let secret_key =
+        SecretKey::from_slice(&sha256::Hash::hash(&seed.to_le_bytes()).to_byte_array())
+            .expect("sha256 digest is a valid secret key");
+    let public_key = secret_key.public_key(&Secp256k1::new());
+    (secret_key, public_key)
+}
+
+/// Signs `body` under the declared schemes with the key pair of `seed` and builds the
+/// inscription with `builder`, which must be set up with everything except the body, the
+/// signature and the sequencer public key. The commit key and reveal signature are drawn from an rng seeded with `seed`
+/// too, so the same inputs always give the same transactions.
+pub fn sign_and_build_inscription(
+    builder: InscriptionBuilder,
+    body: Vec<u8>,
+    seed: u64,
+) -> Result<(Transaction, TxWithId, Option<UTXO>), anyhow::Error> {
+    let (secret_key, _) = deterministic_keypair(seed);
+
+    builder
+        .body(body)
+        .sign_with(&secret_key)
+        .build_with_rng(&mut StdRng::seed_from_u64(seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::secp256k1::{ecdsa, Message, Secp256k1};
+    use bitcoin::{Address, Network, Txid};
+
+    use super::{deterministic_keypair, sign_and_build_inscription};
+    use crate::helpers::builders::{FeeRate, InscriptionBuilder};
+    use crate::helpers::compression::CompressionScheme;
+    use crate::helpers::encryption::EncryptionScheme;
+    use crate::helpers::parsers::{parse_transaction, signed_body_hash};
+    use crate::spec::utxo::UTXO;
+
+    #[test]
+    fn inscribe_parse_and_verify() {
+        let address =
+            Address::from_str("bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9")
+                .unwrap()
+                .require_network(Network::Bitcoin)
+                .unwrap();
+        let utxo = UTXO {
+            tx_id: Txid::from_str(
+                "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            )
+            .unwrap(),
+            vout: 0,
+            address: None,
+            script_pubkey: address.script_pubkey().to_hex_string(),
+            amount: 1_000_000,
+            confirmations: 100,
+            spendable: true,
+            solvable: true,
+        };
+        let builder = InscriptionBuilder::new()
+            .rollup_name("test_rollup")
+            .compression(CompressionScheme::None)
+            .envelope_tags(true)
+            .utxos(vec![utxo])
+            .change_address(address.clone())
+            .reveal_recipient(address)
+            .fee_rates(
+                FeeRate::from_sat_per_vb(12.0).unwrap(),
+                FeeRate::from_sat_per_vb(10.0).unwrap(),
+            )
+            .network(Network::Bitcoin);
+        let body = vec![7; 500];
+
+        let (commit, reveal, _) =
+            sign_and_build_inscription(builder.clone(), body.clone(), 42).unwrap();
+        let (same_commit, same_reveal, _) =
+            sign_and_build_inscription(builder, body.clone(), 42).unwrap();
+        assert_eq!(commit, same_commit);
+        assert_eq!(reveal.tx, same_reveal.tx);
+        assert_eq!(deterministic_keypair(42), deterministic_keypair(42));
+        assert_ne!(deterministic_keypair(42), deterministic_keypair(43));
+
+        let inscription = parse_transaction(&reveal.tx, "test_rollup").unwrap();
+        let (_, public_key) = deterministic_keypair(42);
+        assert_eq!(inscription.body, body);
+        assert_eq!(inscription.public_key, public_key.serialize().to_vec());
+
+        let hash = signed_body_hash(&body, CompressionScheme::None, EncryptionScheme::None);
+        let message = Message::from_digest(hash);
+        let signature = ecdsa::Signature::from_compact(&inscription.signature).unwrap();
+        Secp256k1::new()
+            .verify_ecdsa(&message, &signature, &public_key)
+            .unwrap();
+        assert_eq!(inscription.get_sig_verified_hash(), Some(hash));
+    }
+}
@@ -30,7 +30,7 @@ pub(crate) fn get_blob_with_sender(tx: &Transaction) -> BlobWithSender {
     let blob = parsed_inscription.body;
 
     // Decompress the blob
-    let decompressed_blob = decompress_blob(&blob);
+    let decompressed_blob = decompress_blob(&blob).unwrap();
 
     BlobWithSender::new(
         decompressed_blob,
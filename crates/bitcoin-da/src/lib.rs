@@ -1,9 +1,13 @@
-mod helpers;
+pub mod helpers;
 pub mod spec;
 
 #[cfg(feature = "native")]
 pub mod service;
 pub mod verifier;
 
+/// Value of the reveal output created by the inscription builders.
 #[cfg(feature = "native")]
-const REVEAL_OUTPUT_AMOUNT: u64 = 546;
+pub const REVEAL_OUTPUT_AMOUNT: u64 = 546;
+/// Change outputs below this value are left to the miners instead.
+#[cfg(feature = "native")]
+pub const DUST_THRESHOLD: u64 = 546;
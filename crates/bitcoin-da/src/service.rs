@@ -13,7 +13,7 @@ use async_trait::async_trait;
 use bitcoin::block::Header;
 use bitcoin::consensus::{encode, Decodable};
 use bitcoin::hash_types::WitnessMerkleNode;
-use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::SecretKey;
 use bitcoin::{merkle_tree, Amount, BlockHash, CompactTarget, Transaction, Txid, Wtxid};
 use bitcoincore_rpc::jsonrpc_async::Error as RpcError;
@@ -26,9 +26,10 @@ use tokio::sync::oneshot::channel as oneshot_channel;
 use tracing::{debug, error, info, instrument, trace};
 
 use crate::helpers::builders::{
-    create_inscription_transactions, sign_blob_with_private_key, write_reveal_tx, TxWithId,
+    sign_blob_with_private_key, write_reveal_tx, BitcoinDaConfig, FeeRate, InscriptionBuilder,
+    TxWithId,
 };
-use crate::helpers::compression::{compress_blob, decompress_blob};
+use crate::helpers::compression::{compress_blob, CompressionScheme};
 use crate::helpers::parsers::parse_transaction;
 use crate::spec::blob::BlobWithSender;
 use crate::spec::block::BitcoinBlock;
@@ -39,7 +40,7 @@ use crate::spec::transaction::TransactionWrapper;
 use crate::spec::utxo::UTXO;
 use crate::spec::{BitcoinSpec, RollupParams};
 use crate::verifier::BitcoinVerifier;
-use crate::REVEAL_OUTPUT_AMOUNT;
+use crate::DUST_THRESHOLD;
 
 /// A service that provides data and data availability proofs for Bitcoin
 #[derive(Debug)]
@@ -49,6 +50,8 @@ pub struct BitcoinService {
     network: bitcoin::Network,
     da_private_key: Option<SecretKey>,
     reveal_tx_id_prefix: Vec<u8>,
+    randomize_commit_outputs: bool,
+    envelope_tags: bool,
     inscribes_queue: UnboundedSender<SenderWithNotifier<TxidWrapper>>,
 }
 
@@ -68,6 +71,15 @@ pub struct DaServiceConfig {
 
     // number of last paid fee rates to average if estimation fails
     pub fee_rates_to_avg: Option<usize>,
+
+    // place the change output of commit txs at a random position
+    #[serde(default)]
+    pub randomize_commit_outputs: bool,
+
+    // write the envelope version and compression scheme into inscriptions,
+    // only enable once all full nodes and provers parse them
+    #[serde(default)]
+    pub envelope_tags: bool,
 }
 
 const FINALITY_DEPTH: u64 = 4; // blocks
@@ -98,6 +110,8 @@ impl BitcoinService {
             config.network,
             private_key,
             chain_params.reveal_tx_id_prefix,
+            config.randomize_commit_outputs,
+            config.envelope_tags,
             tx,
         )
         .await)
@@ -197,16 +211,21 @@ impl BitcoinService {
             network: config.network,
             da_private_key: private_key,
             reveal_tx_id_prefix: chain_params.reveal_tx_id_prefix,
+            randomize_commit_outputs: config.randomize_commit_outputs,
+            envelope_tags: config.envelope_tags,
             inscribes_queue: tx,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn with_client(
         client: Client,
         rollup_name: String,
         network: bitcoin::Network,
         da_private_key: Option<SecretKey>,
         reveal_tx_id_prefix: Vec<u8>,
+        randomize_commit_outputs: bool,
+        envelope_tags: bool,
         inscribes_queue: UnboundedSender<SenderWithNotifier<TxidWrapper>>,
     ) -> Self {
         let wallets = client
@@ -224,6 +243,8 @@ impl BitcoinService {
             network,
             da_private_key,
             reveal_tx_id_prefix,
+            randomize_commit_outputs,
+            envelope_tags,
             inscribes_queue,
         }
     }
@@ -241,9 +262,7 @@ impl BitcoinService {
         let utxos: Vec<UTXO> = utxos
             .into_iter()
             .filter(|utxo| {
-                utxo.spendable
-                    && utxo.solvable
-                    && utxo.amount > Amount::from_sat(REVEAL_OUTPUT_AMOUNT)
+                utxo.spendable && utxo.solvable && utxo.amount > Amount::from_sat(DUST_THRESHOLD)
             })
             .map(Into::into)
             .collect();
@@ -316,21 +335,27 @@ impl BitcoinService {
         let (signature, public_key) =
             sign_blob_with_private_key(&blob, &da_private_key).expect("Sequencer sign the blob");
 
+        let fee_rate = FeeRate::from_sat_per_vb(fee_sat_per_vbyte)?;
+        let config = BitcoinDaConfig {
+            default_reveal_prefix: self.reveal_tx_id_prefix.clone(),
+            randomize_commit_outputs: self.randomize_commit_outputs,
+            envelope_tags: self.envelope_tags,
+            ..BitcoinDaConfig::new(network, fee_rate, fee_rate)
+        };
+
         // create inscribe transactions
-        let (unsigned_commit_tx, reveal_tx) = create_inscription_transactions(
-            &rollup_name,
-            blob,
-            signature,
-            public_key,
-            prev_tx,
-            utxos,
-            address,
-            REVEAL_OUTPUT_AMOUNT,
-            fee_sat_per_vbyte,
-            fee_sat_per_vbyte,
-            network,
-            self.reveal_tx_id_prefix.as_slice(),
-        )?;
+        let (unsigned_commit_tx, reveal_tx, _) = InscriptionBuilder::new()
+            .config(&config)
+            .rollup_name(rollup_name)
+            .body(blob)
+            .compression(CompressionScheme::Brotli)
+            .signature(signature)
+            .sequencer_public_key(public_key)
+            .prev_tx(prev_tx)
+            .utxos(utxos)
+            .change_address(address.clone())
+            .reveal_recipient(address)
+            .build()?;
 
         // sign inscribe transactions
         // let serialized_unsigned_commit_tx = &encode::serialize(&unsigned_commit_tx);
@@ -374,7 +399,7 @@ impl BitcoinService {
     pub async fn get_fee_rate_as_sat_vb_ceiled(&self) -> Result<f64, anyhow::Error> {
         let smart_fee = self.client.estimate_smart_fee(1, None).await?;
         let btc_vkb = smart_fee.fee_rate.map_or(0.00001f64, |rate| rate.to_btc());
-        Ok((btc_vkb * 100_000_000.0 / 1000.0).ceil())
+        Ok(FeeRate::from_btc_per_kvb(btc_vkb)?.as_sat_per_vb().ceil())
     }
 }
 
@@ -670,15 +695,13 @@ fn get_relevant_blobs_from_txs(
         let parsed_inscription = parse_transaction(&tx, rollup_name);
 
         if let Ok(inscription) = parsed_inscription {
-            if inscription.get_sig_verified_hash().is_some() {
-                // Decompress the blob
-                let decompressed_blob = decompress_blob(&inscription.body);
-
-                let relevant_tx = BlobWithSender::new(
-                    decompressed_blob,
-                    inscription.public_key,
-                    sha256d::Hash::hash(&inscription.body).to_byte_array(),
-                );
+            // the verifier checks the blob hash against the signed hash
+            if let Some(blob_hash) = inscription.get_sig_verified_hash() {
+                // bodies that don't decompress carry no blob, the verifier skips them too
+                let Ok(blob) = inscription.blob() else {
+                    continue;
+                };
+                let relevant_tx = BlobWithSender::new(blob, inscription.public_key, blob_hash);
 
                 relevant_txs.push(relevant_tx);
             }
@@ -719,6 +742,8 @@ mod tests {
                 "E9873D79C6D87DC0FB6A5778633389F4453213303DA61F20BD67FC233AA33262".to_string(), // Test key, safe to publish
             ),
             fee_rates_to_avg: Some(2), // small to speed up tests
+            randomize_commit_outputs: false,
+            envelope_tags: false,
         };
 
         BitcoinService::new_without_client(
@@ -836,6 +861,8 @@ mod tests {
                 "E9873D79C6D87DC0FB6A5778633389F4453213303DA61F20BD67FC233AA33261".to_string(), // Test key, safe to publish
             ),
             fee_rates_to_avg: Some(2), // small to speed up tests
+            randomize_commit_outputs: false,
+            envelope_tags: false,
         };
 
         let incorrect_service = BitcoinService::new_without_client(
@@ -1,21 +1,109 @@
+use core::str::FromStr;
+
+use anyhow::Context;
 use bitcoin::address::NetworkUnchecked;
-use bitcoin::{Address, Txid};
+use bitcoin::{Address, ScriptBuf, Txid};
 #[cfg(feature = "native")]
 use bitcoincore_rpc::json::ListUnspentResultEntry;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+/// Serializes to the same JSON shape as an entry of bitcoind's `listunspent`,
+/// so the amount is in BTC while [`UTXO::amount`] is in sats.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct UTXO {
+    #[serde(rename = "txid")]
     pub tx_id: Txid,
     pub vout: u32,
     pub address: Option<Address<NetworkUnchecked>>,
+    #[serde(rename = "scriptPubKey")]
     pub script_pubkey: String,
+    #[serde(with = "btc_amount")]
     pub amount: u64,
     pub confirmations: u32,
     pub spendable: bool,
     pub solvable: bool,
 }
 
+impl UTXO {
+    /// Creates a UTXO, failing early if the tx id or the script pubkey are not valid hex.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_checked(
+        tx_id: &str,
+        vout: u32,
+        address: Option<Address<NetworkUnchecked>>,
+        script_pubkey: String,
+        amount: u64,
+        confirmations: u32,
+        spendable: bool,
+        solvable: bool,
+    ) -> anyhow::Result<Self> {
+        let tx_id = Txid::from_str(tx_id).with_context(|| format!("invalid tx id {tx_id}"))?;
+        let utxo = Self {
+            tx_id,
+            vout,
+            address,
+            script_pubkey,
+            amount,
+            confirmations,
+            spendable,
+            solvable,
+        };
+        utxo.script_buf()?;
+        Ok(utxo)
+    }
+
+    /// Decodes the hex encoded script pubkey.
+    pub fn script_buf(&self) -> anyhow::Result<ScriptBuf> {
+        ScriptBuf::from_hex(&self.script_pubkey)
+            .with_context(|| format!("invalid script pubkey {}", self.script_pubkey))
+    }
+
+    /// Type of the script pubkey, `None` if it is not valid hex or of none of the standard types.
+    pub fn script_type(&self) -> Option<ScriptType> {
+        let script = self.script_buf().ok()?;
+        if script.is_p2pkh() {
+            Some(ScriptType::P2pkh)
+        } else if script.is_p2sh() {
+            Some(ScriptType::P2sh)
+        } else if script.is_p2wpkh() {
+            Some(ScriptType::P2wpkh)
+        } else if script.is_p2wsh() {
+            Some(ScriptType::P2wsh)
+        } else if script.is_p2tr() {
+            Some(ScriptType::P2tr)
+        } else {
+            None
+        }
+    }
+}
+
+/// Standard script pubkey types of a [`UTXO`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+}
+
+// (De)serializes an amount in sats as BTC
+mod btc_amount {
+    use bitcoin::Amount;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amount: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(Amount::from_sat(*amount).to_btc())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let btc = f64::deserialize(deserializer)?;
+        Amount::from_btc(btc)
+            .map(Amount::to_sat)
+            .map_err(de::Error::custom)
+    }
+}
+
 #[cfg(feature = "native")]
 impl From<ListUnspentResultEntry> for UTXO {
     fn from(v: ListUnspentResultEntry) -> Self {
@@ -32,6 +120,74 @@ impl From<ListUnspentResultEntry> for UTXO {
     }
 }
 
+// Result of bitcoind's `scantxoutset start`
+#[derive(Deserialize)]
+struct ScanTxOutSetResult {
+    success: bool,
+    height: u32,
+    unspents: Vec<ScanTxOutSetUnspent>,
+}
+
+#[derive(Deserialize)]
+struct ScanTxOutSetUnspent {
+    txid: Txid,
+    vout: u32,
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: String,
+    desc: String,
+    #[serde(with = "btc_amount")]
+    amount: u64,
+    height: u32,
+}
+
+/// Parses the result of bitcoind's `scantxoutset start` into UTXOs, so UTXOs found by scanning
+/// descriptors can be used with the builders. Confirmations are counted from the scanned height.
+/// The address is taken from `addr(...)` descriptors. Outputs of `addr(...)` and `raw(...)`
+/// descriptors are not solvable, all outputs are assumed to be spendable by the scanning wallet.
+pub fn utxos_from_scan_result(json: &serde_json::Value) -> anyhow::Result<Vec<UTXO>> {
+    let result = ScanTxOutSetResult::deserialize(json).context("invalid scantxoutset result")?;
+    if !result.success {
+        anyhow::bail!("scantxoutset did not complete");
+    }
+
+    result
+        .unspents
+        .into_iter()
+        .map(|unspent| {
+            // descriptors end with a checksum after '#'
+            let desc = unspent.desc.split('#').next().unwrap_or_default();
+            let address = desc
+                .strip_prefix("addr(")
+                .and_then(|desc| desc.strip_suffix(')'))
+                .map(Address::<NetworkUnchecked>::from_str)
+                .transpose()
+                .with_context(|| format!("invalid address in descriptor {}", unspent.desc))?;
+            let solvable = !desc.starts_with("addr(") && !desc.starts_with("raw(");
+            let confirmations = (result.height + 1)
+                .checked_sub(unspent.height)
+                .with_context(|| {
+                    format!(
+                        "UTXO {}:{} is above the scanned height {}",
+                        unspent.txid, unspent.vout, result.height
+                    )
+                })?;
+
+            let utxo = UTXO {
+                tx_id: unspent.txid,
+                vout: unspent.vout,
+                address,
+                script_pubkey: unspent.script_pub_key,
+                amount: unspent.amount,
+                confirmations,
+                spendable: true,
+                solvable,
+            };
+            utxo.script_buf()?;
+            Ok(utxo)
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListUnspentEntry {
     pub txid: Txid,
@@ -54,3 +210,177 @@ pub struct ListUnspentEntry {
     pub parent_descs: Option<Vec<String>>,
     pub safe: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{utxos_from_scan_result, UTXO};
+
+    const TX_ID: &str = "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7";
+
+    #[test]
+    fn test_new_checked() {
+        let utxo = UTXO::new_checked(
+            TX_ID,
+            0,
+            None,
+            "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d".to_string(),
+            100_000,
+            1,
+            true,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(utxo.tx_id.to_string(), TX_ID);
+        assert_eq!(
+            utxo.script_buf().unwrap().to_hex_string(),
+            utxo.script_pubkey
+        );
+    }
+
+    #[test]
+    fn test_listunspent_json_round_trip() {
+        let entry = r#"{
+            "txid": "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            "vout": 1,
+            "address": "bcrt1qk4zr7mq7eahshxsvrlc6tmmtfcdkekyaq7f9z2",
+            "label": "",
+            "scriptPubKey": "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d",
+            "amount": 0.50001234,
+            "confirmations": 101,
+            "spendable": true,
+            "solvable": true,
+            "desc": "wpkh([2bc9b9a6/84h/1h/0h/0/0]02f94e6e2e63e0c1e6a7d1bf5b8f3d50b5e0e1e1f2a6d9e2ed5c2a7f3c0c3a5d1e)#5u6rqzyv",
+            "parent_descs": [],
+            "safe": true
+        }"#;
+
+        let utxo: UTXO = serde_json::from_str(entry).unwrap();
+        assert_eq!(utxo.tx_id.to_string(), TX_ID);
+        assert_eq!(utxo.vout, 1);
+        assert_eq!(
+            utxo.address.clone().unwrap().assume_checked().to_string(),
+            "bcrt1qk4zr7mq7eahshxsvrlc6tmmtfcdkekyaq7f9z2"
+        );
+        assert_eq!(
+            utxo.script_pubkey,
+            "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d"
+        );
+        assert_eq!(utxo.amount, 50_001_234);
+        assert_eq!(utxo.confirmations, 101);
+        assert!(utxo.spendable);
+        assert!(utxo.solvable);
+
+        let json = serde_json::to_value(&utxo).unwrap();
+        assert_eq!(json["txid"], TX_ID);
+        assert_eq!(json["scriptPubKey"], utxo.script_pubkey);
+        assert_eq!(json["amount"], 0.50001234);
+        assert_eq!(serde_json::from_value::<UTXO>(json).unwrap(), utxo);
+    }
+
+    #[test]
+    fn test_new_checked_invalid_script_pubkey() {
+        let utxo = UTXO::new_checked(
+            TX_ID,
+            0,
+            None,
+            "not hex".to_string(),
+            100_000,
+            1,
+            true,
+            true,
+        );
+
+        assert!(utxo.is_err());
+    }
+
+    #[test]
+    fn test_new_checked_invalid_tx_id() {
+        let utxo = UTXO::new_checked(
+            "1234",
+            0,
+            None,
+            "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d".to_string(),
+            100_000,
+            1,
+            true,
+            true,
+        );
+
+        assert!(utxo.is_err());
+    }
+
+    #[test]
+    fn test_utxos_from_scan_result() {
+        let result = serde_json::json!({
+            "success": true,
+            "txouts": 9_013_526,
+            "height": 200,
+            "bestblock": "3e2e3d5bd4b6b8bfa3be2ce1da0d8e2d5a23d9d1e24d5c3b0e4b4b3aa3bc1e6f",
+            "unspents": [
+                {
+                    "txid": TX_ID,
+                    "vout": 1,
+                    "scriptPubKey": "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d",
+                    "desc": "addr(bcrt1qk4zr7mq7eahshxsvrlc6tmmtfcdkekyaq7f9z2)#3mghlq7m",
+                    "amount": 0.50001234,
+                    "coinbase": false,
+                    "height": 100,
+                    "blockhash": "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206"
+                },
+                {
+                    "txid": TX_ID,
+                    "vout": 2,
+                    "scriptPubKey": "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d",
+                    "desc": "wpkh(02f94e6e2e63e0c1e6a7d1bf5b8f3d50b5e0e1e1f2a6d9e2ed5c2a7f3c0c3a5d1e)#5u6rqzyv",
+                    "amount": 0.001,
+                    "coinbase": false,
+                    "height": 200,
+                    "blockhash": "3e2e3d5bd4b6b8bfa3be2ce1da0d8e2d5a23d9d1e24d5c3b0e4b4b3aa3bc1e6f"
+                }
+            ],
+            "total_amount": 0.50101234
+        });
+
+        let utxos = utxos_from_scan_result(&result).unwrap();
+        assert_eq!(
+            utxos,
+            vec![
+                UTXO::new_checked(
+                    TX_ID,
+                    1,
+                    Some(
+                        "bcrt1qk4zr7mq7eahshxsvrlc6tmmtfcdkekyaq7f9z2"
+                            .parse()
+                            .unwrap()
+                    ),
+                    "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d".to_string(),
+                    50_001_234,
+                    101,
+                    true,
+                    false,
+                )
+                .unwrap(),
+                UTXO::new_checked(
+                    TX_ID,
+                    2,
+                    None,
+                    "0014b5443f6c1ecf6f0b9a0c1ff1a5ef6b4e1b6cd89d".to_string(),
+                    100_000,
+                    1,
+                    true,
+                    true,
+                )
+                .unwrap(),
+            ]
+        );
+
+        let mut aborted = result.clone();
+        aborted["success"] = false.into();
+        assert!(utxos_from_scan_result(&aborted).is_err());
+
+        let mut invalid_script = result;
+        invalid_script["unspents"][0]["scriptPubKey"] = "not hex".into();
+        assert!(utxos_from_scan_result(&invalid_script).is_err());
+    }
+}
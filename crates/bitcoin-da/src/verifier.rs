@@ -9,7 +9,6 @@ use sov_rollup_interface::digest::Digest;
 use sov_rollup_interface::zk::ValidityCondition;
 use thiserror::Error;
 
-use crate::helpers::compression::decompress_blob;
 use crate::helpers::parsers::parse_transaction;
 use crate::spec::BitcoinSpec;
 
@@ -59,6 +58,8 @@ pub struct ChainValidityCondition {
 pub enum ValidityConditionError {
     #[error("conditions for validity can only be combined if the blocks are consecutive")]
     BlocksNotConsecutive,
+    #[error("condition for validity does not match the block header")]
+    BlockHeaderMismatch,
 }
 
 impl ValidityCondition for ChainValidityCondition {
@@ -69,6 +70,15 @@ impl ValidityCondition for ChainValidityCondition {
         }
         Ok(rhs)
     }
+
+    fn check<B: BlockHeaderTrait>(&self, block_header: &B) -> Result<(), Self::Error> {
+        if self.block_hash != Into::<[u8; 32]>::into(block_header.hash())
+            || self.prev_hash != Into::<[u8; 32]>::into(block_header.prev_hash())
+        {
+            return Err(ValidityConditionError::BlockHeaderMismatch);
+        }
+        Ok(())
+    }
 }
 
 impl DaVerifier for BitcoinVerifier {
@@ -124,7 +134,11 @@ impl DaVerifier for BitcoinVerifier {
 
             // it must be parsed correctly
             if let Ok(parsed_tx) = parse_transaction(tx, &self.rollup_name) {
-                if let Some(blob_hash) = parsed_tx.get_sig_verified_hash() {
+                // bodies that don't decompress carry no blob, like the service skips them
+                let signed_blob = parsed_tx
+                    .get_sig_verified_hash()
+                    .and_then(|blob_hash| Some((blob_hash, parsed_tx.blob().ok()?)));
+                if let Some((blob_hash, decompressed_blob)) = signed_blob {
                     let blob = blobs_iter.next();
 
                     if blob.is_none() {
@@ -140,9 +154,6 @@ impl DaVerifier for BitcoinVerifier {
                         return Err(ValidationError::IncorrectSenderInBlob);
                     }
 
-                    // decompress the blob
-                    let decompressed_blob = decompress_blob(&parsed_tx.body);
-
                     // read the supplied blob from txs
                     let mut blob_content = blobs[index_completeness].blob.clone();
                     blob_content.advance(blob_content.total_len());
@@ -273,7 +284,8 @@ mod tests {
     use bitcoin::hash_types::{TxMerkleNode, WitnessMerkleNode};
     use bitcoin::hashes::Hash;
     use bitcoin::{BlockHash, CompactTarget, ScriptBuf, Witness};
-    use sov_rollup_interface::da::DaVerifier;
+    use sov_rollup_interface::da::{BlockHeaderTrait, DaVerifier};
+    use sov_rollup_interface::zk::ValidityCondition;
 
     use super::BitcoinVerifier;
     use crate::helpers::parsers::parse_transaction;
@@ -285,7 +297,9 @@ mod tests {
     use crate::spec::proof::InclusionMultiProof;
     use crate::spec::transaction::TransactionWrapper;
     use crate::spec::RollupParams;
-    use crate::verifier::{ChainValidityCondition, ValidationError, WITNESS_COMMITMENT_PREFIX};
+    use crate::verifier::{
+        ChainValidityCondition, ValidationError, ValidityConditionError, WITNESS_COMMITMENT_PREFIX,
+    };
 
     #[test]
     fn correct() {
@@ -1009,4 +1023,24 @@ mod tests {
             Err(ValidationError::ValidBlobNotFoundInBlobs)
         );
     }
+
+    #[test]
+    fn validity_condition_check() {
+        let (block_header, _, _, _) = get_mock_data();
+
+        let condition = ChainValidityCondition {
+            prev_hash: block_header.prev_hash().into(),
+            block_hash: block_header.hash().into(),
+        };
+        assert!(condition.check(&block_header).is_ok());
+
+        let condition = ChainValidityCondition {
+            prev_hash: block_header.prev_hash().into(),
+            block_hash: [1; 32],
+        };
+        assert!(matches!(
+            condition.check(&block_header),
+            Err(ValidityConditionError::BlockHeaderMismatch)
+        ));
+    }
 }
@@ -29,6 +29,7 @@ impl<C: Context, Da: DaSpec> TxHooks for Runtime<C, Da> {
             height,
             sequencer,
             current_spec: _current_spec,
+            da_slot_height: _da_slot_height,
         } = arg;
         let AccountsTxHook { sender, sequencer } =
             self.accounts
@@ -13,15 +13,13 @@ use citrea_primitives::{get_da_block_at_height, L1BlockCache, SyncError};
 use jsonrpsee::core::client::Error as JsonrpseeError;
 use jsonrpsee::server::{BatchRequestConfig, ServerBuilder};
 use jsonrpsee::RpcModule;
-use rs_merkle::algorithms::Sha256;
-use rs_merkle::MerkleTree;
 use sequencer_client::{GetSoftConfirmationResponse, SequencerClient};
 use sov_db::ledger_db::{NodeLedgerOps, SlotCommit};
 use sov_db::schema::types::{
     BatchNumber, SlotNumber, StoredSoftConfirmation, StoredStateTransition,
 };
 use sov_modules_api::Context;
-use sov_modules_stf_blueprint::StfBlueprintTrait;
+use sov_modules_stf_blueprint::{compute_commitment_merkle_root, StfBlueprintTrait};
 use sov_rollup_interface::da::{
     BlobReaderTrait, BlockHeaderTrait, DaData, DaSpec, SequencerCommitment,
 };
@@ -116,7 +114,7 @@ where
             InitVariant::Genesis(params) => {
                 info!("No history detected. Initializing chain...");
                 let storage = storage_manager.create_storage_on_l2_height(0)?;
-                let (genesis_root, initialized_storage) = stf.init_chain(storage, params);
+                let (genesis_root, initialized_storage) = stf.init_chain(storage, params)?;
                 storage_manager.save_change_set_l2(0, initialized_storage)?;
                 storage_manager.finalize_l2(0)?;
                 ledger_db.set_l2_genesis_state_root(&genesis_root)?;
@@ -376,20 +374,18 @@ where
             ));
         }
 
-        let soft_confirmations_tree = MerkleTree::<Sha256>::from_leaves(
-            stored_soft_confirmations
+        let soft_confirmations_root = compute_commitment_merkle_root(
+            &stored_soft_confirmations
                 .iter()
                 .map(|x| x.hash)
-                .collect::<Vec<_>>()
-                .as_slice(),
+                .collect::<Vec<_>>(),
         );
 
-        if soft_confirmations_tree.root() != Some(sequencer_commitment.merkle_root) {
+        if soft_confirmations_root != Some(sequencer_commitment.merkle_root) {
             return Err(anyhow!(
                 "Merkle root mismatch - expected 0x{} but got 0x{}. Skipping commitment.",
                 hex::encode(
-                    soft_confirmations_tree
-                        .root()
+                    soft_confirmations_root
                         .ok_or(anyhow!("Could not calculate soft confirmation tree root"))?
                 ),
                 hex::encode(sequencer_commitment.merkle_root)
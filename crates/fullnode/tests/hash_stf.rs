@@ -8,7 +8,7 @@ use sov_modules_stf_blueprint::StfBlueprintTrait;
 use sov_prover_storage_manager::{new_orphan_storage, SnapshotManager};
 use sov_rollup_interface::da::{BlobReaderTrait, BlockHeaderTrait, DaSpec};
 use sov_rollup_interface::spec::SpecId;
-use sov_rollup_interface::stf::{SlotResult, StateTransitionFunction};
+use sov_rollup_interface::stf::{InitChainError, SlotResult, StateTransitionFunction};
 use sov_rollup_interface::zk::{CumulativeStateDiff, ValidityCondition, Zkvm};
 use sov_state::storage::{NativeStorage, StorageKey, StorageValue};
 use sov_state::{
@@ -88,11 +88,15 @@ impl<C: Context, Da: DaSpec, Vm: Zkvm, Cond: ValidityCondition> StfBlueprintTrai
     fn apply_soft_confirmation_txs(
         &self,
         _current_spec: SpecId,
+        _da_slot_height: u64,
         _txs: Vec<Vec<u8>>,
         _batch_workspace: sov_modules_api::WorkingSet<C>,
     ) -> (
         sov_modules_api::WorkingSet<C>,
-        Vec<sov_modules_stf_blueprint::TransactionReceipt<sov_modules_stf_blueprint::TxEffect>>,
+        Result<
+            Vec<sov_modules_stf_blueprint::TransactionReceipt<sov_modules_stf_blueprint::TxEffect>>,
+            sov_modules_stf_blueprint::SlashingReason,
+        >,
     ) {
         unimplemented!()
     }
@@ -102,12 +106,17 @@ impl<C: Context, Da: DaSpec, Vm: Zkvm, Cond: ValidityCondition> StfBlueprintTrai
         _current_spec: SpecId,
         _sequencer_public_key: &[u8],
         _soft_confirmation: &mut sov_modules_api::SignedSoftConfirmationBatch,
-        _tx_receipts: Vec<
-            sov_modules_stf_blueprint::TransactionReceipt<sov_modules_stf_blueprint::TxEffect>,
+        _tx_receipts: Result<
+            Vec<sov_modules_stf_blueprint::TransactionReceipt<sov_modules_stf_blueprint::TxEffect>>,
+            sov_modules_stf_blueprint::SlashingReason,
         >,
         _batch_workspace: sov_modules_api::WorkingSet<C>,
+        _verify_signatures: bool,
     ) -> (
-        sov_modules_stf_blueprint::BatchReceipt<(), sov_modules_stf_blueprint::TxEffect>,
+        sov_modules_stf_blueprint::BatchReceipt<
+            sov_modules_stf_blueprint::SequencerOutcome<C::Address>,
+            sov_modules_stf_blueprint::TxEffect,
+        >,
         sov_modules_api::StateCheckpoint<C>,
     ) {
         unimplemented!()
@@ -117,7 +126,7 @@ impl<C: Context, Da: DaSpec, Vm: Zkvm, Cond: ValidityCondition> StfBlueprintTrai
         &self,
         _current_spec: SpecId,
         _batch_receipt: sov_modules_stf_blueprint::BatchReceipt<
-            (),
+            sov_modules_stf_blueprint::SequencerOutcome<C::Address>,
             sov_modules_stf_blueprint::TxEffect,
         >,
         _checkpoint: sov_modules_api::StateCheckpoint<C>,
@@ -132,6 +141,28 @@ impl<C: Context, Da: DaSpec, Vm: Zkvm, Cond: ValidityCondition> StfBlueprintTrai
     > {
         unimplemented!()
     }
+
+    fn apply_single_soft_confirmation(
+        &self,
+        _current_spec: SpecId,
+        _sequencer_public_key: &[u8],
+        _pre_state_root: &Self::StateRoot,
+        _pre_state: Self::PreState,
+        _witness: <<C as sov_modules_api::Spec>::Storage as Storage>::Witness,
+        _slot_header: &<Da as DaSpec>::BlockHeader,
+        _soft_confirmation: &mut sov_modules_api::SignedSoftConfirmationBatch,
+    ) -> (
+        Result<(), sov_modules_api::hooks::ApplySoftConfirmationError>,
+        SlotResult<
+            Self::StateRoot,
+            Self::ChangeSet,
+            Self::BatchReceiptContents,
+            Self::TxReceiptContents,
+            Self::Witness,
+        >,
+    ) {
+        unimplemented!()
+    }
 }
 
 impl<Vm: Zkvm, Cond: ValidityCondition, Da: DaSpec> StateTransitionFunction<Vm, Da>
@@ -150,11 +181,15 @@ impl<Vm: Zkvm, Cond: ValidityCondition, Da: DaSpec> StateTransitionFunction<Vm,
         &self,
         genesis_state: Self::PreState,
         params: Self::GenesisParams,
-    ) -> (Self::StateRoot, Self::ChangeSet) {
+    ) -> Result<(Self::StateRoot, Self::ChangeSet), InitChainError> {
         let mut hasher = sha2::Sha256::new();
         hasher.update(params);
 
-        HashStf::<Cond>::save_from_hasher(hasher, genesis_state, &mut ArrayWitness::default())
+        Ok(HashStf::<Cond>::save_from_hasher(
+            hasher,
+            genesis_state,
+            &mut ArrayWitness::default(),
+        ))
     }
 
     fn apply_slot<'a, I>(
@@ -298,6 +333,32 @@ fn compare_output() {
     assert_eq!(recorded_state_root, state_root);
 }
 
+#[test]
+fn init_chain_checks_expected_genesis_hash() {
+    let genesis_params = vec![1u8; 32];
+    let init_chain = |expected_genesis_hash: Option<[u8; 32]>| {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let storage = new_orphan_storage(tmpdir.path()).unwrap();
+        let stf = HashStf::<MockValidityCond>::new();
+
+        <HashStf<MockValidityCond> as StateTransitionFunction<
+            MockZkvm<MockValidityCond>,
+            MockDaSpec,
+        >>::init_chain_checked(&stf, storage, genesis_params.clone(), expected_genesis_hash)
+        .map(|(genesis_hash, _)| genesis_hash)
+    };
+
+    let genesis_hash = init_chain(None).unwrap();
+    assert_eq!(init_chain(Some(genesis_hash)).unwrap(), genesis_hash);
+
+    let err = init_chain(Some([0; 32])).unwrap_err();
+    assert!(matches!(
+        err,
+        InitChainError::GenesisHashMismatch { expected, got }
+            if expected == vec![0; 32] && got == genesis_hash.to_vec()
+    ));
+}
+
 // Returns final data hash and root hash
 pub fn get_result_from_blocks(
     genesis_params: &[u8],
@@ -313,7 +374,8 @@ pub fn get_result_from_blocks(
         <HashStf<MockValidityCond> as StateTransitionFunction<
             MockZkvm<MockValidityCond>,
             MockDaSpec,
-        >>::init_chain(&stf, storage, genesis_params.to_vec());
+        >>::init_chain(&stf, storage, genesis_params.to_vec())
+        .unwrap();
 
     let mut state_root = genesis_state_root;
 
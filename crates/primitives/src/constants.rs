@@ -8,3 +8,7 @@ pub const TEST_PRIVATE_KEY: &str =
 
 pub const MAX_STATEDIFF_SIZE_COMMITMENT_THRESHOLD: u64 = 300 * 1024;
 pub const MAX_STATEDIFF_SIZE_PROOF_THRESHOLD: u64 = 400 * 1024;
+
+/// Default upper bound on the total size of the sequencer's DA blobs sequencer commitments
+/// are extracted from, so that an oversized DA payload can't exhaust the resources of the prover.
+pub const MAX_DA_BYTES_PER_COMMITMENT: usize = 64 * 1024 * 1024;
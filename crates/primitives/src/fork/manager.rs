@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::fmt;
 
 use sov_rollup_interface::spec::SpecId;
 #[cfg(feature = "native")]
@@ -17,31 +18,164 @@ pub trait Fork {
 
 pub type SpecActivationBlockHeight = u64;
 
+/// Errors returned when validating a fork table in [`ForkManager::try_new`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForkConfigError {
+    /// Two specs share the same activation height.
+    DuplicateActivationHeight(SpecActivationBlockHeight),
+    /// The same spec appears more than once in the fork table.
+    DuplicateSpecId(SpecId),
+    /// The fork table has no specs, so no spec is active at any height.
+    EmptyForkTable,
+}
+
+impl fmt::Display for ForkConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateActivationHeight(height) => {
+                write!(f, "Multiple specs activate at height {}", height)
+            }
+            Self::DuplicateSpecId(spec) => {
+                write!(f, "Spec {:?} is listed more than once", spec)
+            }
+            Self::EmptyForkTable => write!(f, "The fork table has no specs"),
+        }
+    }
+}
+
+impl std::error::Error for ForkConfigError {}
+
 pub struct ForkManager {
     active_spec: SpecId,
     specs: VecDeque<(SpecId, SpecActivationBlockHeight)>,
-    migration_handlers: Vec<Box<dyn ForkMigration + Sync + Send>>,
+    /// The whole fork table including activated specs, sorted by activation height.
+    forks: Vec<(SpecId, SpecActivationBlockHeight)>,
+    /// Specs which were active before each fork activated by this manager,
+    /// together with the height of that activation.
+    activated: Vec<(SpecId, SpecActivationBlockHeight)>,
+    /// The L2 height the manager was created at, it can't be rolled back below it.
+    start_height: u64,
+    /// Handlers together with the spec they are limited to, if any.
+    migration_handlers: Vec<(Option<SpecId>, Box<dyn ForkMigration + Sync + Send>)>,
 }
 
 impl ForkManager {
     pub fn new(
         current_l2_height: u64,
         active_spec: SpecId,
+        specs: Vec<(SpecId, SpecActivationBlockHeight)>,
+    ) -> Self {
+        Self::with_active_spec(current_l2_height, specs, |_| active_spec)
+    }
+
+    /// Same as [`ForkManager::new`], but derives the active spec at `current_l2_height`
+    /// from `specs` the way [`fork_from_block_number`] does.
+    /// Fails if `specs` is empty, as no spec is active then.
+    pub fn for_height(
+        current_l2_height: u64,
+        specs: Vec<(SpecId, SpecActivationBlockHeight)>,
+    ) -> Result<Self, ForkConfigError> {
+        if specs.is_empty() {
+            return Err(ForkConfigError::EmptyForkTable);
+        }
+        Ok(Self::with_active_spec(current_l2_height, specs, |forks| {
+            fork_from_block_number(forks, current_l2_height)
+        }))
+    }
+
+    // Sorts `specs` once and picks the active spec from the sorted fork table.
+    fn with_active_spec(
+        current_l2_height: u64,
         mut specs: Vec<(SpecId, SpecActivationBlockHeight)>,
+        active_spec: impl FnOnce(&[(SpecId, SpecActivationBlockHeight)]) -> SpecId,
     ) -> Self {
-        // Filter out specs which have already been activated.
-        specs.retain(|(spec, block)| *spec != active_spec && *block > current_l2_height);
         // Make sure the list of specs is sorted by the block number at which they activate.
         specs.sort_by_key(|(_, block_number)| *block_number);
+        let active_spec = active_spec(&specs);
+        let forks = specs.clone();
+        // Filter out specs which have already been activated.
+        specs.retain(|(spec, block)| *spec != active_spec && *block > current_l2_height);
         Self {
             specs: specs.into(),
+            forks,
             active_spec,
+            activated: vec![],
+            start_height: current_l2_height,
             migration_handlers: vec![],
         }
     }
 
+    /// Same as [`ForkManager::new`], but rejects fork tables with duplicate
+    /// activation heights or duplicate spec ids instead of silently accepting them.
+    pub fn try_new(
+        current_l2_height: u64,
+        active_spec: SpecId,
+        mut specs: Vec<(SpecId, SpecActivationBlockHeight)>,
+    ) -> Result<Self, ForkConfigError> {
+        for (i, (spec, _)) in specs.iter().enumerate() {
+            if specs[..i].iter().any(|(other, _)| other == spec) {
+                return Err(ForkConfigError::DuplicateSpecId(*spec));
+            }
+        }
+        specs.sort_by_key(|(_, block_number)| *block_number);
+        if let Some(pair) = specs.windows(2).find(|pair| pair[0].1 == pair[1].1) {
+            return Err(ForkConfigError::DuplicateActivationHeight(pair[1].1));
+        }
+        Ok(Self::new(current_l2_height, active_spec, specs))
+    }
+
+    /// Forks which are scheduled but not yet activated, ordered by activation height.
+    pub fn scheduled_forks(&self) -> &VecDeque<(SpecId, SpecActivationBlockHeight)> {
+        &self.specs
+    }
+
+    /// Undoes the fork activations above `height`, so that the active spec is the one
+    /// of `height` again and the undone forks are scheduled for activation once more.
+    /// Used when the blocks above `height` are reverted.
+    pub fn rollback_to(&mut self, height: u64) -> anyhow::Result<()> {
+        if height < self.start_height {
+            anyhow::bail!(
+                "Cannot roll back to height {} below the fork manager start height {}",
+                height,
+                self.start_height
+            );
+        }
+        while let Some(&(previous_spec, activation_block_height)) = self.activated.last() {
+            if activation_block_height <= height {
+                break;
+            }
+            #[cfg(feature = "native")]
+            info!(
+                "Rolling back fork {:?} activated at height: {}",
+                self.active_spec, activation_block_height
+            );
+
+            self.specs
+                .push_front((self.active_spec, activation_block_height));
+            self.active_spec = previous_spec;
+            self.activated.pop();
+        }
+        Ok(())
+    }
+
+    /// Returns whether `spec` is the spec of the block at `height` according to the fork table,
+    /// the way [`fork_from_block_number`] determines it. Doesn't change the manager's state.
+    pub fn is_active_at(&self, spec: SpecId, height: u64) -> bool {
+        !self.forks.is_empty() && fork_from_block_number(&self.forks, height) == spec
+    }
+
     pub fn register_handler(&mut self, handler: Box<dyn ForkMigration + Sync + Send>) {
-        self.migration_handlers.push(handler);
+        self.migration_handlers.push((None, handler));
+    }
+
+    /// Same as [`ForkManager::register_handler`], but the handler is only invoked
+    /// when `spec` activates instead of on every activation.
+    pub fn register_handler_for(
+        &mut self,
+        spec: SpecId,
+        handler: Box<dyn ForkMigration + Sync + Send>,
+    ) {
+        self.migration_handlers.push((Some(spec), handler));
     }
 }
 
@@ -56,9 +190,12 @@ impl Fork for ForkManager {
                 #[cfg(feature = "native")]
                 info!("Activating fork {:?} at height: {}", *new_spec, height);
 
+                self.activated.push((self.active_spec, height));
                 self.active_spec = *new_spec;
-                for handler in self.migration_handlers.iter() {
-                    handler.spec_activated(self.active_spec)?;
+                for (scope, handler) in self.migration_handlers.iter() {
+                    if scope.map_or(true, |spec| spec == self.active_spec) {
+                        handler.spec_activated(self.active_spec)?;
+                    }
                 }
                 self.specs.pop_front();
             }
@@ -1,7 +1,7 @@
 use anyhow::anyhow;
 use sov_rollup_interface::spec::SpecId;
 
-use super::{Fork, ForkManager};
+use super::{Fork, ForkConfigError, ForkManager};
 use crate::fork::{fork_from_block_number, ForkMigration};
 
 #[test]
@@ -36,6 +36,72 @@ fn test_fork_manager() {
     assert_eq!(fork_manager.active_fork(), SpecId::Fork2);
 }
 
+#[test]
+fn test_fork_manager_scheduled_forks() {
+    // The testing `SpecId` only has three variants, one of which has to be active.
+    let forks = vec![
+        (SpecId::Fork2, 500),
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+    ];
+    let mut fork_manager = ForkManager::new(0, SpecId::Genesis, forks);
+    assert_eq!(
+        fork_manager.scheduled_forks().iter().collect::<Vec<_>>(),
+        vec![&(SpecId::Fork1, 100), &(SpecId::Fork2, 500)]
+    );
+
+    fork_manager.register_block(100).unwrap();
+    assert_eq!(
+        fork_manager.scheduled_forks().iter().collect::<Vec<_>>(),
+        vec![&(SpecId::Fork2, 500)]
+    );
+
+    fork_manager.register_block(500).unwrap();
+    assert!(fork_manager.scheduled_forks().is_empty());
+}
+
+#[test]
+fn test_fork_manager_try_new() {
+    let forks = vec![
+        (SpecId::Fork2, 500),
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+    ];
+    let mut fork_manager = ForkManager::try_new(0, SpecId::Genesis, forks).unwrap();
+    fork_manager.register_block(100).unwrap();
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+    fork_manager.register_block(500).unwrap();
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork2);
+}
+
+#[test]
+fn test_fork_manager_try_new_duplicate_heights() {
+    let forks = vec![
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+        (SpecId::Fork2, 100),
+    ];
+    let result = ForkManager::try_new(0, SpecId::Genesis, forks);
+    assert_eq!(
+        result.err(),
+        Some(ForkConfigError::DuplicateActivationHeight(100))
+    );
+}
+
+#[test]
+fn test_fork_manager_try_new_duplicate_spec_ids() {
+    let forks = vec![
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+        (SpecId::Fork1, 500),
+    ];
+    let result = ForkManager::try_new(0, SpecId::Genesis, forks);
+    assert_eq!(
+        result.err(),
+        Some(ForkConfigError::DuplicateSpecId(SpecId::Fork1))
+    );
+}
+
 #[test]
 fn test_fork_manager_callbacks() {
     let forks = vec![
@@ -62,3 +128,163 @@ fn test_fork_manager_callbacks() {
         assert_eq!(msg.to_string(), "Called");
     }
 }
+
+#[test]
+fn test_fork_manager_scoped_callbacks() {
+    use std::sync::{Arc, Mutex};
+
+    let forks = vec![
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+        (SpecId::Fork2, 500),
+    ];
+
+    struct Handler {
+        calls: Arc<Mutex<Vec<SpecId>>>,
+    }
+    impl ForkMigration for Handler {
+        fn spec_activated(&self, spec_id: SpecId) -> anyhow::Result<()> {
+            self.calls.lock().unwrap().push(spec_id);
+            Ok(())
+        }
+    }
+    let fork1_calls = Arc::new(Mutex::new(vec![]));
+    let fork2_calls = Arc::new(Mutex::new(vec![]));
+    let all_calls = Arc::new(Mutex::new(vec![]));
+
+    let mut fork_manager = ForkManager::new(0, SpecId::Genesis, forks);
+    fork_manager.register_handler_for(
+        SpecId::Fork1,
+        Box::new(Handler {
+            calls: fork1_calls.clone(),
+        }),
+    );
+    fork_manager.register_handler_for(
+        SpecId::Fork2,
+        Box::new(Handler {
+            calls: fork2_calls.clone(),
+        }),
+    );
+    fork_manager.register_handler(Box::new(Handler {
+        calls: all_calls.clone(),
+    }));
+
+    for height in 0..=600 {
+        fork_manager.register_block(height).unwrap();
+    }
+
+    assert_eq!(*fork1_calls.lock().unwrap(), vec![SpecId::Fork1]);
+    assert_eq!(*fork2_calls.lock().unwrap(), vec![SpecId::Fork2]);
+    assert_eq!(
+        *all_calls.lock().unwrap(),
+        vec![SpecId::Fork1, SpecId::Fork2]
+    );
+}
+
+#[test]
+fn test_fork_manager_for_height() {
+    let forks = vec![
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+        (SpecId::Fork2, 103),
+    ];
+
+    for height in [0, 5, 99, 100, 101, 103, 200] {
+        let fork_manager = ForkManager::for_height(height, forks.clone()).unwrap();
+        assert_eq!(
+            fork_manager.active_fork(),
+            fork_from_block_number(&forks, height)
+        );
+    }
+
+    // Step through blocks the same way the STF applies a commitment:
+    // apply with the current spec, then register the block.
+    let mut l2_height = 98;
+    let mut fork_manager = ForkManager::for_height(l2_height, forks).unwrap();
+    let mut applied_specs = vec![];
+    while l2_height < 106 {
+        applied_specs.push(fork_manager.active_fork());
+        fork_manager.register_block(l2_height).unwrap();
+        l2_height += 1;
+    }
+
+    assert_eq!(
+        applied_specs,
+        vec![
+            SpecId::Genesis,
+            SpecId::Genesis,
+            SpecId::Genesis,
+            SpecId::Fork1,
+            SpecId::Fork1,
+            SpecId::Fork1,
+            SpecId::Fork2,
+            SpecId::Fork2,
+        ]
+    );
+}
+
+#[test]
+fn test_fork_manager_for_height_empty_fork_table() {
+    let result = ForkManager::for_height(0, vec![]);
+    assert_eq!(result.err(), Some(ForkConfigError::EmptyForkTable));
+}
+
+#[test]
+fn test_fork_manager_rollback_to() {
+    let forks = vec![
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+        (SpecId::Fork2, 500),
+    ];
+    let mut fork_manager = ForkManager::new(10, SpecId::Genesis, forks);
+    for height in [100, 350, 500] {
+        fork_manager.register_block(height).unwrap();
+    }
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork2);
+
+    fork_manager.rollback_to(500).unwrap();
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork2);
+
+    fork_manager.rollback_to(350).unwrap();
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+    assert_eq!(
+        fork_manager.scheduled_forks().iter().collect::<Vec<_>>(),
+        vec![&(SpecId::Fork2, 500)]
+    );
+
+    fork_manager.rollback_to(99).unwrap();
+    assert_eq!(fork_manager.active_fork(), SpecId::Genesis);
+    assert_eq!(
+        fork_manager.scheduled_forks().iter().collect::<Vec<_>>(),
+        vec![&(SpecId::Fork1, 100), &(SpecId::Fork2, 500)]
+    );
+
+    // Forks activate again when the blocks are re-applied.
+    fork_manager.register_block(100).unwrap();
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+
+    assert!(fork_manager.rollback_to(9).is_err());
+}
+
+#[test]
+fn test_fork_manager_is_active_at() {
+    let forks = vec![
+        (SpecId::Genesis, 0),
+        (SpecId::Fork1, 100),
+        (SpecId::Fork2, 500),
+    ];
+    let fork_manager = ForkManager::new(200, SpecId::Fork1, forks);
+
+    assert!(fork_manager.is_active_at(SpecId::Genesis, 99));
+    assert!(!fork_manager.is_active_at(SpecId::Fork1, 99));
+    assert!(fork_manager.is_active_at(SpecId::Fork1, 100));
+    assert!(!fork_manager.is_active_at(SpecId::Genesis, 100));
+    assert!(fork_manager.is_active_at(SpecId::Fork1, 499));
+    assert!(fork_manager.is_active_at(SpecId::Fork2, 500));
+    assert!(fork_manager.is_active_at(SpecId::Fork2, 10_000));
+    // the query doesn't activate anything
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+
+    let fork_manager = ForkManager::new(0, SpecId::Genesis, vec![]);
+    assert!(!fork_manager.is_active_at(SpecId::Genesis, 0));
+}
@@ -8,3 +8,20 @@ pub const FORKS: [(SpecId, u64); 1] = [
     // (SpecId::Fork1, 100),
     // (SpecId::Fork2, 200)
 ];
+
+/// Specs in which the timestamps of soft confirmations within a sequencer commitment
+/// must not decrease. Add a spec here to enforce the check from its activation height on.
+pub const TIMESTAMP_MONOTONICITY_SPECS: &[SpecId] = &[];
+
+/// Specs in which the soft confirmations of a sequencer commitment may reference an earlier
+/// DA block of the commitment than the soft confirmation before them.
+/// Soft confirmations of other specs must not go back to an earlier DA height.
+pub const INTERLEAVED_DA_SLOT_SPECS: &[SpecId] = &[];
+
+/// Maximum number of transactions a soft confirmation may contain in a spec.
+/// Specs not listed here don't limit the number of transactions.
+pub const SOFT_CONFIRMATION_TX_LIMITS: &[(SpecId, usize)] = &[];
+
+/// Specs in which forced transactions posted to the DA layer are processed.
+/// Forced transactions are not implemented yet, so no spec enables them.
+pub const FORCED_TRANSACTION_SPECS: &[SpecId] = &[];
@@ -124,7 +124,7 @@ where
             InitVariant::Genesis(params) => {
                 info!("No history detected. Initializing chain...");
                 let storage = storage_manager.create_storage_on_l2_height(0)?;
-                let (genesis_root, initialized_storage) = stf.init_chain(storage, params);
+                let (genesis_root, initialized_storage) = stf.init_chain(storage, params)?;
                 storage_manager.save_change_set_l2(0, initialized_storage)?;
                 storage_manager.finalize_l2(0)?;
                 ledger_db.set_l2_genesis_state_root(&genesis_root)?;
@@ -2,10 +2,9 @@ use std::cmp;
 use std::ops::RangeInclusive;
 
 use anyhow::anyhow;
-use rs_merkle::algorithms::Sha256;
-use rs_merkle::MerkleTree;
 use sov_db::ledger_db::SequencerLedgerOps;
 use sov_db::schema::types::BatchNumber;
+use sov_modules_stf_blueprint::compute_commitment_merkle_root;
 use sov_rollup_interface::da::SequencerCommitment;
 use tracing::{debug, instrument};
 
@@ -82,8 +81,7 @@ pub fn get_commitment(
     );
 
     // build merkle tree over soft confirmations
-    let merkle_root = MerkleTree::<Sha256>::from_leaves(soft_confirmation_hashes.as_slice())
-        .root()
+    let merkle_root = compute_commitment_merkle_root(&soft_confirmation_hashes)
         .ok_or(anyhow!("Couldn't compute merkle root"))?;
     Ok(SequencerCommitment {
         merkle_root,
@@ -91,3 +89,31 @@ pub fn get_commitment(
         l2_end_block_number: commitment_info.l2_height_range.end().0,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use rs_merkle::algorithms::Sha256;
+    use rs_merkle::MerkleTree;
+
+    use super::*;
+
+    #[test]
+    fn test_commitment_merkle_root_matches_verifier() {
+        let soft_confirmation_hashes = (0..5u8).map(|i| [i; 32]).collect::<Vec<_>>();
+        let commitment_info = CommitmentInfo {
+            l2_height_range: BatchNumber(1)..=BatchNumber(5),
+        };
+
+        let commitment = get_commitment(commitment_info, soft_confirmation_hashes.clone()).unwrap();
+
+        // The verifier side recomputes the root over the same hashes
+        assert_eq!(
+            Some(commitment.merkle_root),
+            MerkleTree::<Sha256>::from_leaves(&soft_confirmation_hashes).root()
+        );
+        assert_eq!(
+            Some(commitment.merkle_root),
+            compute_commitment_merkle_root(&soft_confirmation_hashes)
+        );
+    }
+}
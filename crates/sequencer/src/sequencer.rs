@@ -10,11 +10,11 @@ use anyhow::anyhow;
 use borsh::BorshDeserialize;
 use citrea_evm::{CallMessage, Evm, RlpEvmTransaction, MIN_TRANSACTION_GAS};
 use citrea_primitives::fork::{Fork, ForkManager};
+use citrea_primitives::forks::SOFT_CONFIRMATION_TX_LIMITS;
 use citrea_primitives::types::SoftConfirmationHash;
 use citrea_primitives::utils::merge_state_diffs;
 use citrea_primitives::MAX_STATEDIFF_SIZE_COMMITMENT_THRESHOLD;
 use citrea_stf::runtime::Runtime;
-use digest::Digest;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::StreamExt;
 use hyper::Method;
@@ -37,7 +37,9 @@ use sov_modules_api::{
     BlobReaderTrait, Context, EncodeCall, PrivateKey, SignedSoftConfirmationBatch, SlotData,
     StateDiff, UnsignedSoftConfirmationBatch, WorkingSet,
 };
-use sov_modules_stf_blueprint::StfBlueprintTrait;
+use sov_modules_stf_blueprint::{
+    sign_soft_confirmation, verify_soft_confirmation_tx_limit, StfBlueprintTrait,
+};
 use sov_rollup_interface::da::{BlockHeaderTrait, DaData, DaSpec, SequencerCommitment};
 use sov_rollup_interface::services::da::{DaService, SenderWithNotifier};
 use sov_rollup_interface::stf::{SoftConfirmationReceipt, StateTransitionFunction};
@@ -140,7 +142,7 @@ where
             InitVariant::Genesis(params) => {
                 info!("No history detected. Initializing chain...",);
                 let storage = storage_manager.create_storage_on_l2_height(0)?;
-                let (genesis_root, initialized_storage) = stf.init_chain(storage, params);
+                let (genesis_root, initialized_storage) = stf.init_chain(storage, params)?;
                 storage_manager.save_change_set_l2(0, initialized_storage)?;
                 storage_manager.finalize_l2(0)?;
                 ledger_db.set_l2_genesis_state_root(&genesis_root)?;
@@ -309,6 +311,7 @@ where
 
                             let (batch_workspace, _) = self.stf.apply_soft_confirmation_txs(
                                 self.fork_manager.active_fork(),
+                                da_block_header.height(),
                                 txs.clone(),
                                 working_set_to_discard,
                             );
@@ -413,6 +416,27 @@ where
 
         let evm_txs = self.get_best_transactions()?;
 
+        // The EVM transactions of a block are applied as a single sov transaction,
+        // which the transaction limit of the active spec has to leave room for
+        let l2_block_mode = match l2_block_mode {
+            L2BlockMode::NotEmpty => match verify_soft_confirmation_tx_limit(
+                self.fork_manager.active_fork(),
+                1,
+                SOFT_CONFIRMATION_TX_LIMITS,
+            ) {
+                Ok(()) => L2BlockMode::NotEmpty,
+                Err(limit) => {
+                    warn!(
+                        "Transaction limit of {} in spec {:?} leaves no room for transactions, producing an empty block",
+                        limit,
+                        self.fork_manager.active_fork()
+                    );
+                    L2BlockMode::Empty
+                }
+            },
+            L2BlockMode::Empty => L2BlockMode::Empty,
+        };
+
         // Dry running transactions would basically allow for figuring out a list of
         // all transactions that would fit into the current block and the list of transactions
         // which do not have enough balance to pay for the L1 fee.
@@ -456,11 +480,21 @@ where
                     let signed_blob = self.make_blob(raw_message, &mut batch_workspace)?;
                     txs.push(signed_blob);
 
-                    (batch_workspace, tx_receipts) = self.stf.apply_soft_confirmation_txs(
-                        self.fork_manager.active_fork(),
-                        txs.clone(),
-                        batch_workspace,
-                    );
+                    let (applied_batch_workspace, applied_tx_receipts) =
+                        self.stf.apply_soft_confirmation_txs(
+                            self.fork_manager.active_fork(),
+                            da_block.header().height(),
+                            txs.clone(),
+                            batch_workspace,
+                        );
+                    batch_workspace = applied_batch_workspace;
+                    tx_receipts = applied_tx_receipts.map_err(|reason| {
+                        anyhow!(
+                            "Sequencer transactions at L2 height {} are invalid: {}",
+                            l2_height,
+                            reason
+                        )
+                    })?;
                 }
 
                 // create the unsigned batch with the txs then sign th sc
@@ -481,18 +515,28 @@ where
                     self.fork_manager.active_fork(),
                     self.sequencer_pub_key.as_ref(),
                     &mut signed_soft_confirmation,
-                    tx_receipts,
+                    Ok(tx_receipts),
                     batch_workspace,
+                    true,
                 );
 
                 // Finalize soft confirmation
-                let slot_result = self.stf.finalize_soft_confirmation(
-                    self.fork_manager.active_fork(),
-                    batch_receipt,
-                    checkpoint,
-                    prestate,
-                    &mut signed_soft_confirmation,
-                );
+                let slot_result = self
+                    .stf
+                    .try_finalize_soft_confirmation(
+                        self.fork_manager.active_fork(),
+                        batch_receipt,
+                        checkpoint,
+                        prestate,
+                        &mut signed_soft_confirmation,
+                    )
+                    .map_err(|e| {
+                        anyhow!(
+                            "Failed to finalize soft confirmation at L2 height {}: {}",
+                            l2_height,
+                            e
+                        )
+                    })?;
 
                 if slot_result.state_root.as_ref() == self.state_root.as_ref() {
                     debug!("Max L2 blocks per L1 is reached for the current L1 block. State root is the same as before, skipping");
@@ -1089,24 +1133,14 @@ where
         soft_confirmation: UnsignedSoftConfirmationBatch,
         prev_soft_confirmation_hash: [u8; 32],
     ) -> anyhow::Result<SignedSoftConfirmationBatch> {
-        let raw = borsh::to_vec(&soft_confirmation).map_err(|e| anyhow!(e))?;
+        let pub_key =
+            borsh::to_vec(&self.sov_tx_signer_priv_key.pub_key()).map_err(|e| anyhow!(e))?;
 
-        let hash = <C as sov_modules_api::Spec>::Hasher::digest(raw.as_slice()).into();
-
-        let signature = self.sov_tx_signer_priv_key.sign(&raw);
-        let pub_key = self.sov_tx_signer_priv_key.pub_key();
-        Ok(SignedSoftConfirmationBatch::new(
-            hash,
+        Ok(sign_soft_confirmation::<C>(
+            soft_confirmation,
             prev_soft_confirmation_hash,
-            soft_confirmation.da_slot_height(),
-            soft_confirmation.da_slot_hash(),
-            soft_confirmation.da_slot_txs_commitment(),
-            soft_confirmation.l1_fee_rate(),
-            soft_confirmation.txs(),
-            soft_confirmation.deposit_data(),
-            borsh::to_vec(&signature).map_err(|e| anyhow!(e))?,
-            borsh::to_vec(&pub_key).map_err(|e| anyhow!(e))?,
-            soft_confirmation.timestamp(),
+            &self.sov_tx_signer_priv_key,
+            &pub_key,
         ))
     }
 
@@ -4,6 +4,7 @@ use anyhow::Error;
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
+use sov_rollup_interface::da::BlockHeaderTrait;
 use sov_rollup_interface::zk::{ValidityCondition, ValidityConditionChecker};
 
 /// A trivial test validity condition structure that only contains a boolean
@@ -29,6 +30,14 @@ impl ValidityCondition for MockValidityCond {
             is_valid: self.is_valid & rhs.is_valid,
         })
     }
+
+    fn check<B: BlockHeaderTrait>(&self, _block_header: &B) -> Result<(), Self::Error> {
+        if self.is_valid {
+            Ok(())
+        } else {
+            Err(anyhow::format_err!("Invalid mock validity condition"))
+        }
+    }
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Debug)]
@@ -6,7 +6,9 @@ use sha2::Digest;
 use sov_rollup_interface::da::{BlobReaderTrait, DaSpec};
 use sov_rollup_interface::soft_confirmation::SignedSoftConfirmationBatch;
 use sov_rollup_interface::spec::SpecId;
-use sov_rollup_interface::stf::{BatchReceipt, SlotResult, StateTransitionFunction};
+use sov_rollup_interface::stf::{
+    BatchReceipt, InitChainError, SlotResult, StateTransitionFunction,
+};
 use sov_rollup_interface::zk::{CumulativeStateDiff, ValidityCondition, Zkvm};
 
 /// An implementation of the [`StateTransitionFunction`]
@@ -53,8 +55,8 @@ impl<Vm: Zkvm, Cond: ValidityCondition, Da: DaSpec> StateTransitionFunction<Vm,
         &self,
         _base_state: Self::PreState,
         _params: Self::GenesisParams,
-    ) -> ([u8; 0], ()) {
-        ([], ())
+    ) -> Result<([u8; 0], ()), InitChainError> {
+        Ok(([], ()))
     }
 
     fn apply_slot<'a, I>(
@@ -88,7 +90,7 @@ impl<Vm: Zkvm, Cond: ValidityCondition, Da: DaSpec> StateTransitionFunction<Vm,
                 151, 20, 133, 110, 226, 51, 179, 144, 42, 89, 29, 13, 95, 41, 37,
             ];
 
-            let _result = if hash == desired_hash {
+            let result = if hash == desired_hash {
                 ApplySlotResult::Success
             } else {
                 ApplySlotResult::Failure
@@ -99,7 +101,7 @@ impl<Vm: Zkvm, Cond: ValidityCondition, Da: DaSpec> StateTransitionFunction<Vm,
                 hash,
                 prev_hash,
                 tx_receipts: vec![],
-                phantom_data: PhantomData,
+                inner: result,
             });
         }
 
@@ -5,7 +5,7 @@ use sov_schema_db::snapshot::{DbSnapshot, QueryManager, ReadOnlyDbSnapshot};
 use sov_schema_db::SchemaBatch;
 
 use crate::rocks_db_config::gen_rocksdb_options;
-use crate::schema::tables::{ModuleAccessoryState, NATIVE_TABLES};
+use crate::schema::tables::{AccessoryKeysByVersion, ModuleAccessoryState, NATIVE_TABLES};
 use crate::schema::types::AccessoryKey;
 
 /// Specifies a particular version of the Accessory state.
@@ -90,11 +90,28 @@ impl<Q: QueryManager> NativeDB<Q> {
     ) -> anyhow::Result<()> {
         let mut batch = SchemaBatch::default();
         for (key, value) in key_value_pairs {
-            batch.put::<ModuleAccessoryState>(&(key, version), &value)?;
+            batch.put::<ModuleAccessoryState>(&(key.clone(), version), &value)?;
+            batch.put::<AccessoryKeysByVersion>(&(version, key), &())?;
         }
         self.db.write_many(batch)?;
         Ok(())
     }
+
+    /// Discards the values written after `version`.
+    /// Only the keys written after `version` are visited, found by their version.
+    pub fn rollback_to_version(&self, version: Version) -> anyhow::Result<()> {
+        let mut batch = SchemaBatch::default();
+        self.db
+            .visit_keys_rev::<AccessoryKeysByVersion>(|(key_version, key)| {
+                if key_version <= version {
+                    return Ok(false);
+                }
+                batch.delete::<ModuleAccessoryState>(&(key.clone(), key_version))?;
+                batch.delete::<AccessoryKeysByVersion>(&(key_version, key))?;
+                Ok(true)
+            })?;
+        self.db.write_many(batch)
+    }
 }
 
 #[cfg(test)]
@@ -142,4 +159,26 @@ mod tests {
         let key = b"spam".to_vec();
         assert_eq!(db.get_value_option(&key, 0).unwrap(), None);
     }
+
+    #[test]
+    fn get_after_rollback() {
+        let db = setup_db();
+
+        let key = b"foo".to_vec();
+        let new_key = b"new".to_vec();
+        db.set_values(vec![(key.clone(), Some(b"bar".to_vec()))], 0)
+            .unwrap();
+        db.set_values(
+            vec![
+                (key.clone(), Some(b"bar2".to_vec())),
+                (new_key.clone(), Some(b"baz".to_vec())),
+            ],
+            1,
+        )
+        .unwrap();
+
+        db.rollback_to_version(0).unwrap();
+        assert_eq!(db.get_value_option(&key, 1).unwrap(), Some(b"bar".to_vec()));
+        assert_eq!(db.get_value_option(&new_key, 1).unwrap(), None);
+    }
 }
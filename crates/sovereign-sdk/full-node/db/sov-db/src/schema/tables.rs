@@ -21,9 +21,11 @@
 //! - `KeyHash -> Key`
 //! - `(Key, Version) -> JmtValue`
 //! - `NodeKey -> Node`
+//! - `(Version, Key) -> ()`
 //!
 //! Module Accessory State Table:
 //! - `(ModuleAddress, Key) -> Value`
+//! - `(Version, Key) -> ()`
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
@@ -47,6 +49,7 @@ pub const STATE_TABLES: &[&str] = &[
     KeyHashToKey::table_name(),
     JmtValues::table_name(),
     JmtNodes::table_name(),
+    StateKeysByVersion::table_name(),
 ];
 
 /// A list of all tables used by the LedgerDB. These tables store rollup "history" - meaning
@@ -81,7 +84,10 @@ pub const LEDGER_TABLES: &[&str] = &[
 /// A list of all tables used by the NativeDB. These tables store
 /// "accessory" state only accessible from a native execution context, to be
 /// used for JSON-RPC and other tooling.
-pub const NATIVE_TABLES: &[&str] = &[ModuleAccessoryState::table_name()];
+pub const NATIVE_TABLES: &[&str] = &[
+    ModuleAccessoryState::table_name(),
+    AccessoryKeysByVersion::table_name(),
+];
 
 /// Macro to define a table that implements [`sov_schema_db::Schema`].
 /// KeyCodec<Schema> and ValueCodec<Schema> must be implemented separately.
@@ -432,6 +438,12 @@ impl ValueCodec<JmtValues> for JmtValue {
     }
 }
 
+define_table_with_seek_key_codec!(
+    /// The state keys written to [`JmtValues`] at each version, ordered by version,
+    /// so the values of the latest versions can be found without scanning all of them
+    (StateKeysByVersion) (Version, StateKey) => ()
+);
+
 define_table_with_default_codec!(
     /// A mapping from key-hashes to their preimages and latest version. Since we store raw
     /// key-value pairs instead of keyHash->value pairs,
@@ -445,6 +457,12 @@ define_table_without_codec!(
     (ModuleAccessoryState) (AccessoryKey, Version) => AccessoryStateValue
 );
 
+define_table_with_seek_key_codec!(
+    /// The accessory keys written to [`ModuleAccessoryState`] at each version, ordered by version,
+    /// so the values of the latest versions can be found without scanning all of them
+    (AccessoryKeysByVersion) (Version, AccessoryKey) => ()
+);
+
 impl KeyEncoder<ModuleAccessoryState> for (AccessoryKey, Version) {
     fn encode_key(&self) -> sov_schema_db::schema::Result<Vec<u8>> {
         let mut out = Vec::with_capacity(self.0.len() + std::mem::size_of::<Version>() + 8);
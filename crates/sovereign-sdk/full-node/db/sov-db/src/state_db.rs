@@ -7,7 +7,7 @@ use sov_schema_db::snapshot::{DbSnapshot, QueryManager, ReadOnlyDbSnapshot};
 use sov_schema_db::SchemaBatch;
 
 use crate::rocks_db_config::gen_rocksdb_options;
-use crate::schema::tables::{JmtNodes, JmtValues, KeyHashToKey, STATE_TABLES};
+use crate::schema::tables::{JmtNodes, JmtValues, KeyHashToKey, StateKeysByVersion, STATE_TABLES};
 use crate::schema::types::StateKey;
 
 /// A typed wrapper around the db for storing rollup state. Internally,
@@ -115,6 +115,36 @@ impl<Q: QueryManager> StateDB<Q> {
         *version
     }
 
+    /// Discard the nodes and values written after `version`,
+    /// so the next batch of writes is done at `version + 1` again.
+    pub fn rollback_to_version(&self, version: Version) -> anyhow::Result<()> {
+        let mut batch = SchemaBatch::new();
+        // Node keys start with their version, so only the nodes written after `version` are visited
+        self.db.visit_keys_rev::<JmtNodes>(|node_key| {
+            if node_key.version() <= version {
+                return Ok(false);
+            }
+            batch.delete::<JmtNodes>(&node_key)?;
+            Ok(true)
+        })?;
+        // Values are keyed by state key first and deletions of keys leave no node to find them by,
+        // so they are found through the keys written at each version
+        self.db
+            .visit_keys_rev::<StateKeysByVersion>(|(key_version, key)| {
+                if key_version <= version {
+                    return Ok(false);
+                }
+                batch.delete::<JmtValues>(&(key.clone(), key_version))?;
+                batch.delete::<StateKeysByVersion>(&(key_version, key))?;
+                Ok(true)
+            })?;
+        self.db.write_many(batch)?;
+
+        let mut next_version = self.next_version.lock().unwrap();
+        *next_version = version + 1;
+        Ok(())
+    }
+
     /// Used to always query for latest possible version!
     pub fn max_out_next_version(&self) {
         let mut version = self.next_version.lock().unwrap();
@@ -173,7 +203,8 @@ impl<Q: QueryManager> TreeWriter for StateDB<Q> {
                     .ok_or(anyhow::format_err!(
                         "Could not find preimage for key hash {key_hash:?}. Has `StateDB::put_preimage` been called for this key?"
                     ))?;
-            batch.put::<JmtValues>(&(key_preimage, *version), value)?;
+            batch.put::<JmtValues>(&(key_preimage.clone(), *version), value)?;
+            batch.put::<StateKeysByVersion>(&(*version, key_preimage), &())?;
         }
         self.db.write_many(batch)?;
         Ok(())
@@ -191,7 +222,7 @@ mod state_db_tests {
     use std::sync::{Arc, RwLock};
 
     use jmt::storage::{NodeBatch, TreeReader, TreeWriter};
-    use jmt::KeyHash;
+    use jmt::{JellyfishMerkleTree, KeyHash};
     use sov_schema_db::snapshot::{DbSnapshot, NoopQueryManager, ReadOnlyLock};
 
     use super::StateDB;
@@ -216,4 +247,49 @@ mod state_db_tests {
         let found = db.get_value_option_by_key(0, &key).unwrap().unwrap();
         assert_eq!(found, value);
     }
+
+    #[test]
+    fn test_rollback_to_version() {
+        let manager = ReadOnlyLock::new(Arc::new(RwLock::new(Default::default())));
+        let db_snapshot = DbSnapshot::<NoopQueryManager>::new(0, manager);
+        let db = StateDB::with_db_snapshot(db_snapshot).unwrap();
+        let key_hash = KeyHash([1u8; 32]);
+        let key = vec![2u8; 100];
+        let deleted_key_hash = KeyHash([3u8; 32]);
+        let deleted_key = vec![4u8; 100];
+        let jmt = JellyfishMerkleTree::<_, sha2::Sha256>::new(&db);
+
+        db.put_preimages(vec![(key_hash, &key), (deleted_key_hash, &deleted_key)])
+            .unwrap();
+        for version in 1..=3u64 {
+            // the second key is deleted after version 1
+            let deleted_value = (version == 1).then(|| vec![0]);
+            let (_, tree_update) = jmt
+                .put_value_set(
+                    vec![
+                        (key_hash, Some(vec![version as u8])),
+                        (deleted_key_hash, deleted_value),
+                    ],
+                    version,
+                )
+                .unwrap();
+            db.write_node_batch(&tree_update.node_batch).unwrap();
+            db.inc_next_version();
+        }
+        assert_eq!(db.get_next_version(), 4);
+        let root_hash = jmt.get_root_hash(1).unwrap();
+
+        db.rollback_to_version(1).unwrap();
+
+        assert_eq!(db.get_next_version(), 2);
+        // the nodes and values written after version 1 are gone
+        assert_eq!(jmt.get_root_hash(1).unwrap(), root_hash);
+        assert!(jmt.get_root_hash_option(2).unwrap().is_none());
+        assert!(jmt.get_root_hash_option(3).unwrap().is_none());
+        assert_eq!(db.get_value_option_by_key(3, &key).unwrap(), Some(vec![1]));
+        assert_eq!(
+            db.get_value_option_by_key(3, &deleted_key).unwrap(),
+            Some(vec![0])
+        );
+    }
 }
@@ -170,6 +170,38 @@ impl<Q: QueryManager> DbSnapshot<Q> {
         Ok(None)
     }
 
+    /// Visit keys written for given [`Schema`] in reverse lexicographic order,
+    /// until `visit` returns `false`. Keys are decoded one at a time and not kept in memory.
+    pub fn visit_keys_rev<S: Schema>(
+        &self,
+        mut visit: impl FnMut(S::Key) -> anyhow::Result<bool>,
+    ) -> anyhow::Result<()> {
+        let local_cache = self
+            .cache
+            .lock()
+            .expect("SchemaBatch lock must not be poisoned");
+        let local_cache_iter = local_cache.iter::<S>();
+
+        let parent = self
+            .parents_manager
+            .read()
+            .expect("Parent lock must not be poisoned");
+
+        let parent_iter = parent.iter::<S>(self.id)?;
+
+        let combined_iter: SnapshotIter<'_, S, _, _> = SnapshotIter {
+            local_cache_iter: local_cache_iter.peekable(),
+            parent_iter: parent_iter.peekable(),
+        };
+
+        for (key, _) in combined_iter {
+            if !visit(S::Key::decode_key(&key)?)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
     /// Get largest value in [`Schema`] that is smaller or equal than give `seek_key`
     pub fn get_prev<S: Schema>(
         &self,
@@ -2,7 +2,9 @@ use std::marker::PhantomData;
 
 use sov_rollup_interface::da::DaSpec;
 use sov_rollup_interface::spec::SpecId;
-use sov_rollup_interface::stf::{BatchReceipt, SlotResult, StateTransitionFunction};
+use sov_rollup_interface::stf::{
+    BatchReceipt, InitChainError, SlotResult, StateTransitionFunction,
+};
 use sov_rollup_interface::zk::{CumulativeStateDiff, ValidityCondition, Zkvm};
 
 /// A mock implementation of the [`StateTransitionFunction`]
@@ -28,8 +30,8 @@ impl<Vm: Zkvm, Cond: ValidityCondition, Da: DaSpec> StateTransitionFunction<Vm,
         &self,
         _base_state: Self::PreState,
         _params: Self::GenesisParams,
-    ) -> ([u8; 0], ()) {
-        ([], ())
+    ) -> Result<([u8; 0], ()), InitChainError> {
+        Ok(([], ()))
     }
 
     fn apply_slot<'a, I>(
@@ -58,7 +60,7 @@ impl<Vm: Zkvm, Cond: ValidityCondition, Da: DaSpec> StateTransitionFunction<Vm,
                 hash: [0; 32],
                 prev_hash: [0; 32],
                 tx_receipts: vec![],
-                phantom_data: PhantomData,
+                inner: (),
             }],
             witness: (),
             state_diff: vec![],
@@ -314,4 +314,11 @@ pub trait NativeStorage: Storage {
 
     /// Get the root hash of the tree at the requested version
     fn get_root_hash(&self, version: Version) -> Result<Self::Root, anyhow::Error>;
+
+    /// Get the version of the last committed state update
+    fn latest_version(&self) -> Version;
+
+    /// Discards the state and accessory updates committed after `version`,
+    /// so the next state update is committed on top of the tree at `version`.
+    fn rollback_to_version(&self, version: Version) -> Result<(), anyhow::Error>;
 }
@@ -1,3 +1,5 @@
+use std::io::Read;
+
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
@@ -9,3 +11,53 @@ pub struct Batch {
     /// Raw transactions.
     pub txs: Vec<RawTx>,
 }
+
+impl Batch {
+    /// Creates a batch of `txs`.
+    pub fn new(txs: Vec<RawTx>) -> Self {
+        Self { txs }
+    }
+
+    /// Raw transactions of the batch.
+    pub fn txs(&self) -> &[RawTx] {
+        &self.txs
+    }
+
+    /// Reads the borsh encoded batch from `reader` one transaction at a time,
+    /// instead of buffering the whole batch in memory first.
+    pub fn stream_txs<R: Read>(mut reader: R) -> std::io::Result<BatchTxReader<R>> {
+        let remaining = u32::deserialize_reader(&mut reader)?;
+        Ok(BatchTxReader { reader, remaining })
+    }
+}
+
+/// Iterator over the transactions of a borsh encoded [`Batch`], created by [`Batch::stream_txs`].
+pub struct BatchTxReader<R> {
+    reader: R,
+    remaining: u32,
+}
+
+impl<R> BatchTxReader<R> {
+    /// Number of transactions that have not been read yet.
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+}
+
+impl<R: Read> Iterator for BatchTxReader<R> {
+    type Item = std::io::Result<RawTx>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let tx = RawTx::deserialize_reader(&mut self.reader);
+        // Stop after the first error, the rest of the stream can't be trusted
+        self.remaining = if tx.is_ok() { self.remaining - 1 } else { 0 };
+        Some(tx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
@@ -1,8 +1,15 @@
 #![deny(missing_docs)]
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "native")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::time::Instant;
+
 use borsh::BorshDeserialize;
 use citrea_primitives::fork::{fork_from_block_number, Fork, ForkManager};
+use citrea_primitives::forks::TIMESTAMP_MONOTONICITY_SPECS;
 use itertools::Itertools;
 use rs_merkle::algorithms::Sha256;
 use rs_merkle::MerkleTree;
@@ -13,22 +20,31 @@ use sov_modules_api::hooks::{
 };
 use sov_modules_api::{
     native_debug, native_warn, BasicAddress, BlobReaderTrait, Context, DaSpec, DispatchCall,
-    Genesis, Signature, Spec, StateCheckpoint, UnsignedSoftConfirmationBatch, WorkingSet, Zkvm,
+    Genesis, Signature, Spec, StateCheckpoint, UnsignedSoftConfirmationBatch, ValidityCondition,
+    WorkingSet, Zkvm,
 };
-use sov_rollup_interface::da::{DaData, SequencerCommitment};
+use sov_rollup_interface::da::{CommitmentError, DaData, SequencerCommitment};
 use sov_rollup_interface::digest::Digest;
 use sov_rollup_interface::soft_confirmation::SignedSoftConfirmationBatch;
 use sov_rollup_interface::spec::SpecId;
 pub use sov_rollup_interface::stf::{BatchReceipt, TransactionReceipt};
-use sov_rollup_interface::stf::{SlotResult, StateTransitionFunction};
+use sov_rollup_interface::stf::{InitChainError, SlotResult, StateDiff, StateTransitionFunction};
 use sov_rollup_interface::zk::CumulativeStateDiff;
-use sov_state::Storage;
+#[cfg(feature = "native")]
+use sov_state::storage::NativeStorage;
+use sov_state::{OrderedReadsAndWrites, Storage};
 
 mod batch;
+#[cfg(feature = "native")]
+mod state_diff;
 mod stf_blueprint;
+#[cfg(test)]
+mod tests;
 mod tx_verifier;
 
-pub use batch::Batch;
+pub use batch::{Batch, BatchTxReader};
+#[cfg(feature = "native")]
+pub use state_diff::{compress_state_diff, decompress_state_diff};
 pub use stf_blueprint::StfBlueprint;
 pub use tx_verifier::RawTx;
 
@@ -40,6 +56,8 @@ pub struct RuntimeTxHook<C: Context> {
     pub sequencer: C::PublicKey,
     /// Current spec
     pub current_spec: SpecId,
+    /// DA slot height of the soft confirmation the tx belongs to
+    pub da_slot_height: u64,
 }
 
 /// This trait has to be implemented by a runtime in order to be used in `StfBlueprint`.
@@ -88,9 +106,58 @@ pub trait Runtime<C: Context, Da: DaSpec>:
 #[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum TxEffect {
     /// Batch was reverted.
-    Reverted,
+    Reverted(TxCost),
     /// Batch was processed successfully.
-    Successful,
+    Successful(TxCost),
+}
+
+impl TxEffect {
+    /// Returns the cost recorded for the transaction.
+    pub fn cost(&self) -> TxCost {
+        match self {
+            TxEffect::Reverted(cost) | TxEffect::Successful(cost) => *cost,
+        }
+    }
+}
+
+/// Summary of the transactions of an applied soft confirmation, for monitoring.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AppliedSummary {
+    /// L2 height of the soft confirmation.
+    pub l2_height: u64,
+    /// Number of transactions applied, including reverted ones.
+    pub tx_count: usize,
+    /// Number of transactions that were reverted.
+    pub reverted_tx_count: usize,
+}
+
+/// Summarizes the batch receipts of the [`SlotResult`] of the soft confirmation at `l2_height`,
+/// without going through the receipts of each transaction by hand.
+pub fn applied_summary<B>(
+    l2_height: u64,
+    batch_receipts: &[BatchReceipt<B, TxEffect>],
+) -> AppliedSummary {
+    let receipts = batch_receipts
+        .iter()
+        .flat_map(|batch_receipt| &batch_receipt.tx_receipts);
+    let (tx_count, reverted_tx_count) = receipts.fold((0, 0), |(total, reverted), receipt| {
+        let is_reverted = matches!(receipt.receipt, TxEffect::Reverted(_));
+        (total + 1, reverted + usize::from(is_reverted))
+    });
+    AppliedSummary {
+        l2_height,
+        tx_count,
+        reverted_tx_count,
+    }
+}
+
+/// The work done by a transaction, measured in serialized bytes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TxCost {
+    /// Size of the serialized transaction.
+    pub tx_size: u64,
+    /// Size of this and all preceding transactions of the soft confirmation.
+    pub cumulative_tx_size: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -117,14 +184,202 @@ pub struct GenesisParams<RT> {
 }
 
 /// Reason why sequencer was slashed.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, thiserror::Error,
+)]
+#[non_exhaustive]
 pub enum SlashingReason {
     /// This status indicates problem with batch deserialization.
+    #[error("invalid batch encoding")]
     InvalidBatchEncoding,
     /// Stateless verification failed, for example deserialized transactions have invalid signatures.
+    #[error("stateless verification failed")]
     StatelessVerificationFailed,
     /// This status indicates problem with transaction deserialization.
+    #[error("invalid transaction encoding")]
     InvalidTransactionEncoding,
+    /// The soft confirmation has more transactions than its spec allows.
+    #[error("too many transactions")]
+    TooManyTransactions,
+}
+
+impl SlashingReason {
+    /// The outcome for the sequencer at `sequencer_da_address` slashed for this reason.
+    pub fn into_outcome<A: BasicAddress>(self, sequencer_da_address: A) -> SequencerOutcome<A> {
+        SequencerOutcome::Slashed {
+            reason: self,
+            sequencer_da_address,
+        }
+    }
+}
+
+/// Storage failures while finalizing a soft confirmation.
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    /// The jellyfish merkle tree update could not be computed.
+    #[error("Failed to compute state update: {0}")]
+    StateUpdate(anyhow::Error),
+}
+
+/// Errors that can occur while applying soft confirmations from sequencer commitments.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SequencerCommitmentError {
+    /// No DA block header was provided for the DA height a soft confirmation references.
+    #[error("Missing DA block header for DA height {0}")]
+    MissingDaBlockHeader(u64),
+}
+
+/// Error returned by [`validate_sequencer_commitments_range`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CommitmentRangeError {
+    /// The start of the range is after its end.
+    #[error("Sequencer commitments range ({start}, {end}) is inverted")]
+    Inverted {
+        /// Index of the first commitment in the range
+        start: u32,
+        /// Index of the last commitment in the range
+        end: u32,
+    },
+    /// The end of the range points past the last available commitment.
+    #[error("Sequencer commitments range ends at index {end} but only {count} commitments are available")]
+    OutOfBounds {
+        /// Index of the last commitment in the range
+        end: u32,
+        /// Number of available commitments
+        count: usize,
+    },
+}
+
+/// Why a DA blob was ignored while extracting sequencer commitments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlobRejectionReason {
+    /// The blob was not sent by the sequencer.
+    NotFromSequencer,
+    /// The blob could not be deserialized into [`DaData`].
+    Malformed(String),
+    /// The blob is valid [`DaData`], but not a sequencer commitment.
+    NotSequencerCommitment,
+}
+
+/// A DA blob ignored while extracting sequencer commitments.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedBlob<A> {
+    /// Sender of the blob
+    pub sender: A,
+    /// Why the blob was ignored
+    pub reason: BlobRejectionReason,
+}
+
+/// Error returned when a soft confirmation is applied with a different sequencer public key
+/// than the one the [`StfBlueprint`] was pinned to.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Sequencer public key {} does not match the pinned sequencer public key {}", hex::encode(.got), hex::encode(.expected))]
+pub struct SequencerPublicKeyMismatch {
+    /// The pinned sequencer public key
+    pub expected: Vec<u8>,
+    /// The sequencer public key that was passed
+    pub got: Vec<u8>,
+}
+
+/// Error returned by [`verify_soft_confirmation_chain`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ChainError {
+    /// A soft confirmation does not point to the hash of the one before it.
+    #[error("Soft confirmation at index {index} has previous hash {}, expected {}", hex::encode(.got), hex::encode(.expected))]
+    PrevHashMismatch {
+        /// Index of the offending soft confirmation
+        index: usize,
+        /// Hash of the previous soft confirmation
+        expected: [u8; 32],
+        /// Previous hash the soft confirmation points to
+        got: [u8; 32],
+    },
+}
+
+/// Structural errors in sequencer commitments and the soft confirmations they commit to.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SoftConfirmationVerificationError {
+    /// The number of commitments, soft confirmation lists and DA header lists differ.
+    #[error("Got {commitments} commitments, {soft_confirmations} soft confirmation lists and {da_block_headers} DA header lists")]
+    LengthMismatch {
+        /// Number of sequencer commitments
+        commitments: usize,
+        /// Number of soft confirmation lists
+        soft_confirmations: usize,
+        /// Number of DA block header lists
+        da_block_headers: usize,
+    },
+    /// A commitment does not start right after the previous one ends.
+    #[error("Sequencer commitments must be sequential: expected L2 start {expected}, got {got}")]
+    NonSequentialCommitments {
+        /// Expected L2 start height
+        expected: u64,
+        /// Actual L2 start height
+        got: u64,
+    },
+    /// The commitment itself is malformed.
+    #[error("Invalid sequencer commitment: {0}")]
+    InvalidCommitment(#[from] CommitmentError),
+    /// The number of soft confirmations does not match the commitment's L2 range.
+    #[error("Sequencer commitment for L2 range {l2_start}..={l2_end} has {soft_confirmations} soft confirmations")]
+    L2RangeMismatch {
+        /// Start L2 height of the commitment
+        l2_start: u64,
+        /// End L2 height of the commitment
+        l2_end: u64,
+        /// Number of soft confirmations provided
+        soft_confirmations: usize,
+    },
+    /// No DA block headers were provided for a commitment.
+    #[error("No DA block headers provided for sequencer commitment {commitment_index}")]
+    NoDaHeaders {
+        /// Index of the commitment without DA block headers
+        commitment_index: usize,
+    },
+    /// A soft confirmation does not point to the hash of the previous soft confirmation.
+    #[error("Soft confirmation previous hash must match the hash of the block before")]
+    PrevHashMismatch,
+    /// A soft confirmation's DA slot hash does not match the DA block header.
+    #[error("Soft confirmation DA slot hash must match DA block header hash")]
+    DaSlotHashMismatch,
+    /// A soft confirmation's DA slot height does not match the DA block header.
+    #[error("Soft confirmation DA slot height must match DA block header height")]
+    DaSlotHeightMismatch,
+    /// A soft confirmation references a DA height before the DA block header of the soft
+    /// confirmation preceding it.
+    #[error("Soft confirmation DA slot height {da_slot_height} is before the current DA block header height {header_height}")]
+    DaSlotHeightBehindHeader {
+        /// DA slot height the soft confirmation references
+        da_slot_height: u64,
+        /// Height of the current DA block header
+        header_height: u64,
+    },
+    /// A soft confirmation references a DA block whose header was not provided.
+    #[error(transparent)]
+    MissingDaBlockHeader(#[from] SequencerCommitmentError),
+    /// DA block headers are not consecutive.
+    #[error(
+        "DA block headers must be in order: expected height {expected_height} on top of 0x{}, got height {height} on top of 0x{}",
+        hex::encode(.expected_prev_hash),
+        hex::encode(.prev_hash)
+    )]
+    DaBlockHeadersNotInOrder {
+        /// Height following the previous DA block header
+        expected_height: u64,
+        /// Hash of the previous DA block header
+        expected_prev_hash: [u8; 32],
+        /// Height of the out of order DA block header
+        height: u64,
+        /// Previous hash of the out of order DA block header
+        prev_hash: [u8; 32],
+    },
+    /// Some DA block headers are not referenced by any soft confirmation.
+    #[error("All DA headers must be checked")]
+    UnusedDaBlockHeaders,
+    /// The merkle root of the soft confirmation hashes does not match the commitment.
+    #[error("Invalid merkle root")]
+    InvalidMerkleRoot,
 }
 
 /// Trait for soft confirmation handling
@@ -145,28 +400,43 @@ pub trait StfBlueprintTrait<C: Context, Da: DaSpec, Vm: Zkvm>:
     ) -> (Result<(), ApplySoftConfirmationError>, WorkingSet<C>);
 
     /// Apply soft confirmation transactions
+    /// An empty list of transactions is valid and results in no transaction receipts.
+    /// If a transaction fails stateless verification or decoding, none are applied and the
+    /// reason the sequencer is slashed for is returned instead of the receipts.
     fn apply_soft_confirmation_txs(
         &self,
         current_spec: SpecId,
+        da_slot_height: u64,
         txs: Vec<Vec<u8>>,
         batch_workspace: WorkingSet<C>,
-    ) -> (WorkingSet<C>, Vec<TransactionReceipt<TxEffect>>);
+    ) -> (
+        WorkingSet<C>,
+        Result<Vec<TransactionReceipt<TxEffect>>, SlashingReason>,
+    );
 
     /// End a soft confirmation
+    /// `tx_receipts` is the result of [`StfBlueprintTrait::apply_soft_confirmation_txs`],
+    /// a slashing reason ends up as a [`SequencerOutcome::Slashed`] in the batch receipt.
+    /// On native, `verify_signatures` can be set to false to skip the signature check when
+    /// replaying soft confirmations that were already verified. The zk path always verifies.
     fn end_soft_confirmation(
         &self,
         current_spec: SpecId,
         sequencer_public_key: &[u8],
         soft_confirmation: &mut SignedSoftConfirmationBatch,
-        tx_receipts: Vec<TransactionReceipt<TxEffect>>,
+        tx_receipts: Result<Vec<TransactionReceipt<TxEffect>>, SlashingReason>,
         batch_workspace: WorkingSet<C>,
-    ) -> (BatchReceipt<(), TxEffect>, StateCheckpoint<C>);
+        #[cfg(feature = "native")] verify_signatures: bool,
+    ) -> (
+        BatchReceipt<SequencerOutcome<C::Address>, TxEffect>,
+        StateCheckpoint<C>,
+    );
 
     /// Finalizes a soft confirmation
     fn finalize_soft_confirmation(
         &self,
         current_spec: SpecId,
-        batch_receipt: BatchReceipt<(), TxEffect>,
+        batch_receipt: BatchReceipt<SequencerOutcome<C::Address>, TxEffect>,
         checkpoint: StateCheckpoint<C>,
         pre_state: Self::PreState,
         soft_confirmation: &mut SignedSoftConfirmationBatch,
@@ -177,6 +447,453 @@ pub trait StfBlueprintTrait<C: Context, Da: DaSpec, Vm: Zkvm>:
         Self::TxReceiptContents,
         Self::Witness,
     >;
+
+    /// Same as [`StfBlueprintTrait::finalize_soft_confirmation`], but returns storage
+    /// failures instead of panicking, so the node can shut down cleanly.
+    /// Defaults to calling [`StfBlueprintTrait::finalize_soft_confirmation`].
+    #[cfg(feature = "native")]
+    #[allow(clippy::type_complexity)]
+    fn try_finalize_soft_confirmation(
+        &self,
+        current_spec: SpecId,
+        batch_receipt: BatchReceipt<SequencerOutcome<C::Address>, TxEffect>,
+        checkpoint: StateCheckpoint<C>,
+        pre_state: Self::PreState,
+        soft_confirmation: &mut SignedSoftConfirmationBatch,
+    ) -> Result<
+        SlotResult<
+            Self::StateRoot,
+            Self::ChangeSet,
+            Self::BatchReceiptContents,
+            Self::TxReceiptContents,
+            Self::Witness,
+        >,
+        StorageError,
+    > {
+        Ok(self.finalize_soft_confirmation(
+            current_spec,
+            batch_receipt,
+            checkpoint,
+            pre_state,
+            soft_confirmation,
+        ))
+    }
+
+    /// Applies a single soft confirmation on top of the given pre-state root.
+    /// Returns the outcome of the soft confirmation verification together with the slot result.
+    /// If verification fails, the batch workspace is reverted and the pre-state root is returned.
+    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::type_complexity)]
+    fn apply_single_soft_confirmation(
+        &self,
+        current_spec: SpecId,
+        sequencer_public_key: &[u8],
+        pre_state_root: &Self::StateRoot,
+        pre_state: Self::PreState,
+        witness: <<C as Spec>::Storage as Storage>::Witness,
+        slot_header: &<Da as DaSpec>::BlockHeader,
+        soft_confirmation: &mut SignedSoftConfirmationBatch,
+    ) -> (
+        Result<(), ApplySoftConfirmationError>,
+        SlotResult<
+            Self::StateRoot,
+            Self::ChangeSet,
+            Self::BatchReceiptContents,
+            Self::TxReceiptContents,
+            Self::Witness,
+        >,
+    );
+}
+
+impl<C, RT, Vm, Da> StfBlueprint<C, Da, Vm, RT>
+where
+    C: Context,
+    Vm: Zkvm,
+    Da: DaSpec,
+    RT: Runtime<C, Da>,
+{
+    #[allow(clippy::type_complexity)]
+    fn finalize_soft_confirmation_inner(
+        &self,
+        batch_receipt: BatchReceipt<SequencerOutcome<C::Address>, TxEffect>,
+        checkpoint: StateCheckpoint<C>,
+        pre_state: C::Storage,
+        soft_confirmation: &mut SignedSoftConfirmationBatch,
+    ) -> Result<
+        SlotResult<
+            <C::Storage as Storage>::Root,
+            C::Storage,
+            SequencerOutcome<C::Address>,
+            TxEffect,
+            <<C as Spec>::Storage as Storage>::Witness,
+        >,
+        StorageError,
+    > {
+        native_debug!(
+            "soft confirmation with hash: {:?} from sequencer {:?} has been applied with #{} transactions.",
+            soft_confirmation.hash(),
+            soft_confirmation.sequencer_pub_key(),
+            batch_receipt.tx_receipts.len(),
+        );
+
+        let mut batch_receipts = vec![];
+
+        for (i, tx_receipt) in batch_receipt.tx_receipts.iter().enumerate() {
+            native_debug!(
+                "tx #{} hash: 0x{} result {:?}",
+                i,
+                hex::encode(tx_receipt.tx_hash),
+                tx_receipt.receipt
+            );
+        }
+        batch_receipts.push(batch_receipt);
+
+        let (state_root, witness, storage, state_diff) = {
+            let working_set = checkpoint.to_revertable();
+            // Save checkpoint
+            let mut checkpoint = working_set.checkpoint();
+
+            let (cache_log, mut witness) = checkpoint.freeze();
+
+            let (root_hash, state_diff) =
+                commit_state_update(&pre_state, cache_log, &mut witness, |root_hash| {
+                    let mut working_set = checkpoint.to_revertable();
+
+                    self.runtime
+                        .finalize_hook(root_hash, &mut working_set.accessory_state());
+
+                    let mut checkpoint = working_set.checkpoint();
+                    checkpoint.freeze_non_provable()
+                })?;
+
+            (root_hash, witness, pre_state, state_diff)
+        };
+
+        Ok(SlotResult {
+            state_root,
+            change_set: storage,
+            batch_receipts,
+            witness,
+            state_diff,
+        })
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn apply_soft_confirmations_from_sequencer_commitments_inner(
+        &self,
+        sequencer_public_key: &[u8],
+        sequencer_da_public_key: &[u8],
+        initial_state_root: &<C::Storage as Storage>::Root,
+        initial_batch_hash: [u8; 32],
+        pre_state: C::Storage,
+        da_data: Vec<<Da as DaSpec>::BlobTransaction>,
+        sequencer_commitments_range: (u32, u32),
+        witnesses: std::collections::VecDeque<Vec<<C::Storage as Storage>::Witness>>,
+        slot_headers: std::collections::VecDeque<Vec<<Da as DaSpec>::BlockHeader>>,
+        validity_condition: &<Da as DaSpec>::ValidityCondition,
+        soft_confirmations: std::collections::VecDeque<Vec<SignedSoftConfirmationBatch>>,
+        forks: Vec<(SpecId, u64)>,
+        #[cfg(feature = "native")] options: ApplyCommitmentsOptions<
+            '_,
+            <C::Storage as Storage>::Root,
+            <C::Storage as Storage>::Witness,
+        >,
+    ) -> (
+        <C::Storage as Storage>::Root,
+        CumulativeStateDiff,
+        Option<u64>,
+    ) {
+        #[cfg(feature = "native")]
+        let ApplyCommitmentsOptions {
+            mut applied_cache,
+            deadline,
+            witness_for,
+            mut on_applied,
+        } = options;
+        #[cfg(feature = "native")]
+        let mut witness_for = witness_for
+            .unwrap_or_else(|| Box::new(eager_witnesses(witnesses, &soft_confirmations)));
+        #[cfg(not(feature = "native"))]
+        let mut witness_for = eager_witnesses(witnesses, &soft_confirmations);
+
+        if let Err(e) = self.check_sequencer_public_key(sequencer_public_key) {
+            panic!("{}", e);
+        }
+
+        let mut state_diff = CumulativeStateDiff::default();
+
+        if let Err(index) = check_da_data_size(
+            &da_data,
+            sequencer_da_public_key,
+            self.max_da_bytes_per_commitment,
+        ) {
+            panic!(
+                "DA blobs of the sequencer exceed the limit of {} bytes at blob {}",
+                self.max_da_bytes_per_commitment, index
+            );
+        }
+
+        // First extract all sequencer commitments
+        let mut sequencer_commitments = extract_sequencer_commitments(
+            &da_data,
+            sequencer_da_public_key,
+            #[cfg(feature = "native")]
+            None,
+        );
+
+        // Sort commitments just in case
+        sort_sequencer_commitments(&mut sequencer_commitments);
+
+        if let Err(e) = validate_sequencer_commitments_range(
+            sequencer_commitments_range,
+            sequencer_commitments.len(),
+        ) {
+            native_warn!("{}", e);
+            panic!("{}", e);
+        }
+
+        let sequencer_commitments = sequencer_commitments
+            .into_iter()
+            .skip(sequencer_commitments_range.0 as usize)
+            .take(
+                sequencer_commitments_range.1 as usize - sequencer_commitments_range.0 as usize + 1,
+            )
+            .collect::<Vec<_>>();
+
+        // Then verify these soft confirmations.
+        #[cfg(feature = "native")]
+        let verification = verify_commitment_structure_parallel(
+            &sequencer_commitments,
+            initial_batch_hash,
+            &soft_confirmations,
+            &slot_headers,
+        );
+        #[cfg(not(feature = "native"))]
+        let verification = verify_commitment_structure(
+            &sequencer_commitments,
+            initial_batch_hash,
+            &soft_confirmations,
+            &slot_headers,
+        );
+        if let Err(e) = verification {
+            native_warn!("Invalid sequencer commitment structure: {}", e);
+            panic!("Invalid sequencer commitment structure: {}", e);
+        }
+
+        let mut current_state_root = initial_state_root.clone();
+        let mut last_timestamp = None;
+
+        #[cfg(feature = "native")]
+        let deadline_passed = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        #[cfg(not(feature = "native"))]
+        let deadline_passed = || false;
+
+        // should panic if number of sequencer commitments, soft confirmations and slot headers don't match
+        let commitments = sequencer_commitments
+            .into_iter()
+            .zip_eq(soft_confirmations)
+            .zip_eq(slot_headers)
+            .enumerate();
+        let applied_up_to_height = apply_commitments_until(
+            commitments,
+            deadline_passed,
+            |(commitment_index, ((sequencer_commitment, soft_confirmations), da_block_headers))| {
+                if let Err(index) = verify_timestamp_monotonicity(
+                    &soft_confirmations,
+                    sequencer_commitment.l2_start_block_number,
+                    last_timestamp,
+                    &forks,
+                    TIMESTAMP_MONOTONICITY_SPECS,
+                ) {
+                    panic!(
+                        "Soft confirmation at L2 height {} has a lower timestamp than the one before it",
+                        sequencer_commitment.l2_start_block_number + index as u64
+                    );
+                }
+                if let Some(last) = soft_confirmations.last() {
+                    last_timestamp = Some(last.timestamp());
+                }
+                if let Err(e) = verify_da_slot_height_monotonicity(
+                    &soft_confirmations,
+                    sequencer_commitment.l2_start_block_number,
+                    &forks,
+                    self.interleaved_da_slot_specs,
+                ) {
+                    native_warn!("Invalid sequencer commitment structure: {}", e);
+                    panic!("Invalid sequencer commitment structure: {}", e);
+                }
+
+                let assigned_da_block_headers =
+                    match assign_da_block_headers(&soft_confirmations, &da_block_headers) {
+                        Ok(assigned_da_block_headers) => assigned_da_block_headers,
+                        Err(e) => {
+                            let e = SoftConfirmationVerificationError::from(e);
+                            native_warn!("Invalid sequencer commitment structure: {}", e);
+                            panic!("Invalid sequencer commitment structure: {}", e);
+                        }
+                    };
+
+                let mut l2_height = sequencer_commitment.l2_start_block_number;
+                let mut fork_manager = ForkManager::for_height(l2_height, forks.clone())
+                    .expect("Fork table must not be empty");
+                let mut current_spec = fork_manager.active_fork();
+
+                // now that we verified the claimed root, we can apply the soft confirmations
+                for (confirmation_index, (mut soft_confirmation, da_block_header)) in
+                    soft_confirmations
+                        .into_iter()
+                        .zip(assigned_da_block_headers)
+                        .enumerate()
+                {
+                    // the DA validity condition must hold for the slot the soft confirmation is built on
+                    if let Err(e) = validity_condition.check(da_block_header) {
+                        let e: anyhow::Error = e.into();
+                        native_warn!(
+                            "Validity condition does not hold for DA block {}: {:?}",
+                            da_block_header.height(),
+                            e
+                        );
+                        panic!(
+                            "Validity condition does not hold for DA block {}: {:?}",
+                            da_block_header.height(),
+                            e
+                        );
+                    }
+
+                    let witness = witness_for(commitment_index, confirmation_index);
+
+                    #[cfg(feature = "native")]
+                    let hash = soft_confirmation.hash();
+                    let apply = || {
+                        let (_, result) = self.apply_single_soft_confirmation(
+                            current_spec,
+                            sequencer_public_key,
+                            &current_state_root,
+                            pre_state.clone(),
+                            witness,
+                            da_block_header,
+                            &mut soft_confirmation,
+                        );
+                        (result.state_root, result.state_diff)
+                    };
+
+                    #[cfg(feature = "native")]
+                    let (next_state_root, soft_confirmation_state_diff) =
+                        apply_or_reuse_soft_confirmation(
+                            applied_cache.as_deref_mut(),
+                            hash,
+                            &current_state_root,
+                            apply,
+                        )
+                        .unwrap_or_else(|e| panic!("{}", e));
+                    #[cfg(not(feature = "native"))]
+                    let (next_state_root, soft_confirmation_state_diff) = apply();
+
+                    #[cfg(feature = "native")]
+                    if let Some(on_applied) = on_applied.as_mut() {
+                        on_applied(l2_height, &next_state_root, &soft_confirmation_state_diff);
+                    }
+                    state_diff.extend(soft_confirmation_state_diff);
+                    current_state_root = next_state_root;
+
+                    // Notify fork manager about the block so that the next spec / fork
+                    // is transitioned into if criteria is met.
+                    if let Err(e) = fork_manager.register_block(l2_height) {
+                        panic!("Fork transition failed {}", e);
+                    }
+                    l2_height += 1;
+
+                    // Update current spec for the next iteration
+                    current_spec = fork_manager.active_fork();
+                }
+
+                sequencer_commitment.l2_end_block_number
+            },
+        );
+
+        (current_state_root, state_diff, applied_up_to_height)
+    }
+
+    /// Same as [`StateTransitionFunction::apply_soft_confirmations_from_sequencer_commitments`],
+    /// with the native-only behaviours chosen by `options`.
+    /// Returns the state root and diff together with the L2 height the soft confirmations were
+    /// applied up to, `None` if no commitment was applied before the deadline passed.
+    #[cfg(feature = "native")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply_soft_confirmations_from_sequencer_commitments_with_options(
+        &self,
+        sequencer_public_key: &[u8],
+        sequencer_da_public_key: &[u8],
+        initial_state_root: &<C::Storage as Storage>::Root,
+        initial_batch_hash: [u8; 32],
+        pre_state: C::Storage,
+        da_data: Vec<<Da as DaSpec>::BlobTransaction>,
+        sequencer_commitments_range: (u32, u32),
+        witnesses: std::collections::VecDeque<Vec<<C::Storage as Storage>::Witness>>,
+        slot_headers: std::collections::VecDeque<Vec<<Da as DaSpec>::BlockHeader>>,
+        validity_condition: &<Da as DaSpec>::ValidityCondition,
+        soft_confirmations: std::collections::VecDeque<Vec<SignedSoftConfirmationBatch>>,
+        forks: Vec<(SpecId, u64)>,
+        options: ApplyCommitmentsOptions<
+            '_,
+            <C::Storage as Storage>::Root,
+            <C::Storage as Storage>::Witness,
+        >,
+    ) -> (
+        <C::Storage as Storage>::Root,
+        CumulativeStateDiff,
+        Option<u64>,
+    ) {
+        self.apply_soft_confirmations_from_sequencer_commitments_inner(
+            sequencer_public_key,
+            sequencer_da_public_key,
+            initial_state_root,
+            initial_batch_hash,
+            pre_state,
+            da_data,
+            sequencer_commitments_range,
+            witnesses,
+            slot_headers,
+            validity_condition,
+            soft_confirmations,
+            forks,
+            options,
+        )
+    }
+
+    /// Reverts the soft confirmations applied above `target_height`, e.g. after a sequencer reorg.
+    /// Returns the state root recorded at `target_height`, on top of which the soft confirmations
+    /// above it have to be applied again. The state and accessory updates committed to
+    /// `pre_state` after the version recorded at `target_height` are discarded, as are the states
+    /// recorded above `target_height`, and `fork_manager` is rolled back to `target_height`.
+    #[cfg(feature = "native")]
+    pub fn revert_to_l2_height(
+        &self,
+        pre_state: C::Storage,
+        target_height: u64,
+        recorded_states: &mut BTreeMap<u64, RecordedState<<C::Storage as Storage>::Root>>,
+        fork_manager: &mut ForkManager,
+    ) -> anyhow::Result<<C::Storage as Storage>::Root>
+    where
+        C::Storage: NativeStorage,
+    {
+        let Some(recorded) = recorded_states.get(&target_height) else {
+            anyhow::bail!("No state recorded at L2 height {}", target_height);
+        };
+        if pre_state.get_root_hash(recorded.version)? != recorded.state_root {
+            anyhow::bail!(
+                "State root recorded at L2 height {} is not the one of storage version {}",
+                target_height,
+                recorded.version
+            );
+        }
+
+        // storage goes first, so a failed rollback leaves the fork manager in line with it
+        pre_state.rollback_to_version(recorded.version)?;
+        fork_manager.rollback_to(target_height)?;
+        let recorded = truncate_recorded_state_roots(recorded_states, target_height)?;
+        native_debug!("Reverted soft confirmations to L2 height {}", target_height);
+        Ok(recorded.state_root)
+    }
 }
 
 impl<C, RT, Vm, Da> StfBlueprintTrait<C, Da, Vm> for StfBlueprint<C, Da, Vm, RT>
@@ -232,10 +949,14 @@ where
     fn apply_soft_confirmation_txs(
         &self,
         current_spec: SpecId,
+        da_slot_height: u64,
         txs: Vec<Vec<u8>>,
         batch_workspace: WorkingSet<C>,
-    ) -> (WorkingSet<C>, Vec<TransactionReceipt<TxEffect>>) {
-        self.apply_sov_txs_inner(txs, current_spec, batch_workspace)
+    ) -> (
+        WorkingSet<C>,
+        Result<Vec<TransactionReceipt<TxEffect>>, SlashingReason>,
+    ) {
+        self.apply_sov_txs_inner(txs, current_spec, da_slot_height, batch_workspace)
     }
 
     fn end_soft_confirmation(
@@ -243,9 +964,13 @@ where
         _current_spec: SpecId,
         sequencer_public_key: &[u8],
         soft_confirmation: &mut SignedSoftConfirmationBatch,
-        tx_receipts: Vec<TransactionReceipt<TxEffect>>,
+        tx_receipts: Result<Vec<TransactionReceipt<TxEffect>>, SlashingReason>,
         batch_workspace: WorkingSet<C>,
-    ) -> (BatchReceipt<(), TxEffect>, StateCheckpoint<C>) {
+        #[cfg(feature = "native")] verify_signatures: bool,
+    ) -> (
+        BatchReceipt<SequencerOutcome<C::Address>, TxEffect>,
+        StateCheckpoint<C>,
+    ) {
         let unsigned = UnsignedSoftConfirmationBatch::new(
             soft_confirmation.da_slot_height(),
             soft_confirmation.da_slot_hash(),
@@ -261,16 +986,20 @@ where
         // check the claimed hash
         assert_eq!(
             soft_confirmation.hash(),
-            Into::<[u8; 32]>::into(<C as Spec>::Hasher::digest(unsigned_raw)),
+            Into::<[u8; 32]>::into(<C as Spec>::Hasher::digest(&unsigned_raw)),
             "Soft confirmation hashes must match"
         );
 
-        // verify signature
+        #[cfg(not(feature = "native"))]
+        let verify_signatures = true;
+
+        // verify signature over the already serialized batch
         assert!(
-            verify_soft_confirmation_signature::<C>(
-                unsigned,
+            verify_soft_confirmation_signature_unless_trusted::<C>(
+                &unsigned_raw,
                 soft_confirmation.signature().as_slice(),
-                sequencer_public_key
+                sequencer_public_key,
+                verify_signatures,
             )
             .is_ok(),
             "Signature verification must succeed"
@@ -285,66 +1014,125 @@ where
     fn finalize_soft_confirmation(
         &self,
         _current_spec: SpecId,
-        batch_receipt: BatchReceipt<(), TxEffect>,
+        batch_receipt: BatchReceipt<SequencerOutcome<C::Address>, TxEffect>,
         checkpoint: StateCheckpoint<C>,
         pre_state: Self::PreState,
         soft_confirmation: &mut SignedSoftConfirmationBatch,
     ) -> SlotResult<
         <C::Storage as Storage>::Root,
         C::Storage,
-        (),
+        SequencerOutcome<C::Address>,
         TxEffect,
         <<C as Spec>::Storage as Storage>::Witness,
     > {
-        native_debug!(
-            "soft confirmation with hash: {:?} from sequencer {:?} has been applied with #{} transactions.",
-            soft_confirmation.hash(),
-            soft_confirmation.sequencer_pub_key(),
-            batch_receipt.tx_receipts.len(),
-        );
-
-        let mut batch_receipts = vec![];
-
-        for (i, tx_receipt) in batch_receipt.tx_receipts.iter().enumerate() {
-            native_debug!(
-                "tx #{} hash: 0x{} result {:?}",
-                i,
-                hex::encode(tx_receipt.tx_hash),
-                tx_receipt.receipt
-            );
-        }
-        batch_receipts.push(batch_receipt);
-
-        let (state_root, witness, storage, state_diff) = {
-            let working_set = checkpoint.to_revertable();
-            // Save checkpoint
-            let mut checkpoint = working_set.checkpoint();
-
-            let (cache_log, mut witness) = checkpoint.freeze();
-
-            let (root_hash, state_update, state_diff) = pre_state
-                .compute_state_update(cache_log, &mut witness)
-                .expect("jellyfish merkle tree update must succeed");
-
-            let mut working_set = checkpoint.to_revertable();
+        self.finalize_soft_confirmation_inner(
+            batch_receipt,
+            checkpoint,
+            pre_state,
+            soft_confirmation,
+        )
+        .expect("jellyfish merkle tree update must succeed")
+    }
 
-            self.runtime
-                .finalize_hook(&root_hash, &mut working_set.accessory_state());
+    #[cfg(feature = "native")]
+    fn try_finalize_soft_confirmation(
+        &self,
+        _current_spec: SpecId,
+        batch_receipt: BatchReceipt<SequencerOutcome<C::Address>, TxEffect>,
+        checkpoint: StateCheckpoint<C>,
+        pre_state: Self::PreState,
+        soft_confirmation: &mut SignedSoftConfirmationBatch,
+    ) -> Result<
+        SlotResult<
+            <C::Storage as Storage>::Root,
+            C::Storage,
+            SequencerOutcome<C::Address>,
+            TxEffect,
+            <<C as Spec>::Storage as Storage>::Witness,
+        >,
+        StorageError,
+    > {
+        self.finalize_soft_confirmation_inner(
+            batch_receipt,
+            checkpoint,
+            pre_state,
+            soft_confirmation,
+        )
+    }
 
-            let mut checkpoint = working_set.checkpoint();
-            let accessory_log = checkpoint.freeze_non_provable();
+    fn apply_single_soft_confirmation(
+        &self,
+        current_spec: SpecId,
+        sequencer_public_key: &[u8],
+        pre_state_root: &Self::StateRoot,
+        pre_state: Self::PreState,
+        witness: <<C as Spec>::Storage as Storage>::Witness,
+        slot_header: &<Da as DaSpec>::BlockHeader,
+        soft_confirmation: &mut SignedSoftConfirmationBatch,
+    ) -> (
+        Result<(), ApplySoftConfirmationError>,
+        SlotResult<
+            <C::Storage as Storage>::Root,
+            C::Storage,
+            SequencerOutcome<C::Address>,
+            TxEffect,
+            <<C as Spec>::Storage as Storage>::Witness,
+        >,
+    ) {
+        match self.begin_soft_confirmation(
+            current_spec,
+            sequencer_public_key,
+            pre_state_root,
+            pre_state.clone(),
+            witness,
+            slot_header,
+            soft_confirmation,
+        ) {
+            (Ok(()), batch_workspace) => {
+                let (batch_workspace, tx_receipts) = self.apply_soft_confirmation_txs(
+                    current_spec,
+                    soft_confirmation.da_slot_height(),
+                    soft_confirmation.txs(),
+                    batch_workspace,
+                );
 
-            pre_state.commit(&state_update, &accessory_log);
+                let (batch_receipt, checkpoint) = self.end_soft_confirmation(
+                    current_spec,
+                    sequencer_public_key,
+                    soft_confirmation,
+                    tx_receipts,
+                    batch_workspace,
+                    #[cfg(feature = "native")]
+                    true,
+                );
 
-            (root_hash, witness, pre_state, state_diff)
-        };
+                let slot_result = self.finalize_soft_confirmation(
+                    current_spec,
+                    batch_receipt,
+                    checkpoint,
+                    pre_state,
+                    soft_confirmation,
+                );
 
-        SlotResult {
-            state_root,
-            change_set: storage,
-            batch_receipts,
-            witness,
-            state_diff,
+                (Ok(()), slot_result)
+            }
+            (Err(err), batch_workspace) => {
+                native_warn!(
+                    "Error applying soft confirmation: {:?} \n reverting batch workspace",
+                    err
+                );
+                batch_workspace.revert();
+                (
+                    Err(err),
+                    SlotResult {
+                        state_root: pre_state_root.clone(),
+                        change_set: pre_state, // should be empty
+                        batch_receipts: vec![],
+                        witness: <<C as Spec>::Storage as Storage>::Witness::default(),
+                        state_diff: vec![],
+                    },
+                )
+            }
         }
     }
 }
@@ -364,8 +1152,7 @@ where
 
     type TxReceiptContents = TxEffect;
 
-    type BatchReceiptContents = ();
-    // SequencerOutcome<<Da::BlobTransaction as BlobReaderTrait>::Address>;
+    type BatchReceiptContents = SequencerOutcome<C::Address>;
 
     type Witness = <<C as Spec>::Storage as Storage>::Witness;
 
@@ -375,19 +1162,19 @@ where
         &self,
         pre_state: Self::PreState,
         params: Self::GenesisParams,
-    ) -> (Self::StateRoot, Self::ChangeSet) {
+    ) -> Result<(Self::StateRoot, Self::ChangeSet), InitChainError> {
         let mut working_set = StateCheckpoint::new(pre_state.clone()).to_revertable();
 
         self.runtime
             .genesis(&params.runtime, &mut working_set)
-            .expect("Runtime initialization must succeed");
+            .map_err(|sov_modules_api::Error::ModuleError(e)| InitChainError::RuntimeGenesis(e))?;
 
         let mut checkpoint = working_set.checkpoint();
         let (log, mut witness) = checkpoint.freeze();
 
         let (genesis_hash, state_update, _) = pre_state
             .compute_state_update(log, &mut witness)
-            .expect("Storage update must succeed");
+            .map_err(InitChainError::StorageUpdate)?;
 
         let mut working_set = checkpoint.to_revertable();
 
@@ -400,7 +1187,7 @@ where
         // TODO: Commit is fine
         pre_state.commit(&state_update, &accessory_log);
 
-        (genesis_hash, pre_state)
+        Ok((genesis_hash, pre_state))
     }
 
     fn apply_slot<'a, I>(
@@ -433,7 +1220,7 @@ where
         pre_state: Self::PreState,
         witness: Self::Witness,
         slot_header: &<Da as DaSpec>::BlockHeader,
-        _validity_condition: &<Da as DaSpec>::ValidityCondition,
+        validity_condition: &<Da as DaSpec>::ValidityCondition,
         soft_confirmation: &mut SignedSoftConfirmationBatch,
     ) -> SlotResult<
         Self::StateRoot,
@@ -442,53 +1229,37 @@ where
         Self::TxReceiptContents,
         Self::Witness,
     > {
-        match self.begin_soft_confirmation(
+        if let Err(e) = self.check_sequencer_public_key(sequencer_public_key) {
+            panic!("{}", e);
+        }
+
+        // the DA validity condition must hold for the slot the soft confirmation is built on
+        if let Err(e) = validity_condition.check(slot_header) {
+            let e: anyhow::Error = e.into();
+            native_warn!(
+                "Validity condition does not hold for DA block {}: {:?} \n reverting batch",
+                slot_header.height(),
+                e
+            );
+            return SlotResult {
+                state_root: pre_state_root.clone(),
+                change_set: pre_state,
+                batch_receipts: vec![],
+                witness: <<C as Spec>::Storage as Storage>::Witness::default(),
+                state_diff: vec![],
+            };
+        }
+
+        self.apply_single_soft_confirmation(
             current_spec,
             sequencer_public_key,
             pre_state_root,
-            pre_state.clone(),
+            pre_state,
             witness,
             slot_header,
             soft_confirmation,
-        ) {
-            (Ok(()), batch_workspace) => {
-                let (batch_workspace, tx_receipts) = self.apply_soft_confirmation_txs(
-                    current_spec,
-                    soft_confirmation.txs(),
-                    batch_workspace,
-                );
-
-                let (batch_receipt, checkpoint) = self.end_soft_confirmation(
-                    current_spec,
-                    sequencer_public_key,
-                    soft_confirmation,
-                    tx_receipts,
-                    batch_workspace,
-                );
-
-                self.finalize_soft_confirmation(
-                    current_spec,
-                    batch_receipt,
-                    checkpoint,
-                    pre_state,
-                    soft_confirmation,
-                )
-            }
-            (Err(err), batch_workspace) => {
-                native_warn!(
-                    "Error applying soft confirmation: {:?} \n reverting batch workspace",
-                    err
-                );
-                batch_workspace.revert();
-                SlotResult {
-                    state_root: pre_state_root.clone(),
-                    change_set: pre_state, // should be empty
-                    batch_receipts: vec![],
-                    witness: <<C as Spec>::Storage as Storage>::Witness::default(),
-                    state_diff: vec![],
-                }
-            }
-        }
+        )
+        .1
     }
 
     fn apply_soft_confirmations_from_sequencer_commitments(
@@ -506,232 +1277,779 @@ where
         soft_confirmations: std::collections::VecDeque<Vec<SignedSoftConfirmationBatch>>,
         forks: Vec<(SpecId, u64)>,
     ) -> (Self::StateRoot, CumulativeStateDiff) {
-        let mut state_diff = CumulativeStateDiff::default();
-
-        // First extract all sequencer commitments
-        // Ignore broken DaData and zk proofs. Also ignore ForcedTransaction's (will be implemented in the future).
-        let mut sequencer_commitments: Vec<SequencerCommitment> = vec![];
-        for blob in da_data {
-            // TODO: get sequencer da pub key
-            if blob.sender().as_ref() == sequencer_da_public_key {
-                let da_data = DaData::try_from_slice(blob.verified_data());
-
-                if let Ok(DaData::SequencerCommitment(commitment)) = da_data {
-                    sequencer_commitments.push(commitment);
-                }
-            }
-        }
+        let (state_root, state_diff, _) = self
+            .apply_soft_confirmations_from_sequencer_commitments_inner(
+                sequencer_public_key,
+                sequencer_da_public_key,
+                initial_state_root,
+                initial_batch_hash,
+                pre_state,
+                da_data,
+                sequencer_commitments_range,
+                witnesses,
+                slot_headers,
+                validity_condition,
+                soft_confirmations,
+                forks,
+                #[cfg(feature = "native")]
+                ApplyCommitmentsOptions::default(),
+            );
+        (state_root, state_diff)
+    }
+}
 
-        // Sort commitments just in case
-        sequencer_commitments.sort_unstable();
+/// Checks the structural invariants of sequencer commitments without applying any state:
+/// commitments are sequential, soft confirmations chain by previous hash and reference
+/// consecutive DA block headers, and the merkle root of the claimed soft confirmation hashes
+/// matches each commitment. DA slot heights don't go back outside of the `interleaved_specs`,
+/// with the spec of each soft confirmation determined by `forks`, as when applying them.
+/// Meant to reject malformed inputs before running the full state transition.
+pub fn verify_commitment_structure<H: BlockHeaderTrait>(
+    sequencer_commitments: &[SequencerCommitment],
+    initial_batch_hash: [u8; 32],
+    soft_confirmations: &std::collections::VecDeque<Vec<SignedSoftConfirmationBatch>>,
+    da_block_headers: &std::collections::VecDeque<Vec<H>>,
+    forks: &[(SpecId, u64)],
+    interleaved_specs: &[SpecId],
+) -> Result<(), SoftConfirmationVerificationError> {
+    check_commitment_input_lengths(sequencer_commitments, soft_confirmations, da_block_headers)?;
+    // if the commitments are not sequential, then the proof is invalid.
+    check_commitments_sequential(sequencer_commitments)?;
+
+    let mut previous_batch_hash = initial_batch_hash;
+
+    for ((sequencer_commitment, soft_confirmations), da_block_headers) in sequencer_commitments
+        .iter()
+        .zip(soft_confirmations)
+        .zip(da_block_headers)
+    {
+        previous_batch_hash = verify_single_commitment_structure(
+            sequencer_commitment,
+            previous_batch_hash,
+            soft_confirmations,
+            da_block_headers,
+            forks,
+            interleaved_specs,
+        )?;
+    }
 
-        // Then verify these soft confirmations.
+    Ok(())
+}
 
-        let mut current_state_root = initial_state_root.clone();
-        let mut previous_batch_hash = initial_batch_hash;
-        let mut last_commitment_end_height: Option<u64> = None;
-
-        // should panic if number of sequencer commitments, soft confirmations, slot headers and witnesses don't match
-        for (((sequencer_commitment, soft_confirmations), da_block_headers), witnesses) in
-            sequencer_commitments
-                .into_iter()
-                .skip(sequencer_commitments_range.0 as usize)
-                .take(
-                    sequencer_commitments_range.1 as usize - sequencer_commitments_range.0 as usize
-                        + 1,
+/// Parallel version of [`verify_commitment_structure`], returning the same result.
+/// Commitments only depend on each other through the hash of the last soft confirmation of
+/// the previous commitment, which is known upfront, so each commitment is verified on its own.
+#[cfg(feature = "native")]
+pub fn verify_commitment_structure_parallel<H: BlockHeaderTrait + Sync>(
+    sequencer_commitments: &[SequencerCommitment],
+    initial_batch_hash: [u8; 32],
+    soft_confirmations: &std::collections::VecDeque<Vec<SignedSoftConfirmationBatch>>,
+    da_block_headers: &std::collections::VecDeque<Vec<H>>,
+    forks: &[(SpecId, u64)],
+    interleaved_specs: &[SpecId],
+) -> Result<(), SoftConfirmationVerificationError> {
+    use rayon::prelude::*;
+
+    check_commitment_input_lengths(sequencer_commitments, soft_confirmations, da_block_headers)?;
+    check_commitments_sequential(sequencer_commitments)?;
+
+    // an empty commitment fails verification on its own, so the hash it hands over is irrelevant.
+    let previous_batch_hashes = std::iter::once(initial_batch_hash)
+        .chain(soft_confirmations.iter().map(|soft_confirmations| {
+            soft_confirmations
+                .last()
+                .map(|soft_confirmation| soft_confirmation.hash())
+                .unwrap_or_default()
+        }))
+        .take(sequencer_commitments.len())
+        .collect::<Vec<_>>();
+
+    let results = sequencer_commitments
+        .par_iter()
+        .zip(soft_confirmations.par_iter())
+        .zip(da_block_headers.par_iter())
+        .zip(previous_batch_hashes.par_iter())
+        .map(
+            |(
+                ((sequencer_commitment, soft_confirmations), da_block_headers),
+                previous_batch_hash,
+            )| {
+                verify_single_commitment_structure(
+                    sequencer_commitment,
+                    *previous_batch_hash,
+                    soft_confirmations,
+                    da_block_headers,
+                    forks,
+                    interleaved_specs,
                 )
-                .zip_eq(soft_confirmations)
-                .zip_eq(slot_headers)
-                .zip_eq(witnesses)
+            },
+        )
+        .collect::<Vec<_>>();
+
+    // report the first failure in commitment order, same as the sequential verification.
+    for result in results {
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Checks that soft confirmation timestamps do not decrease, for soft confirmations in one of the
+/// `enforced_specs`. `soft_confirmations` start at `l2_start_block_number`, which together with
+/// `forks` determines the spec of each soft confirmation. `prev_timestamp` is the timestamp of
+/// the soft confirmation before the first one, if any, so that the check carries over from one
+/// sequencer commitment to the next.
+/// Returns the index of the first soft confirmation with a lower timestamp than the one before it.
+pub fn verify_timestamp_monotonicity(
+    soft_confirmations: &[SignedSoftConfirmationBatch],
+    l2_start_block_number: u64,
+    prev_timestamp: Option<u64>,
+    forks: &[(SpecId, u64)],
+    enforced_specs: &[SpecId],
+) -> Result<(), usize> {
+    if enforced_specs.is_empty() {
+        return Ok(());
+    }
+
+    let mut prev_timestamp = prev_timestamp;
+    for (index, soft_confirmation) in soft_confirmations.iter().enumerate() {
+        let spec = fork_from_block_number(forks, l2_start_block_number + index as u64);
+        if enforced_specs.contains(&spec)
+            && prev_timestamp
+                .is_some_and(|prev_timestamp| soft_confirmation.timestamp() < prev_timestamp)
         {
-            // if the commitment is not sequential, then the proof is invalid.
-            if let Some(end_height) = last_commitment_end_height {
-                assert_eq!(
-                    end_height + 1,
-                    sequencer_commitment.l2_start_block_number,
-                    "Sequencer commitments must be sequential"
-                );
+            return Err(index);
+        }
+        prev_timestamp = Some(soft_confirmation.timestamp());
+    }
+    Ok(())
+}
 
-                last_commitment_end_height = Some(sequencer_commitment.l2_end_block_number);
-            } else {
-                last_commitment_end_height = Some(sequencer_commitment.l2_end_block_number);
+/// Checks that soft confirmations of a sequencer commitment don't go back to an earlier DA height
+/// than the highest one referenced before them, unless they are in one of the `interleaved_specs`.
+/// `soft_confirmations` start at `l2_start_block_number`, which together with `forks` determines
+/// the spec of each soft confirmation.
+pub fn verify_da_slot_height_monotonicity(
+    soft_confirmations: &[SignedSoftConfirmationBatch],
+    l2_start_block_number: u64,
+    forks: &[(SpecId, u64)],
+    interleaved_specs: &[SpecId],
+) -> Result<(), SoftConfirmationVerificationError> {
+    let mut header_height = None;
+    for (index, soft_confirmation) in soft_confirmations.iter().enumerate() {
+        let da_slot_height = soft_confirmation.da_slot_height();
+        let spec = fork_from_block_number(forks, l2_start_block_number + index as u64);
+        if let Some(header_height) = header_height.filter(|&height| da_slot_height < height) {
+            if !interleaved_specs.contains(&spec) {
+                return Err(
+                    SoftConfirmationVerificationError::DaSlotHeightBehindHeader {
+                        da_slot_height,
+                        header_height,
+                    },
+                );
             }
+        }
+        header_height = header_height.max(Some(da_slot_height));
+    }
+    Ok(())
+}
 
-            // we must verify given DA headers match the commitments
-            let mut index_headers = 0;
-            let mut index_soft_confirmation = 0;
-            let mut current_da_height = da_block_headers[index_headers].height();
-
-            assert_eq!(
-                soft_confirmations[index_soft_confirmation].prev_hash(),
-                previous_batch_hash,
-                "Soft confirmation previous hash must match the hash of the block before"
-            );
+/// Checks that a soft confirmation with `num_txs` transactions stays within the limit
+/// `tx_limits` sets for `spec`. Specs without a limit accept any number of transactions.
+/// Returns the exceeded limit.
+pub fn verify_soft_confirmation_tx_limit(
+    spec: SpecId,
+    num_txs: usize,
+    tx_limits: &[(SpecId, usize)],
+) -> Result<(), usize> {
+    match tx_limits
+        .iter()
+        .find(|(limited_spec, _)| *limited_spec == spec)
+    {
+        Some(&(_, limit)) if num_txs > limit => Err(limit),
+        _ => Ok(()),
+    }
+}
 
-            assert_eq!(
-                soft_confirmations[index_soft_confirmation].da_slot_hash(),
-                da_block_headers[index_headers].hash().into(),
-                "Soft confirmation DA slot hash must match DA block header hash"
-            );
+/// Whether forced transactions are processed at `l2_height`, that is whether the spec active
+/// at that height per `forks` is one of the `enabled_specs`. Blocks before the activation height
+/// of an enabling spec must keep ignoring forced transactions.
+pub fn forced_transactions_enabled(
+    l2_height: u64,
+    forks: &[(SpecId, u64)],
+    enabled_specs: &[SpecId],
+) -> bool {
+    enabled_specs.contains(&fork_from_block_number(forks, l2_height))
+}
 
-            assert_eq!(
-                soft_confirmations[index_soft_confirmation].da_slot_height(),
-                da_block_headers[index_headers].height(),
-                "Soft confirmation DA slot height must match DA block header height"
-            );
+/// Checks that an inclusive `(start, end)` range of sequencer commitment indices
+/// selects at least one of the `commitment_count` available commitments.
+pub fn validate_sequencer_commitments_range(
+    range: (u32, u32),
+    commitment_count: usize,
+) -> Result<(), CommitmentRangeError> {
+    let (start, end) = range;
+    if start > end {
+        return Err(CommitmentRangeError::Inverted { start, end });
+    }
+    if end as usize >= commitment_count {
+        return Err(CommitmentRangeError::OutOfBounds {
+            end,
+            count: commitment_count,
+        });
+    }
+    Ok(())
+}
 
-            previous_batch_hash = soft_confirmations[index_soft_confirmation].hash();
-            index_soft_confirmation += 1;
+/// Checks that each commitment starts right after the L2 block the previous one ended at.
+/// Returns the index of the first commitment that leaves a gap or overlaps the previous one.
+pub fn validate_commitment_sequentiality(commitments: &[SequencerCommitment]) -> Result<(), u64> {
+    match commitments
+        .windows(2)
+        .position(|pair| pair[0].l2_end_block_number + 1 != pair[1].l2_start_block_number)
+    {
+        Some(position) => Err(position as u64 + 1),
+        None => Ok(()),
+    }
+}
 
-            while index_soft_confirmation < soft_confirmations.len() {
-                // the soft confirmations DA hash must equal to da hash in index_headers
-                // if it's not matching, and if it's not matching the next one, then state transition is invalid.
+fn check_commitments_sequential(
+    sequencer_commitments: &[SequencerCommitment],
+) -> Result<(), SoftConfirmationVerificationError> {
+    validate_commitment_sequentiality(sequencer_commitments).map_err(|index| {
+        let index = index as usize;
+        SoftConfirmationVerificationError::NonSequentialCommitments {
+            expected: sequencer_commitments[index - 1].l2_end_block_number + 1,
+            got: sequencer_commitments[index].l2_start_block_number,
+        }
+    })
+}
 
-                if soft_confirmations[index_soft_confirmation].da_slot_hash()
-                    == da_block_headers[index_headers].hash().into()
-                {
-                    assert_eq!(
-                        soft_confirmations[index_soft_confirmation].da_slot_height(),
-                        da_block_headers[index_headers].height(),
-                        "Soft confirmation DA slot height must match DA block header height"
-                    );
+fn check_commitment_input_lengths<H>(
+    sequencer_commitments: &[SequencerCommitment],
+    soft_confirmations: &std::collections::VecDeque<Vec<SignedSoftConfirmationBatch>>,
+    da_block_headers: &std::collections::VecDeque<Vec<H>>,
+) -> Result<(), SoftConfirmationVerificationError> {
+    if sequencer_commitments.len() != soft_confirmations.len()
+        || sequencer_commitments.len() != da_block_headers.len()
+    {
+        return Err(SoftConfirmationVerificationError::LengthMismatch {
+            commitments: sequencer_commitments.len(),
+            soft_confirmations: soft_confirmations.len(),
+            da_block_headers: da_block_headers.len(),
+        });
+    }
+    if let Some(commitment_index) = da_block_headers.iter().position(Vec::is_empty) {
+        return Err(SoftConfirmationVerificationError::NoDaHeaders { commitment_index });
+    }
+    Ok(())
+}
 
-                    assert_eq!(
-                        soft_confirmations[index_soft_confirmation].prev_hash(),
-                        previous_batch_hash,
-                        "Soft confirmation previous hash must match the hash of the block before"
-                    );
+/// Checks that soft confirmations form a hash chain: the first one points to `initial_prev_hash`
+/// and every other one to the hash of the soft confirmation before it.
+/// Does not need DA data or state, so it can be used to check L2 headers on their own.
+pub fn verify_soft_confirmation_chain(
+    confirmations: &[SignedSoftConfirmationBatch],
+    initial_prev_hash: [u8; 32],
+) -> Result<(), ChainError> {
+    let mut expected = initial_prev_hash;
+    for (index, confirmation) in confirmations.iter().enumerate() {
+        if confirmation.prev_hash() != expected {
+            return Err(ChainError::PrevHashMismatch {
+                index,
+                expected,
+                got: confirmation.prev_hash(),
+            });
+        }
+        expected = confirmation.hash();
+    }
+    Ok(())
+}
 
-                    previous_batch_hash = soft_confirmations[index_soft_confirmation].hash();
-                    index_soft_confirmation += 1;
-                } else {
-                    index_headers += 1;
+/// Verifies the structure of a single commitment and returns the hash of its last soft confirmation.
+fn verify_single_commitment_structure<H: BlockHeaderTrait>(
+    sequencer_commitment: &SequencerCommitment,
+    previous_batch_hash: [u8; 32],
+    soft_confirmations: &[SignedSoftConfirmationBatch],
+    da_block_headers: &[H],
+    forks: &[(SpecId, u64)],
+    interleaved_specs: &[SpecId],
+) -> Result<[u8; 32], SoftConfirmationVerificationError> {
+    sequencer_commitment.validate()?;
+
+    let l2_range_length = sequencer_commitment
+        .l2_end_block_number
+        .checked_sub(sequencer_commitment.l2_start_block_number)
+        .map(|diff| diff + 1);
+    if soft_confirmations.is_empty() || l2_range_length != Some(soft_confirmations.len() as u64) {
+        return Err(SoftConfirmationVerificationError::L2RangeMismatch {
+            l2_start: sequencer_commitment.l2_start_block_number,
+            l2_end: sequencer_commitment.l2_end_block_number,
+            soft_confirmations: soft_confirmations.len(),
+        });
+    }
 
-                    // this can also be done in soft confirmation rule enforcer?
-                    assert_eq!(
-                        da_block_headers[index_headers].height(),
-                        current_da_height + 1,
-                        "DA block headers must be in order"
-                    );
+    // claimed hashes are collected once and reused for the prev hash chain and the merkle root.
+    // they will be checked against the actual batch contents inside apply_soft_confirmation.
+    let soft_confirmation_hashes = soft_confirmations
+        .iter()
+        .map(|soft_confirmation| soft_confirmation.hash())
+        .collect::<Vec<_>>();
+
+    verify_soft_confirmation_chain(soft_confirmations, previous_batch_hash)
+        .map_err(|_| SoftConfirmationVerificationError::PrevHashMismatch)?;
+
+    // we must verify given DA headers match the commitments, starting with them forming a chain
+    for headers in da_block_headers.windows(2) {
+        let expected_height = headers[0].height() + 1;
+        let expected_prev_hash: [u8; 32] = headers[0].hash().into();
+        let prev_hash: [u8; 32] = headers[1].prev_hash().into();
+        if headers[1].height() != expected_height || expected_prev_hash != prev_hash {
+            return Err(
+                SoftConfirmationVerificationError::DaBlockHeadersNotInOrder {
+                    expected_height,
+                    expected_prev_hash,
+                    height: headers[1].height(),
+                    prev_hash,
+                },
+            );
+        }
+    }
 
-                    assert_eq!(
-                        da_block_headers[index_headers - 1].hash(),
-                        da_block_headers[index_headers].prev_hash(),
-                        "DA block headers must be in order"
-                    );
+    // soft confirmations may go back and forth between the DA blocks of the commitment in the
+    // specs allowing it, but the first one must be on the first DA block header.
+    verify_da_slot_height_monotonicity(
+        soft_confirmations,
+        sequencer_commitment.l2_start_block_number,
+        forks,
+        interleaved_specs,
+    )?;
+    // empty header lists are rejected by `check_commitment_input_lengths`
+    let assigned_da_block_headers = assign_da_block_headers(soft_confirmations, da_block_headers)?;
+    if !std::ptr::eq(assigned_da_block_headers[0], &da_block_headers[0]) {
+        return Err(SoftConfirmationVerificationError::DaSlotHashMismatch);
+    }
+    for (soft_confirmation, header) in soft_confirmations.iter().zip(&assigned_da_block_headers) {
+        if soft_confirmation.da_slot_height() != header.height() {
+            return Err(SoftConfirmationVerificationError::DaSlotHeightMismatch);
+        }
+    }
 
-                    current_da_height += 1;
+    // every DA header must be referenced, the chained headers are all distinct
+    let referenced_da_slot_hashes = soft_confirmations
+        .iter()
+        .map(|soft_confirmation| soft_confirmation.da_slot_hash())
+        .collect::<std::collections::HashSet<_>>();
+    if referenced_da_slot_hashes.len() != da_block_headers.len() {
+        return Err(SoftConfirmationVerificationError::UnusedDaBlockHeaders);
+    }
 
-                    // if the next one is not matching, then the state transition is invalid.
-                    assert_eq!(
-                        soft_confirmations[index_soft_confirmation].da_slot_hash(),
-                        da_block_headers[index_headers].hash().into(),
-                        "Soft confirmation DA slot hash must match DA block header hash"
-                    );
+    // now verify the claimed merkle root of soft confirmation hashes
+    if compute_commitment_merkle_root(&soft_confirmation_hashes)
+        != Some(sequencer_commitment.merkle_root)
+    {
+        return Err(SoftConfirmationVerificationError::InvalidMerkleRoot);
+    }
 
-                    assert_eq!(
-                        soft_confirmations[index_soft_confirmation].da_slot_height(),
-                        da_block_headers[index_headers].height(),
-                        "Soft confirmation DA slot height must match DA block header height"
-                    );
+    Ok(*soft_confirmation_hashes
+        .last()
+        .expect("soft confirmations are not empty"))
+}
 
-                    assert_eq!(
-                        soft_confirmations[index_soft_confirmation].prev_hash(),
-                        previous_batch_hash,
-                        "Soft confirmation previous hash must match the hash of the block before"
-                    );
+/// Serves the witnesses of `soft_confirmations` by commitment index from `witnesses`.
+/// Panics if there is not exactly one witness per soft confirmation.
+fn eager_witnesses<W>(
+    witnesses: std::collections::VecDeque<Vec<W>>,
+    soft_confirmations: &std::collections::VecDeque<Vec<SignedSoftConfirmationBatch>>,
+) -> impl FnMut(usize, usize) -> W {
+    assert!(
+        witnesses.len() == soft_confirmations.len()
+            && witnesses
+                .iter()
+                .zip(soft_confirmations)
+                .all(|(witnesses, soft_confirmations)| witnesses.len() == soft_confirmations.len()),
+        "Number of witnesses and soft confirmations must match"
+    );
+
+    let mut witnesses = witnesses
+        .into_iter()
+        .map(Vec::into_iter)
+        .collect::<Vec<_>>();
+    move |commitment_index, _| {
+        witnesses[commitment_index]
+            .next()
+            .expect("Witnesses are served in order")
+    }
+}
 
-                    previous_batch_hash = soft_confirmations[index_soft_confirmation].hash();
-                    index_soft_confirmation += 1;
-                }
-            }
+/// Applies `commitments` in order with `apply`, which returns the last L2 height of the commitment.
+/// `deadline_passed` is checked before each commitment and no further commitment is applied once
+/// it returns true. Returns the L2 height applied up to, `None` if no commitment was applied.
+fn apply_commitments_until<T>(
+    commitments: impl IntoIterator<Item = T>,
+    mut deadline_passed: impl FnMut() -> bool,
+    mut apply: impl FnMut(T) -> u64,
+) -> Option<u64> {
+    let mut applied_up_to_height = None;
+    for commitment in commitments {
+        if deadline_passed() {
+            break;
+        }
+        applied_up_to_height = Some(apply(commitment));
+    }
+    applied_up_to_height
+}
 
-            // final da header was checked against
-            assert_eq!(
-                index_headers,
-                da_block_headers.len() - 1,
-                "All DA headers must be checked"
-            );
+/// Native-only options of
+/// [`StfBlueprint::apply_soft_confirmations_from_sequencer_commitments_with_options`].
+/// The default applies every sequencer commitment, executing each soft confirmation with the
+/// witness given for it.
+#[cfg(feature = "native")]
+pub struct ApplyCommitmentsOptions<'a, R, W> {
+    /// Soft confirmations found in the cache are not executed again, their recorded state root
+    /// and state diff are used instead. Newly applied soft confirmations are added to the cache.
+    /// Meant to quickly resync ranges that were already applied.
+    pub applied_cache: Option<&'a mut HashMap<[u8; 32], AppliedSoftConfirmation<R>>>,
+    /// Once passed, no further sequencer commitment is applied. Only checked between
+    /// commitments, a commitment is never applied partially.
+    pub deadline: Option<Instant>,
+    /// Requests the witness of each soft confirmation with the index of its commitment and its
+    /// index within the commitment, right before it is applied, instead of taking it from the
+    /// given witnesses. Lets the witnesses be loaded on demand.
+    pub witness_for: Option<WitnessFor<'a, W>>,
+    /// Called with the L2 height, state root and state diff after each soft confirmation, so
+    /// intermediate state roots can be persisted as they are applied.
+    pub on_applied: Option<OnApplied<'a, R>>,
+}
 
-            // now verify the claimed merkle root of soft confirmation hashes
-            let mut soft_confirmation_hashes = vec![];
+/// Loads the witness of a soft confirmation, see [`ApplyCommitmentsOptions::witness_for`].
+#[cfg(feature = "native")]
+pub type WitnessFor<'a, W> = Box<dyn FnMut(usize, usize) -> W + 'a>;
+
+/// Observes each applied soft confirmation, see [`ApplyCommitmentsOptions::on_applied`].
+#[cfg(feature = "native")]
+pub type OnApplied<'a, R> = Box<dyn FnMut(u64, &R, &StateDiff) + 'a>;
+
+#[cfg(feature = "native")]
+impl<R, W> Default for ApplyCommitmentsOptions<'_, R, W> {
+    fn default() -> Self {
+        Self {
+            applied_cache: None,
+            deadline: None,
+            witness_for: None,
+            on_applied: None,
+        }
+    }
+}
 
-            for soft_confirmation in soft_confirmations.iter() {
-                // given hashes will be checked inside apply_soft_confirmation.
-                // so use the claimed hash for now.
-                soft_confirmation_hashes.push(soft_confirmation.hash());
-            }
+/// A soft confirmation recorded in the cache of applied soft confirmations.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedSoftConfirmation<R> {
+    /// State root the soft confirmation was applied on
+    pub pre_state_root: R,
+    /// State root after the soft confirmation
+    pub state_root: R,
+    /// State diff of the soft confirmation
+    pub state_diff: StateDiff,
+}
 
-            let calculated_root =
-                MerkleTree::<Sha256>::from_leaves(soft_confirmation_hashes.as_slice()).root();
+/// Error returned when the cache of applied soft confirmations disagrees with the state being applied.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AppliedCacheError {
+    /// The soft confirmation was cached on top of another state root than the current one.
+    #[error("Soft confirmation {} was cached on top of a different state root than the current one", hex::encode(.0))]
+    StateRootMismatch([u8; 32]),
+}
 
-            assert_eq!(
-                calculated_root,
-                Some(sequencer_commitment.merkle_root),
-                "Invalid merkle root"
-            );
+/// Returns the state root and state diff of the soft confirmation with `hash`, taking them from
+/// `applied_cache` if it was applied before and running `apply` otherwise.
+/// A cached soft confirmation must have been applied on `current_state_root`.
+#[cfg(feature = "native")]
+fn apply_or_reuse_soft_confirmation<R: Clone + Eq>(
+    applied_cache: Option<&mut HashMap<[u8; 32], AppliedSoftConfirmation<R>>>,
+    hash: [u8; 32],
+    current_state_root: &R,
+    apply: impl FnOnce() -> (R, StateDiff),
+) -> Result<(R, StateDiff), AppliedCacheError> {
+    let Some(applied_cache) = applied_cache else {
+        return Ok(apply());
+    };
+
+    if let Some(applied) = applied_cache.get(&hash) {
+        if applied.pre_state_root != *current_state_root {
+            return Err(AppliedCacheError::StateRootMismatch(hash));
+        }
+        return Ok((applied.state_root.clone(), applied.state_diff.clone()));
+    }
 
-            let mut da_block_headers_iter = da_block_headers.into_iter().peekable();
-            let mut da_block_header = da_block_headers_iter.next().unwrap();
+    let (state_root, state_diff) = apply();
+    applied_cache.insert(
+        hash,
+        AppliedSoftConfirmation {
+            pre_state_root: current_state_root.clone(),
+            state_root: state_root.clone(),
+            state_diff: state_diff.clone(),
+        },
+    );
+    Ok((state_root, state_diff))
+}
 
-            let mut l2_height = sequencer_commitment.l2_start_block_number;
-            let mut current_spec = fork_from_block_number(&forks, l2_height);
-            let mut fork_manager = ForkManager::new(l2_height, current_spec, forks.clone());
+/// Computes the state update from `state_accesses` and commits it to `storage`, together with
+/// the accessory writes `finalize` returns for the new state root.
+/// Returns the new state root and the state diff.
+fn commit_state_update<S: Storage>(
+    storage: &S,
+    state_accesses: OrderedReadsAndWrites,
+    witness: &mut S::Witness,
+    finalize: impl FnOnce(&S::Root) -> OrderedReadsAndWrites,
+) -> Result<(S::Root, StateDiff), StorageError> {
+    let (root_hash, state_update, state_diff) = storage
+        .compute_state_update(state_accesses, witness)
+        .map_err(StorageError::StateUpdate)?;
+
+    let accessory_log = finalize(&root_hash);
+    storage.commit(&state_update, &accessory_log);
+
+    Ok((root_hash, state_diff))
+}
 
-            // now that we verified the claimed root, we can apply the soft confirmations
-            // should panic if the number of witnesses and soft confirmations don't match
-            for (mut soft_confirmation, witness) in soft_confirmations.into_iter().zip_eq(witnesses)
-            {
-                if soft_confirmation.da_slot_height() != da_block_header.height() {
-                    da_block_header = da_block_headers_iter.next().unwrap();
-                }
+/// Computes the merkle root over the hashes of the soft confirmations in a sequencer commitment.
+/// Returns `None` if `soft_confirmation_hashes` is empty.
+/// Used both by the sequencer to build commitments and by verifiers to check them.
+pub fn compute_commitment_merkle_root(soft_confirmation_hashes: &[[u8; 32]]) -> Option<[u8; 32]> {
+    MerkleTree::<Sha256>::from_leaves(soft_confirmation_hashes).root()
+}
 
-                let result = self.apply_soft_confirmation(
-                    current_spec,
-                    sequencer_public_key,
-                    &current_state_root,
-                    pre_state.clone(),
-                    witness,
-                    &da_block_header,
-                    validity_condition,
-                    &mut soft_confirmation,
-                );
+/// Appends `soft_confirmation_hashes` to a running `merkle_tree` and returns the root over all
+/// the leaves appended so far, the same root [`compute_commitment_merkle_root`] computes for them.
+/// Lets a prover that processes commitments one at a time keep the tree instead of rebuilding it.
+pub fn append_commitment_merkle_leaves(
+    merkle_tree: &mut MerkleTree<Sha256>,
+    soft_confirmation_hashes: &[[u8; 32]],
+) -> Option<[u8; 32]> {
+    merkle_tree.append(&mut soft_confirmation_hashes.to_vec());
+    merkle_tree.commit();
+    merkle_tree.root()
+}
 
-                current_state_root = result.state_root;
-                state_diff.extend(result.state_diff);
+/// State root recorded after applying the soft confirmation at an L2 height, together with the
+/// storage version it was committed at, see [`StfBlueprint::revert_to_l2_height`].
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedState<R> {
+    /// State root after applying the soft confirmation
+    pub state_root: R,
+    /// Storage version the state root was committed at
+    pub version: u64,
+}
 
-                // Notify fork manager about the block so that the next spec / fork
-                // is transitioned into if criteria is met.
-                if let Err(e) = fork_manager.register_block(l2_height) {
-                    panic!("Fork transition failed {}", e);
-                }
-                l2_height += 1;
+/// Drops the state roots recorded above `target_height` and returns the one recorded at it.
+/// Leaves `recorded_state_roots` untouched if no root is recorded at `target_height`.
+#[cfg(feature = "native")]
+fn truncate_recorded_state_roots<R: Clone>(
+    recorded_state_roots: &mut BTreeMap<u64, R>,
+    target_height: u64,
+) -> anyhow::Result<R> {
+    let Some(state_root) = recorded_state_roots.get(&target_height).cloned() else {
+        anyhow::bail!("No state root recorded at L2 height {}", target_height);
+    };
+    recorded_state_roots.split_off(&(target_height + 1));
+    Ok(state_root)
+}
 
-                // Update current spec for the next iteration
-                current_spec = fork_manager.active_fork();
-            }
-            assert_eq!(sequencer_commitment.l2_end_block_number, l2_height - 1);
-        }
+/// Checks that `sequencer_public_key` is the `pinned` one, if any key is pinned.
+fn check_pinned_sequencer_public_key(
+    pinned: Option<&[u8]>,
+    sequencer_public_key: &[u8],
+) -> Result<(), SequencerPublicKeyMismatch> {
+    match pinned {
+        Some(pinned) if pinned != sequencer_public_key => Err(SequencerPublicKeyMismatch {
+            expected: pinned.to_vec(),
+            got: sequencer_public_key.to_vec(),
+        }),
+        _ => Ok(()),
+    }
+}
 
-        (current_state_root, state_diff)
+/// Same as [`verify_soft_confirmation_signature`], but succeeds without checking anything
+/// if `verify_signatures` is false.
+fn verify_soft_confirmation_signature_unless_trusted<C: Context>(
+    unsigned_soft_confirmation_raw: &[u8],
+    signature: &[u8],
+    sequencer_public_key: &[u8],
+    verify_signatures: bool,
+) -> Result<(), anyhow::Error> {
+    if !verify_signatures {
+        return Ok(());
     }
+    verify_soft_confirmation_signature::<C>(
+        unsigned_soft_confirmation_raw,
+        signature,
+        sequencer_public_key,
+    )
+}
+
+/// Signs an unsigned soft confirmation with the sequencer key and wraps it into a
+/// [`SignedSoftConfirmationBatch`], hashing it the same way `end_soft_confirmation` checks it.
+#[cfg(feature = "native")]
+pub fn sign_soft_confirmation<C: Context>(
+    unsigned: UnsignedSoftConfirmationBatch,
+    prev_hash: [u8; 32],
+    secret_key: &C::PrivateKey,
+    sequencer_public_key: &[u8],
+) -> SignedSoftConfirmationBatch {
+    use sov_modules_api::PrivateKey;
+
+    let unsigned_raw = borsh::to_vec(&unsigned).unwrap();
+    let hash = <C as Spec>::Hasher::digest(&unsigned_raw).into();
+    let signature = borsh::to_vec(&secret_key.sign(&unsigned_raw)).unwrap();
+
+    SignedSoftConfirmationBatch::new(
+        hash,
+        prev_hash,
+        unsigned.da_slot_height(),
+        unsigned.da_slot_hash(),
+        unsigned.da_slot_txs_commitment(),
+        unsigned.l1_fee_rate(),
+        unsigned.txs(),
+        unsigned.deposit_data(),
+        signature,
+        sequencer_public_key.to_vec(),
+        unsigned.timestamp(),
+    )
 }
 
 fn verify_soft_confirmation_signature<C: Context>(
-    unsigned_soft_confirmation: UnsignedSoftConfirmationBatch,
+    unsigned_soft_confirmation_raw: &[u8],
     signature: &[u8],
     sequencer_public_key: &[u8],
 ) -> Result<(), anyhow::Error> {
-    let message = borsh::to_vec(&unsigned_soft_confirmation).unwrap();
-
     let signature = C::Signature::try_from(signature)?;
 
-    // TODO: if verify function is modified to take the claimed hash in signed soft confirmation
-    // we wouldn't need to hash the thing twice
     signature.verify(
         &C::PublicKey::try_from(sequencer_public_key)?,
-        message.as_slice(),
+        unsigned_soft_confirmation_raw,
     )?;
 
     Ok(())
 }
+
+/// Extracts sequencer commitments from DA blobs.
+/// Ignores broken DaData and zk proofs. Also ignores ForcedTransaction's (will be implemented in the future).
+/// On native, every ignored blob is recorded in `rejected` if given, which does not change the result.
+pub fn extract_sequencer_commitments<B: BlobReaderTrait>(
+    da_data: &[B],
+    sequencer_da_public_key: &[u8],
+    #[cfg(feature = "native")] mut rejected: Option<&mut Vec<RejectedBlob<B::Address>>>,
+) -> Vec<SequencerCommitment> {
+    let mut sequencer_commitments: Vec<SequencerCommitment> = vec![];
+    for blob in da_data {
+        // TODO: get sequencer da pub key
+        let reason = if blob.sender().as_ref() == sequencer_da_public_key {
+            match DaData::try_from_slice(blob.verified_data()) {
+                Ok(DaData::SequencerCommitment(commitment)) => {
+                    sequencer_commitments.push(commitment);
+                    continue;
+                }
+                Ok(_) => BlobRejectionReason::NotSequencerCommitment,
+                Err(e) => BlobRejectionReason::Malformed(e.to_string()),
+            }
+        } else {
+            BlobRejectionReason::NotFromSequencer
+        };
+
+        #[cfg(feature = "native")]
+        if let Some(rejected) = rejected.as_mut() {
+            rejected.push(RejectedBlob {
+                sender: blob.sender(),
+                reason,
+            });
+        }
+        #[cfg(not(feature = "native"))]
+        let _ = reason;
+    }
+    sequencer_commitments
+}
+
+/// Checks that the DA blobs sent by `sequencer_da_public_key` add up to at most `max_bytes` bytes.
+/// Blobs of other senders are not counted, so they can't push the sequencer over the limit.
+/// Returns the index of the first blob that takes the total above the limit.
+pub fn check_da_data_size<B: BlobReaderTrait>(
+    da_data: &[B],
+    sequencer_da_public_key: &[u8],
+    max_bytes: usize,
+) -> Result<(), usize> {
+    let mut total_bytes = 0usize;
+    for (index, blob) in da_data.iter().enumerate() {
+        if blob.sender().as_ref() != sequencer_da_public_key {
+            continue;
+        }
+        total_bytes = total_bytes.saturating_add(blob.total_len());
+        if total_bytes > max_bytes {
+            return Err(index);
+        }
+    }
+    Ok(())
+}
+
+/// Sorts sequencer commitments by L2 start height, then L2 end height, then merkle root.
+/// `SequencerCommitment`'s `Ord` only compares the L2 start height, so commitments with
+/// equal ranges would otherwise end up in an unspecified order.
+fn sort_sequencer_commitments(sequencer_commitments: &mut [SequencerCommitment]) {
+    sequencer_commitments.sort_by(|a, b| {
+        a.l2_start_block_number
+            .cmp(&b.l2_start_block_number)
+            .then(a.l2_end_block_number.cmp(&b.l2_end_block_number))
+            .then(a.merkle_root.cmp(&b.merkle_root))
+    });
+}
+
+/// Finds the DA block header of each soft confirmation by its DA slot hash, so the assignment
+/// doesn't depend on soft confirmations referencing the headers in order.
+/// Chaining of the headers themselves is checked by [`verify_commitment_structure`],
+/// which accepts soft confirmations interleaved across the DA blocks of a commitment.
+/// Whether a spec allows that is checked by [`verify_da_slot_height_monotonicity`].
+fn assign_da_block_headers<'a, H: BlockHeaderTrait>(
+    soft_confirmations: &[SignedSoftConfirmationBatch],
+    da_block_headers: &'a [H],
+) -> Result<Vec<&'a H>, SequencerCommitmentError> {
+    let headers_by_hash: HashMap<[u8; 32], &H> = da_block_headers
+        .iter()
+        .map(|header| (header.hash().into(), header))
+        .collect();
+
+    soft_confirmations
+        .iter()
+        .map(|soft_confirmation| {
+            headers_by_hash
+                .get(&soft_confirmation.da_slot_hash())
+                .copied()
+                .ok_or(SequencerCommitmentError::MissingDaBlockHeader(
+                    soft_confirmation.da_slot_height(),
+                ))
+        })
+        .collect()
+}
+
+/// Counts the soft confirmations assigned to each DA block header, matched the same way
+/// the commitment is applied. Returns `(da_height, count)` in the order of the headers,
+/// headers without soft confirmations have a count of zero. Doesn't touch any state.
+pub fn confirmations_per_da_header<H: BlockHeaderTrait>(
+    soft_confirmations: &[SignedSoftConfirmationBatch],
+    da_block_headers: &[H],
+) -> Result<Vec<(u64, usize)>, SequencerCommitmentError> {
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for header in assign_da_block_headers(soft_confirmations, da_block_headers)? {
+        *counts.entry(header.height()).or_default() += 1;
+    }
+
+    Ok(da_block_headers
+        .iter()
+        .map(|header| {
+            let height = header.height();
+            (height, counts.get(&height).copied().unwrap_or_default())
+        })
+        .collect())
+}
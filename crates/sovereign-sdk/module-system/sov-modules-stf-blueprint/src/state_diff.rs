@@ -0,0 +1,35 @@
+use std::io::{Error, ErrorKind, Write};
+
+use sov_rollup_interface::da::{
+    BLOB_COMPRESSION_BUFFER_SIZE, BLOB_COMPRESSION_LG_WINDOW_SIZE, BLOB_COMPRESSION_QUALITY,
+};
+use sov_rollup_interface::zk::CumulativeStateDiff;
+
+/// Borsh encodes `state_diff` and compresses it with brotli, the way blobs are compressed
+/// before being posted to DA. Shrinks the DA footprint of the state diff.
+pub fn compress_state_diff(state_diff: &CumulativeStateDiff) -> Vec<u8> {
+    use brotli::CompressorWriter;
+
+    let serialized = borsh::to_vec(state_diff).expect("State diff serialization can't fail");
+    let mut writer = CompressorWriter::new(
+        Vec::new(),
+        BLOB_COMPRESSION_BUFFER_SIZE,
+        BLOB_COMPRESSION_QUALITY,
+        BLOB_COMPRESSION_LG_WINDOW_SIZE,
+    );
+    writer.write_all(&serialized).unwrap();
+    writer.into_inner()
+}
+
+/// Restores a state diff compressed with [`compress_state_diff`].
+/// Fails if `compressed` is not a brotli compressed, borsh encoded state diff.
+pub fn decompress_state_diff(compressed: &[u8]) -> std::io::Result<CumulativeStateDiff> {
+    use brotli::DecompressorWriter;
+
+    let mut writer = DecompressorWriter::new(Vec::new(), BLOB_COMPRESSION_BUFFER_SIZE);
+    writer.write_all(compressed)?;
+    let serialized = writer
+        .into_inner()
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "Truncated brotli stream"))?;
+    borsh::from_slice(&serialized).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+}
@@ -1,8 +1,11 @@
 use std::marker::PhantomData;
 
+use citrea_primitives::forks::{INTERLEAVED_DA_SLOT_SPECS, SOFT_CONFIRMATION_TX_LIMITS};
+use citrea_primitives::MAX_DA_BYTES_PER_COMMITMENT;
 use sov_modules_api::hooks::{ApplySoftConfirmationError, HookSoftConfirmationInfo};
 use sov_modules_api::{
-    native_debug, native_error, Context, DaSpec, DispatchCall, StateCheckpoint, WorkingSet,
+    native_debug, native_error, Context, DaSpec, DispatchCall, PublicKey, Spec, StateCheckpoint,
+    WorkingSet,
 };
 use sov_rollup_interface::soft_confirmation::SignedSoftConfirmationBatch;
 use sov_rollup_interface::spec::SpecId;
@@ -14,7 +17,10 @@ use sov_zk_cycle_macros::cycle_tracker;
 use tracing::instrument;
 
 use crate::tx_verifier::{verify_txs_stateless, TransactionAndRawHash};
-use crate::{RawTx, Runtime, RuntimeTxHook, SlashingReason, TxEffect};
+use crate::{
+    check_pinned_sequencer_public_key, verify_soft_confirmation_tx_limit, RawTx, Runtime,
+    RuntimeTxHook, SequencerOutcome, SequencerPublicKeyMismatch, SlashingReason, TxCost, TxEffect,
+};
 
 /// An implementation of the
 /// [`StateTransitionFunction`](sov_rollup_interface::stf::StateTransitionFunction)
@@ -23,12 +29,23 @@ pub struct StfBlueprint<C: Context, Da: DaSpec, Vm, RT: Runtime<C, Da>> {
     /// State storage used by the rollup.
     /// The runtime includes all the modules that the rollup supports.
     pub(crate) runtime: RT,
+    /// Sequencer public key every soft confirmation must be verified against, if pinned.
+    sequencer_public_key: Option<Vec<u8>>,
+    /// Specs in which soft confirmations may go back to an earlier DA block of their commitment.
+    pub(crate) interleaved_da_slot_specs: &'static [SpecId],
+    /// Maximum number of transactions a soft confirmation may contain in a spec.
+    soft_confirmation_tx_limits: &'static [(SpecId, usize)],
+    /// Maximum total size of the sequencer's DA blobs sequencer commitments are extracted from.
+    pub(crate) max_da_bytes_per_commitment: usize,
     phantom_context: PhantomData<C>,
     phantom_vm: PhantomData<Vm>,
     phantom_da: PhantomData<Da>,
 }
 
-type ApplySoftConfirmationResult = Result<BatchReceipt<(), TxEffect>, ApplySoftConfirmationError>;
+type ApplySoftConfirmationResult<C> = Result<
+    BatchReceipt<SequencerOutcome<<C as Spec>::Address>, TxEffect>,
+    ApplySoftConfirmationError,
+>;
 
 impl<C, Vm, Da, RT> Default for StfBlueprint<C, Da, Vm, RT>
 where
@@ -51,25 +68,106 @@ where
     pub fn new() -> Self {
         Self {
             runtime: RT::default(),
+            sequencer_public_key: None,
+            interleaved_da_slot_specs: INTERLEAVED_DA_SLOT_SPECS,
+            soft_confirmation_tx_limits: SOFT_CONFIRMATION_TX_LIMITS,
+            max_da_bytes_per_commitment: MAX_DA_BYTES_PER_COMMITMENT,
             phantom_context: PhantomData,
             phantom_vm: PhantomData,
             phantom_da: PhantomData,
         }
     }
 
+    /// [`StfBlueprint`] constructor pinning the sequencer public key.
+    /// Soft confirmations are only applied if the caller passes this same key.
+    pub fn with_sequencer_public_key(sequencer_public_key: Vec<u8>) -> Self {
+        Self {
+            sequencer_public_key: Some(sequencer_public_key),
+            ..Self::new()
+        }
+    }
+
+    /// Lets soft confirmations go back to an earlier DA block of their commitment in `specs`
+    /// instead of [`INTERLEAVED_DA_SLOT_SPECS`].
+    pub fn with_interleaved_da_slot_specs(mut self, specs: &'static [SpecId]) -> Self {
+        self.interleaved_da_slot_specs = specs;
+        self
+    }
+
+    /// Limits the number of transactions of soft confirmations per `tx_limits`
+    /// instead of [`SOFT_CONFIRMATION_TX_LIMITS`].
+    pub fn with_soft_confirmation_tx_limits(
+        mut self,
+        tx_limits: &'static [(SpecId, usize)],
+    ) -> Self {
+        self.soft_confirmation_tx_limits = tx_limits;
+        self
+    }
+
+    /// Limits the total size of the sequencer's DA blobs to `max_bytes`
+    /// instead of [`MAX_DA_BYTES_PER_COMMITMENT`].
+    pub fn with_max_da_bytes_per_commitment(mut self, max_bytes: usize) -> Self {
+        self.max_da_bytes_per_commitment = max_bytes;
+        self
+    }
+
+    /// The pinned sequencer public key, if any.
+    pub fn sequencer_public_key(&self) -> Option<&[u8]> {
+        self.sequencer_public_key.as_deref()
+    }
+
+    /// Checks that `sequencer_public_key` is the pinned sequencer public key.
+    /// Any key is accepted if none is pinned.
+    pub fn check_sequencer_public_key(
+        &self,
+        sequencer_public_key: &[u8],
+    ) -> Result<(), SequencerPublicKeyMismatch> {
+        check_pinned_sequencer_public_key(self.sequencer_public_key(), sequencer_public_key)
+    }
+
     /// Applies sov txs to the state
     #[cfg_attr(feature = "native", instrument(level = "trace", skip_all))]
     pub fn apply_sov_txs_inner(
         &self,
         txs: Vec<Vec<u8>>,
         current_spec: SpecId,
+        da_slot_height: u64,
         mut batch_workspace: WorkingSet<C>,
-    ) -> (WorkingSet<C>, Vec<TransactionReceipt<TxEffect>>) {
-        let txs = self.verify_txs_stateless_soft(&txs);
+    ) -> (
+        WorkingSet<C>,
+        Result<Vec<TransactionReceipt<TxEffect>>, SlashingReason>,
+    ) {
+        // forks may limit the number of transactions from their activation on
+        if let Err(limit) = verify_soft_confirmation_tx_limit(
+            current_spec,
+            txs.len(),
+            self.soft_confirmation_tx_limits,
+        ) {
+            native_error!(
+                "Sequencer included {} transactions, more than the limit of {} in spec {:?}",
+                txs.len(),
+                limit,
+                current_spec
+            );
+            return (batch_workspace, Err(SlashingReason::TooManyTransactions));
+        }
 
-        let messages = self
-            .decode_txs(&txs)
-            .expect("Decoding transactions from the sequencer failed");
+        // Soft confirmations without transactions are valid, there is nothing to dispatch
+        if txs.is_empty() {
+            return (batch_workspace, Ok(vec![]));
+        }
+
+        let tx_costs = tx_costs(&txs);
+        let (txs, messages) = match self.preprocess_txs(&txs) {
+            Ok(preprocessed) => preprocessed,
+            Err(reason) => {
+                native_error!(
+                    "Sequencer included an invalid transaction and is slashed for: {}",
+                    reason
+                );
+                return (batch_workspace, Err(reason));
+            }
+        };
 
         // Sanity check after pre processing
         assert_eq!(
@@ -79,8 +177,10 @@ where
         );
         // Dispatching transactions
         let mut tx_receipts = Vec::with_capacity(txs.len());
-        for (TransactionAndRawHash { tx, raw_tx_hash }, msg) in
-            txs.into_iter().zip(messages.into_iter())
+        for ((TransactionAndRawHash { tx, raw_tx_hash }, msg), tx_cost) in txs
+            .into_iter()
+            .zip(messages.into_iter())
+            .zip(tx_costs.into_iter())
         {
             // Pre dispatch hook
             // TODO set the sequencer pubkey
@@ -88,6 +188,7 @@ where
                 height: 1,
                 sequencer: tx.pub_key().clone(),
                 current_spec,
+                da_slot_height,
             };
             let ctx = match self
                 .runtime
@@ -102,7 +203,7 @@ where
                         tx_hash: raw_tx_hash,
                         body_to_save: None,
                         events: batch_workspace.take_events(),
-                        receipt: TxEffect::Reverted,
+                        receipt: TxEffect::Reverted(tx_cost),
                     };
 
                     tx_receipts.push(receipt);
@@ -118,7 +219,7 @@ where
 
             let events = batch_workspace.take_events();
             let tx_effect = match tx_result {
-                Ok(_) => TxEffect::Successful,
+                Ok(_) => TxEffect::Successful(tx_cost),
                 Err(e) => {
                     native_error!(
                         "Tx 0x{} was reverted error: {}",
@@ -128,7 +229,7 @@ where
                     // The transaction causing invalid state transition is reverted
                     // but we don't slash and we continue processing remaining transactions.
                     batch_workspace = batch_workspace.revert().to_revertable();
-                    TxEffect::Reverted
+                    TxEffect::Reverted(tx_cost)
                 }
             };
             native_debug!("Tx {} effect: {:?}", hex::encode(raw_tx_hash), tx_effect);
@@ -150,7 +251,7 @@ where
                 .post_dispatch_tx_hook(&tx, &ctx, &mut batch_workspace)
                 .expect("inconsistent state: error in post_dispatch_tx_hook");
         }
-        (batch_workspace, tx_receipts)
+        (batch_workspace, Ok(tx_receipts))
     }
 
     /// Begins the inner processes of applying soft confirmation
@@ -204,10 +305,20 @@ where
     pub fn end_soft_confirmation_inner(
         &self,
         soft_confirmation: &mut SignedSoftConfirmationBatch,
-        tx_receipts: Vec<TransactionReceipt<TxEffect>>,
+        tx_receipts: Result<Vec<TransactionReceipt<TxEffect>>, SlashingReason>,
         mut batch_workspace: WorkingSet<C>,
-    ) -> (ApplySoftConfirmationResult, StateCheckpoint<C>) {
+    ) -> (ApplySoftConfirmationResult<C>, StateCheckpoint<C>) {
         // TODO: calculate the amount based of gas and fees
+        let (tx_receipts, outcome) = match tx_receipts {
+            Ok(tx_receipts) => (tx_receipts, SequencerOutcome::Rewarded(0)),
+            Err(reason) => {
+                let sequencer_address =
+                    C::PublicKey::try_from(soft_confirmation.sequencer_pub_key())
+                        .expect("Sequencer public key must be valid")
+                        .to_address();
+                (vec![], reason.into_outcome(sequencer_address))
+            }
+        };
 
         if let Err(e) = self
             .runtime
@@ -222,7 +333,7 @@ where
                 hash: soft_confirmation.hash(),
                 prev_hash: soft_confirmation.prev_hash(),
                 tx_receipts,
-                phantom_data: PhantomData,
+                inner: outcome,
             }),
             batch_workspace.checkpoint(),
         )
@@ -235,7 +346,7 @@ where
         soft_confirmation: &mut SignedSoftConfirmationBatch,
         pre_state_root: &<C::Storage as Storage>::Root,
         current_spec: SpecId,
-    ) -> (ApplySoftConfirmationResult, StateCheckpoint<C>) {
+    ) -> (ApplySoftConfirmationResult<C>, StateCheckpoint<C>) {
         match self.begin_soft_confirmation_inner(
             checkpoint,
             soft_confirmation,
@@ -247,6 +358,7 @@ where
                 let (batch_workspace, tx_receipts) = self.apply_sov_txs_inner(
                     soft_confirmation.txs(),
                     current_spec,
+                    soft_confirmation.da_slot_height(),
                     batch_workspace,
                 );
 
@@ -256,15 +368,26 @@ where
         }
     }
 
-    // Stateless verification of transaction, such as signature check
-    // Single malformed transaction results in sequencer slashing.
-    fn verify_txs_stateless_soft(&self, txs: &[Vec<u8>]) -> Vec<TransactionAndRawHash<C>> {
-        verify_txs_stateless(
+    // Stateless verification and decoding of the transactions of a soft confirmation.
+    // Single malformed transaction results in sequencer slashing for the returned reason.
+    #[allow(clippy::type_complexity)]
+    fn preprocess_txs(
+        &self,
+        txs: &[Vec<u8>],
+    ) -> Result<
+        (
+            Vec<TransactionAndRawHash<C>>,
+            Vec<<RT as DispatchCall>::Decodable>,
+        ),
+        SlashingReason,
+    > {
+        let txs = verify_txs_stateless(
             txs.iter()
                 .map(|tx| RawTx { data: tx.clone() })
                 .collect::<Vec<_>>(),
-        )
-        .expect("Sequencer must not include non-deserializable transaction.")
+        )?;
+        let messages = self.decode_txs(&txs)?;
+        Ok((txs, messages))
     }
 
     // Checks that runtime message can be decoded from transaction.
@@ -286,3 +409,18 @@ where
         Ok(decoded_messages)
     }
 }
+
+/// Computes the size and cumulative size of each serialized transaction.
+pub(crate) fn tx_costs(txs: &[Vec<u8>]) -> Vec<TxCost> {
+    let mut cumulative_tx_size = 0;
+    txs.iter()
+        .map(|tx| {
+            let tx_size = tx.len() as u64;
+            cumulative_tx_size += tx_size;
+            TxCost {
+                tx_size,
+                cumulative_tx_size,
+            }
+        })
+        .collect()
+}
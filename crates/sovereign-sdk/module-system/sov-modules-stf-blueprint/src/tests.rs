@@ -0,0 +1,2640 @@
+use std::collections::VecDeque;
+
+use rs_merkle::algorithms::Sha256;
+use rs_merkle::MerkleTree;
+use sov_mock_da::MockBlockHeader;
+use sov_modules_api::da::BlockHeaderTrait;
+use sov_rollup_interface::da::{CommitmentError, SequencerCommitment};
+use sov_rollup_interface::soft_confirmation::SignedSoftConfirmationBatch;
+use sov_rollup_interface::spec::SpecId;
+use sov_rollup_interface::stf::StateDiff;
+use sov_state::storage::{StorageKey, StorageProof, StorageValue};
+use sov_state::{ArrayWitness, OrderedReadsAndWrites, Storage};
+
+#[cfg(feature = "native")]
+mod runtime;
+
+use crate::stf_blueprint::tx_costs;
+use crate::{
+    assign_da_block_headers, commit_state_update, compute_commitment_merkle_root,
+    confirmations_per_da_header, eager_witnesses, sort_sequencer_commitments,
+    validate_commitment_sequentiality, validate_sequencer_commitments_range,
+    verify_commitment_structure, verify_da_slot_height_monotonicity,
+    verify_soft_confirmation_chain, verify_soft_confirmation_tx_limit,
+    verify_timestamp_monotonicity, Batch, ChainError, CommitmentRangeError, RawTx,
+    SequencerCommitmentError, SoftConfirmationVerificationError, StorageError, TxCost,
+};
+
+#[test]
+fn compute_commitment_merkle_root_edge_cases() {
+    assert_eq!(compute_commitment_merkle_root(&[]), None);
+
+    let leaf = [7u8; 32];
+    assert_eq!(compute_commitment_merkle_root(&[leaf]), Some(leaf));
+}
+
+#[test]
+fn compute_commitment_merkle_root_matches_merkle_tree() {
+    let hashes = (0..5u8).map(|i| [i; 32]).collect::<Vec<_>>();
+
+    let expected = MerkleTree::<Sha256>::from_leaves(&hashes).root();
+    assert!(expected.is_some());
+    assert_eq!(compute_commitment_merkle_root(&hashes), expected);
+}
+
+#[test]
+fn append_commitment_merkle_leaves_matches_batch_root() {
+    use crate::append_commitment_merkle_leaves;
+
+    let hashes = (0..1000u32)
+        .map(|i| {
+            let mut hash = [0; 32];
+            hash[..4].copy_from_slice(&i.to_le_bytes());
+            hash
+        })
+        .collect::<Vec<_>>();
+
+    let mut merkle_tree = MerkleTree::<Sha256>::new();
+    assert_eq!(append_commitment_merkle_leaves(&mut merkle_tree, &[]), None);
+
+    // commitments of uneven sizes, so that appends don't line up with subtrees
+    let mut appended = 0;
+    for (index, chunk_size) in [1, 2, 37, 100, 3, 257, 600].into_iter().enumerate() {
+        let end = (appended + chunk_size).min(hashes.len());
+        let root = append_commitment_merkle_leaves(&mut merkle_tree, &hashes[appended..end]);
+        appended = end;
+
+        assert_eq!(
+            root,
+            compute_commitment_merkle_root(&hashes[..appended]),
+            "root after commitment {} differs",
+            index
+        );
+    }
+
+    assert_eq!(appended, hashes.len());
+    assert_eq!(
+        merkle_tree.root(),
+        MerkleTree::<Sha256>::from_leaves(&hashes).root()
+    );
+}
+
+#[test]
+fn sort_sequencer_commitments_breaks_ties() {
+    let commitment =
+        |merkle_root: u8, l2_start_block_number, l2_end_block_number| SequencerCommitment {
+            merkle_root: [merkle_root; 32],
+            l2_start_block_number,
+            l2_end_block_number,
+        };
+
+    let mut commitments = vec![
+        commitment(2, 1, 5),
+        commitment(9, 6, 10),
+        commitment(1, 1, 5),
+        commitment(3, 1, 4),
+    ];
+    let mut reversed = commitments.iter().rev().cloned().collect::<Vec<_>>();
+
+    sort_sequencer_commitments(&mut commitments);
+    sort_sequencer_commitments(&mut reversed);
+
+    // Equal ranges are ordered by merkle root regardless of the input order
+    let expected = vec![
+        commitment(3, 1, 4),
+        commitment(1, 1, 5),
+        commitment(2, 1, 5),
+        commitment(9, 6, 10),
+    ];
+    assert_eq!(commitments, expected);
+    assert_eq!(reversed, expected);
+}
+
+fn soft_confirmation(
+    hash: u8,
+    prev_hash: [u8; 32],
+    da_block_header: &MockBlockHeader,
+) -> SignedSoftConfirmationBatch {
+    SignedSoftConfirmationBatch::new(
+        [hash; 32],
+        prev_hash,
+        da_block_header.height(),
+        da_block_header.hash().into(),
+        da_block_header.txs_commitment().into(),
+        0,
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+        0,
+    )
+}
+
+#[test]
+fn assign_da_block_headers_skips_gaps() {
+    let headers = (1..=4)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+
+    // Soft confirmations jump from DA height 1 straight to DA height 3
+    let soft_confirmations = [
+        soft_confirmation(1, [0; 32], &headers[0]),
+        soft_confirmation(2, [1; 32], &headers[2]),
+        soft_confirmation(3, [2; 32], &headers[3]),
+    ];
+    let assigned = assign_da_block_headers(&soft_confirmations, &headers).unwrap();
+    assert_eq!(
+        assigned
+            .iter()
+            .map(|header| header.height())
+            .collect::<Vec<_>>(),
+        vec![1, 3, 4]
+    );
+}
+
+#[test]
+fn assign_da_block_headers_interleaved() {
+    let headers = (1..=2)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+
+    // going back and forth between the two DA blocks
+    let soft_confirmations = [
+        soft_confirmation(1, [0; 32], &headers[0]),
+        soft_confirmation(2, [1; 32], &headers[1]),
+        soft_confirmation(3, [2; 32], &headers[0]),
+        soft_confirmation(4, [3; 32], &headers[1]),
+    ];
+    let assigned = assign_da_block_headers(&soft_confirmations, &headers).unwrap();
+    assert_eq!(assigned.len(), soft_confirmations.len());
+    for (soft_confirmation, header) in soft_confirmations.iter().zip(assigned) {
+        assert_eq!(
+            soft_confirmation.da_slot_hash(),
+            Into::<[u8; 32]>::into(header.hash())
+        );
+        assert_eq!(soft_confirmation.da_slot_height(), header.height());
+    }
+}
+
+#[test]
+fn assign_da_block_headers_missing() {
+    let headers = (1..=2)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+
+    let soft_confirmations = [
+        soft_confirmation(1, [0; 32], &headers[0]),
+        soft_confirmation(2, [1; 32], &MockBlockHeader::from_height(5)),
+    ];
+    assert_eq!(
+        assign_da_block_headers(&soft_confirmations, &headers),
+        Err(SequencerCommitmentError::MissingDaBlockHeader(5))
+    );
+}
+
+#[test]
+fn confirmations_per_da_header_counts() {
+    let headers = (1..=4)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+
+    // three soft confirmations on DA height 1, none on 2, one on 3 and two on 4
+    let soft_confirmations = [
+        soft_confirmation(1, [0; 32], &headers[0]),
+        soft_confirmation(2, [1; 32], &headers[0]),
+        soft_confirmation(3, [2; 32], &headers[0]),
+        soft_confirmation(4, [3; 32], &headers[2]),
+        soft_confirmation(5, [4; 32], &headers[3]),
+        soft_confirmation(6, [5; 32], &headers[3]),
+    ];
+    assert_eq!(
+        confirmations_per_da_header(&soft_confirmations, &headers),
+        Ok(vec![(1, 3), (2, 0), (3, 1), (4, 2)])
+    );
+
+    assert_eq!(
+        confirmations_per_da_header(&soft_confirmations, &headers[1..]),
+        Err(SequencerCommitmentError::MissingDaBlockHeader(1))
+    );
+}
+
+/// Fork table of the commitment structure tests, genesis spec from the start
+const FORKS: &[(SpecId, u64)] = &[(SpecId::Genesis, 0)];
+
+/// Two soft confirmations on DA height 1 and one on DA height 2, committed in L2 range 1..=3
+fn commitment_fixture() -> (
+    SequencerCommitment,
+    Vec<SignedSoftConfirmationBatch>,
+    Vec<MockBlockHeader>,
+) {
+    let da_block_headers = vec![
+        MockBlockHeader::from_height(1),
+        MockBlockHeader::from_height(2),
+    ];
+    let soft_confirmations = vec![
+        soft_confirmation(1, [0; 32], &da_block_headers[0]),
+        soft_confirmation(2, [1; 32], &da_block_headers[0]),
+        soft_confirmation(3, [2; 32], &da_block_headers[1]),
+    ];
+    let commitment = SequencerCommitment {
+        merkle_root: compute_commitment_merkle_root(&[[1; 32], [2; 32], [3; 32]]).unwrap(),
+        l2_start_block_number: 1,
+        l2_end_block_number: 3,
+    };
+    (commitment, soft_confirmations, da_block_headers)
+}
+
+#[test]
+fn verify_commitment_structure_valid() {
+    let (commitment, soft_confirmations, da_block_headers) = commitment_fixture();
+
+    assert_eq!(
+        verify_commitment_structure(
+            &[commitment],
+            [0; 32],
+            &VecDeque::from([soft_confirmations]),
+            &VecDeque::from([da_block_headers]),
+            FORKS,
+            &[],
+        ),
+        Ok(())
+    );
+}
+
+#[test]
+fn validate_sequencer_commitments_range_rejects_inverted_range() {
+    assert_eq!(validate_sequencer_commitments_range((0, 0), 1), Ok(()));
+    assert_eq!(validate_sequencer_commitments_range((1, 2), 3), Ok(()));
+    assert_eq!(
+        validate_sequencer_commitments_range((2, 1), 3),
+        Err(CommitmentRangeError::Inverted { start: 2, end: 1 })
+    );
+}
+
+#[test]
+fn validate_sequencer_commitments_range_rejects_out_of_bounds_end() {
+    assert_eq!(
+        validate_sequencer_commitments_range((0, 3), 3),
+        Err(CommitmentRangeError::OutOfBounds { end: 3, count: 3 })
+    );
+    assert_eq!(
+        validate_sequencer_commitments_range((0, 0), 0),
+        Err(CommitmentRangeError::OutOfBounds { end: 0, count: 0 })
+    );
+}
+
+#[test]
+fn validate_commitment_sequentiality_reports_first_break() {
+    let commitment = |l2_start_block_number, l2_end_block_number| SequencerCommitment {
+        merkle_root: [0; 32],
+        l2_start_block_number,
+        l2_end_block_number,
+    };
+
+    assert_eq!(validate_commitment_sequentiality(&[]), Ok(()));
+    assert_eq!(
+        validate_commitment_sequentiality(&[commitment(1, 5), commitment(6, 6), commitment(7, 9)]),
+        Ok(())
+    );
+
+    // gap between 6 and 8
+    assert_eq!(
+        validate_commitment_sequentiality(&[commitment(1, 5), commitment(6, 6), commitment(8, 9)]),
+        Err(2)
+    );
+
+    // second commitment overlaps the first one
+    assert_eq!(
+        validate_commitment_sequentiality(&[commitment(1, 5), commitment(4, 6), commitment(8, 9)]),
+        Err(1)
+    );
+}
+
+#[test]
+fn verify_timestamp_monotonicity_rejects_decreasing_timestamps() {
+    let with_timestamps = |timestamps: &[u64]| {
+        timestamps
+            .iter()
+            .map(|timestamp| {
+                SignedSoftConfirmationBatch::new(
+                    [0; 32],
+                    [0; 32],
+                    1,
+                    [0; 32],
+                    [0; 32],
+                    0,
+                    vec![],
+                    vec![],
+                    vec![],
+                    vec![],
+                    *timestamp,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let forks = [(SpecId::Genesis, 0)];
+
+    let increasing = with_timestamps(&[10, 10, 11, 15]);
+    assert_eq!(
+        verify_timestamp_monotonicity(&increasing, 1, None, &forks, &[SpecId::Genesis]),
+        Ok(())
+    );
+
+    let decreasing = with_timestamps(&[10, 12, 11, 15]);
+    assert_eq!(
+        verify_timestamp_monotonicity(&decreasing, 1, None, &forks, &[SpecId::Genesis]),
+        Err(2)
+    );
+    // not enforced in any spec
+    assert_eq!(
+        verify_timestamp_monotonicity(&decreasing, 1, None, &forks, &[]),
+        Ok(())
+    );
+
+    // the last timestamp of the previous commitment carries over
+    assert_eq!(
+        verify_timestamp_monotonicity(&increasing, 5, Some(10), &forks, &[SpecId::Genesis]),
+        Ok(())
+    );
+    assert_eq!(
+        verify_timestamp_monotonicity(&increasing, 5, Some(11), &forks, &[SpecId::Genesis]),
+        Err(0)
+    );
+}
+
+#[test]
+fn soft_confirmation_tx_limit_depends_on_spec() {
+    // only the fork limits the number of transactions
+    let tx_limits = [(SpecId::Fork1, 3)];
+    let num_txs = 5;
+
+    assert_eq!(
+        verify_soft_confirmation_tx_limit(SpecId::Genesis, num_txs, &tx_limits),
+        Ok(())
+    );
+    assert_eq!(
+        verify_soft_confirmation_tx_limit(SpecId::Fork1, num_txs, &tx_limits),
+        Err(3)
+    );
+    assert_eq!(
+        verify_soft_confirmation_tx_limit(SpecId::Fork1, 3, &tx_limits),
+        Ok(())
+    );
+    assert_eq!(
+        verify_soft_confirmation_tx_limit(SpecId::Fork1, num_txs, &[]),
+        Ok(())
+    );
+}
+
+#[test]
+fn forced_transactions_enabled_from_fork_height() {
+    use citrea_primitives::forks::FORCED_TRANSACTION_SPECS;
+
+    use crate::forced_transactions_enabled;
+
+    let forks = [(SpecId::Genesis, 0), (SpecId::Fork1, 10)];
+    let enabled_specs = [SpecId::Fork1];
+
+    assert!(!forced_transactions_enabled(0, &forks, &enabled_specs));
+    assert!(!forced_transactions_enabled(9, &forks, &enabled_specs));
+    assert!(forced_transactions_enabled(10, &forks, &enabled_specs));
+    assert!(forced_transactions_enabled(11, &forks, &enabled_specs));
+
+    // not enabled in any spec yet
+    assert!(!forced_transactions_enabled(
+        11,
+        &forks,
+        FORCED_TRANSACTION_SPECS
+    ));
+}
+
+#[test]
+fn verify_soft_confirmation_chain_detects_broken_links() {
+    let (_, mut soft_confirmations, da_block_headers) = commitment_fixture();
+
+    assert_eq!(
+        verify_soft_confirmation_chain(&soft_confirmations, [0; 32]),
+        Ok(())
+    );
+    assert_eq!(verify_soft_confirmation_chain(&[], [0; 32]), Ok(()));
+
+    assert_eq!(
+        verify_soft_confirmation_chain(&soft_confirmations, [1; 32]),
+        Err(ChainError::PrevHashMismatch {
+            index: 0,
+            expected: [1; 32],
+            got: [0; 32],
+        })
+    );
+
+    soft_confirmations[2] = soft_confirmation(3, [7; 32], &da_block_headers[1]);
+    assert_eq!(
+        verify_soft_confirmation_chain(&soft_confirmations, [0; 32]),
+        Err(ChainError::PrevHashMismatch {
+            index: 2,
+            expected: [2; 32],
+            got: [7; 32],
+        })
+    );
+}
+
+#[test]
+fn verify_commitment_structure_rejects_malformed_commitments() {
+    let (commitment, soft_confirmations, da_block_headers) = commitment_fixture();
+    let soft_confirmations = VecDeque::from([soft_confirmations]);
+    let da_block_headers = VecDeque::from([da_block_headers]);
+
+    let mut wrong_root = commitment.clone();
+    wrong_root.merkle_root = [9; 32];
+    assert_eq!(
+        verify_commitment_structure(
+            &[wrong_root],
+            [0; 32],
+            &soft_confirmations,
+            &da_block_headers,
+            FORKS,
+            &[],
+        ),
+        Err(SoftConfirmationVerificationError::InvalidMerkleRoot)
+    );
+
+    let mut zero_root = commitment.clone();
+    zero_root.merkle_root = [0; 32];
+    assert_eq!(
+        verify_commitment_structure(
+            &[zero_root],
+            [0; 32],
+            &soft_confirmations,
+            &da_block_headers,
+            FORKS,
+            &[],
+        ),
+        Err(SoftConfirmationVerificationError::InvalidCommitment(
+            CommitmentError::ZeroMerkleRoot
+        ))
+    );
+
+    assert_eq!(
+        verify_commitment_structure(
+            &[commitment.clone()],
+            [9; 32],
+            &soft_confirmations,
+            &da_block_headers,
+            FORKS,
+            &[],
+        ),
+        Err(SoftConfirmationVerificationError::PrevHashMismatch)
+    );
+
+    let mut wrong_range = commitment;
+    wrong_range.l2_end_block_number = 4;
+    assert_eq!(
+        verify_commitment_structure(
+            &[wrong_range],
+            [0; 32],
+            &soft_confirmations,
+            &da_block_headers,
+            FORKS,
+            &[],
+        ),
+        Err(SoftConfirmationVerificationError::L2RangeMismatch {
+            l2_start: 1,
+            l2_end: 4,
+            soft_confirmations: 3,
+        })
+    );
+}
+
+#[test]
+fn verify_commitment_structure_reports_out_of_order_da_headers() {
+    let (commitment, soft_confirmations, mut da_block_headers) = commitment_fixture();
+    // skip DA height 2, the last soft confirmation still points to it
+    da_block_headers[1] = MockBlockHeader::from_height(3);
+    let expected_prev_hash: [u8; 32] = da_block_headers[0].hash().into();
+    let prev_hash: [u8; 32] = da_block_headers[1].prev_hash().into();
+
+    let err = verify_commitment_structure(
+        &[commitment],
+        [0; 32],
+        &VecDeque::from([soft_confirmations]),
+        &VecDeque::from([da_block_headers]),
+        FORKS,
+        &[],
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        err,
+        SoftConfirmationVerificationError::DaBlockHeadersNotInOrder {
+            expected_height: 2,
+            expected_prev_hash,
+            height: 3,
+            prev_hash,
+        }
+    );
+    let message = err.to_string();
+    assert!(message.contains("expected height 2"));
+    assert!(message.contains("got height 3"));
+    assert!(message.contains(&hex::encode(expected_prev_hash)));
+    assert!(message.contains(&hex::encode(prev_hash)));
+}
+
+#[test]
+fn verify_commitment_structure_rejects_commitment_without_da_headers() {
+    let (commitment, soft_confirmations, da_block_headers) = commitment_fixture();
+    let next_commitment = SequencerCommitment {
+        merkle_root: compute_commitment_merkle_root(&[[4; 32]]).unwrap(),
+        l2_start_block_number: 4,
+        l2_end_block_number: 4,
+    };
+    let next_soft_confirmations = vec![soft_confirmation(4, [3; 32], &da_block_headers[1])];
+
+    let err = verify_commitment_structure(
+        &[commitment, next_commitment],
+        [0; 32],
+        &VecDeque::from([soft_confirmations, next_soft_confirmations]),
+        &VecDeque::from([da_block_headers, vec![]]),
+        FORKS,
+        &[],
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        err,
+        SoftConfirmationVerificationError::NoDaHeaders {
+            commitment_index: 1
+        }
+    );
+    assert!(err.to_string().contains("sequencer commitment 1"));
+}
+
+#[test]
+fn verify_commitment_structure_accepts_interleaved_da_slot_heights() {
+    let (mut commitment, mut soft_confirmations, da_block_headers) = commitment_fixture();
+    // after moving on to DA height 2, go back to DA height 1
+    soft_confirmations.push(soft_confirmation(4, [3; 32], &da_block_headers[0]));
+    commitment.l2_end_block_number = 4;
+    commitment.merkle_root =
+        compute_commitment_merkle_root(&[[1; 32], [2; 32], [3; 32], [4; 32]]).unwrap();
+    let soft_confirmations = VecDeque::from([soft_confirmations]);
+    let da_block_headers = VecDeque::from([da_block_headers]);
+
+    assert_eq!(
+        verify_commitment_structure(
+            &[commitment.clone()],
+            [0; 32],
+            &soft_confirmations,
+            &da_block_headers,
+            FORKS,
+            &[SpecId::Genesis],
+        ),
+        Ok(())
+    );
+    // outside the interleaved specs, the dry run rejects it like applying it would
+    assert_eq!(
+        verify_commitment_structure(
+            &[commitment],
+            [0; 32],
+            &soft_confirmations,
+            &da_block_headers,
+            FORKS,
+            &[],
+        ),
+        Err(
+            SoftConfirmationVerificationError::DaSlotHeightBehindHeader {
+                da_slot_height: 1,
+                header_height: 2,
+            }
+        )
+    );
+}
+
+#[test]
+fn da_slot_height_monotonicity_is_enforced_outside_interleaved_specs() {
+    let (_, mut soft_confirmations, da_block_headers) = commitment_fixture();
+    // after moving on to DA height 2, go back to DA height 1 at L2 height 4
+    soft_confirmations.push(soft_confirmation(4, [3; 32], &da_block_headers[0]));
+    let backwards = Err(
+        SoftConfirmationVerificationError::DaSlotHeightBehindHeader {
+            da_slot_height: 1,
+            header_height: 2,
+        },
+    );
+
+    let forks = [(SpecId::Genesis, 0)];
+    assert_eq!(
+        verify_da_slot_height_monotonicity(&soft_confirmations, 1, &forks, &[]),
+        backwards
+    );
+    assert_eq!(
+        verify_da_slot_height_monotonicity(&soft_confirmations, 1, &forks, &[SpecId::Genesis]),
+        Ok(())
+    );
+
+    // only the spec of the soft confirmation going back counts
+    let forks = [(SpecId::Genesis, 0), (SpecId::Fork1, 4)];
+    assert_eq!(
+        verify_da_slot_height_monotonicity(&soft_confirmations, 1, &forks, &[SpecId::Fork1]),
+        Ok(())
+    );
+    assert_eq!(
+        verify_da_slot_height_monotonicity(&soft_confirmations, 1, &forks, &[SpecId::Genesis]),
+        backwards
+    );
+}
+
+#[test]
+fn verify_commitment_structure_rejects_missing_da_header() {
+    let (mut commitment, mut soft_confirmations, da_block_headers) = commitment_fixture();
+    soft_confirmations.push(soft_confirmation(
+        4,
+        [3; 32],
+        &MockBlockHeader::from_height(3),
+    ));
+    commitment.l2_end_block_number = 4;
+    commitment.merkle_root =
+        compute_commitment_merkle_root(&[[1; 32], [2; 32], [3; 32], [4; 32]]).unwrap();
+
+    assert_eq!(
+        verify_commitment_structure(
+            &[commitment],
+            [0; 32],
+            &VecDeque::from([soft_confirmations]),
+            &VecDeque::from([da_block_headers]),
+            FORKS,
+            &[],
+        ),
+        Err(SoftConfirmationVerificationError::MissingDaBlockHeader(
+            SequencerCommitmentError::MissingDaBlockHeader(3)
+        ))
+    );
+}
+
+#[test]
+fn verify_commitment_structure_rejects_commitment_not_starting_on_first_da_header() {
+    let (commitment, mut soft_confirmations, da_block_headers) = commitment_fixture();
+    // all soft confirmations stay on DA heights 1 and 2, but the first one is on height 2,
+    // going back to height 1 is allowed in the interleaved specs
+    soft_confirmations[0] = soft_confirmation(1, [0; 32], &da_block_headers[1]);
+
+    assert_eq!(
+        verify_commitment_structure(
+            &[commitment],
+            [0; 32],
+            &VecDeque::from([soft_confirmations]),
+            &VecDeque::from([da_block_headers]),
+            FORKS,
+            &[SpecId::Genesis],
+        ),
+        Err(SoftConfirmationVerificationError::DaSlotHashMismatch)
+    );
+}
+
+/// The commitment fixture followed by a second commitment over L2 range 4..=5,
+/// with soft confirmations on DA heights 2 and 3
+fn two_commitments_fixture() -> (
+    Vec<SequencerCommitment>,
+    VecDeque<Vec<SignedSoftConfirmationBatch>>,
+    VecDeque<Vec<MockBlockHeader>>,
+) {
+    let (first_commitment, first_soft_confirmations, first_da_block_headers) = commitment_fixture();
+
+    let da_block_headers = vec![
+        MockBlockHeader::from_height(2),
+        MockBlockHeader::from_height(3),
+    ];
+    let soft_confirmations = vec![
+        soft_confirmation(4, [3; 32], &da_block_headers[0]),
+        soft_confirmation(5, [4; 32], &da_block_headers[1]),
+    ];
+    let commitment = SequencerCommitment {
+        merkle_root: compute_commitment_merkle_root(&[[4; 32], [5; 32]]).unwrap(),
+        l2_start_block_number: 4,
+        l2_end_block_number: 5,
+    };
+
+    (
+        vec![first_commitment, commitment],
+        VecDeque::from([first_soft_confirmations, soft_confirmations]),
+        VecDeque::from([first_da_block_headers, da_block_headers]),
+    )
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn verify_commitment_structure_parallel_matches_sequential() {
+    use crate::verify_commitment_structure_parallel;
+
+    let (commitments, soft_confirmations, da_block_headers) = two_commitments_fixture();
+    let verify =
+        |commitments: &[SequencerCommitment],
+         soft_confirmations: &VecDeque<Vec<SignedSoftConfirmationBatch>>| {
+            let sequential = verify_commitment_structure(
+                commitments,
+                [0; 32],
+                soft_confirmations,
+                &da_block_headers,
+                FORKS,
+                &[],
+            );
+            let parallel = verify_commitment_structure_parallel(
+                commitments,
+                [0; 32],
+                soft_confirmations,
+                &da_block_headers,
+                FORKS,
+                &[],
+            );
+            assert_eq!(sequential, parallel);
+            parallel
+        };
+
+    assert_eq!(verify(&commitments, &soft_confirmations), Ok(()));
+
+    // the second commitment does not chain from the first one
+    let mut broken_chain = soft_confirmations.clone();
+    broken_chain[1][0] = soft_confirmation(4, [9; 32], &da_block_headers[1][0]);
+    assert_eq!(
+        verify(&commitments, &broken_chain),
+        Err(SoftConfirmationVerificationError::PrevHashMismatch)
+    );
+
+    // both commitments are invalid, the first failure is reported
+    let mut wrong_roots = commitments.clone();
+    wrong_roots[0].merkle_root = [1; 32];
+    wrong_roots[1].l2_end_block_number = 6;
+    assert_eq!(
+        verify(&wrong_roots, &soft_confirmations),
+        Err(SoftConfirmationVerificationError::InvalidMerkleRoot)
+    );
+
+    let mut gap = commitments;
+    gap[1].l2_start_block_number = 5;
+    assert_eq!(
+        verify(&gap, &soft_confirmations),
+        Err(
+            SoftConfirmationVerificationError::NonSequentialCommitments {
+                expected: 4,
+                got: 5,
+            }
+        )
+    );
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn apply_or_reuse_soft_confirmation_skips_cached() {
+    use std::collections::HashMap;
+
+    use crate::{apply_or_reuse_soft_confirmation, AppliedCacheError, AppliedSoftConfirmation};
+
+    let mut applied_cache = HashMap::new();
+    let mut calls = 0;
+    let mut apply_or_reuse =
+        |applied_cache: &mut HashMap<[u8; 32], AppliedSoftConfirmation<u64>>,
+         hash: [u8; 32],
+         current_state_root: u64| {
+            apply_or_reuse_soft_confirmation(Some(applied_cache), hash, &current_state_root, || {
+                calls += 1;
+                (
+                    current_state_root + 10,
+                    vec![(
+                        hash.to_vec(),
+                        Some(current_state_root.to_le_bytes().to_vec()),
+                    )],
+                )
+            })
+        };
+    let state_diff = |hash: [u8; 32], pre_state_root: u64| {
+        vec![(hash.to_vec(), Some(pre_state_root.to_le_bytes().to_vec()))]
+    };
+
+    assert_eq!(
+        apply_or_reuse(&mut applied_cache, [1; 32], 0),
+        Ok((10, state_diff([1; 32], 0)))
+    );
+    assert_eq!(
+        apply_or_reuse(&mut applied_cache, [2; 32], 10),
+        Ok((20, state_diff([2; 32], 10)))
+    );
+    // already applied, the recorded root and diff are returned without applying again
+    assert_eq!(
+        apply_or_reuse(&mut applied_cache, [1; 32], 0),
+        Ok((10, state_diff([1; 32], 0)))
+    );
+    assert_eq!(
+        apply_or_reuse(&mut applied_cache, [2; 32], 10),
+        Ok((20, state_diff([2; 32], 10)))
+    );
+    // the cache says [2; 32] was applied on top of state root 10
+    assert_eq!(
+        apply_or_reuse(&mut applied_cache, [2; 32], 99),
+        Err(AppliedCacheError::StateRootMismatch([2; 32]))
+    );
+    assert_eq!(calls, 2);
+    assert_eq!(applied_cache.len(), 2);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn applied_cache_reuses_state_diff() {
+    use std::collections::HashMap;
+
+    use sov_mock_da::{MockBlockHeader, MockDaSpec, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        commitment_blob, genesis_storage, sequencer_key, soft_confirmation_chain, tx,
+        TestBlueprint, TestCall, TestZkvm, SEQUENCER_DA_PUBLIC_KEY,
+    };
+    use crate::ApplyCommitmentsOptions;
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_headers = (1..=2)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    let soft_confirmations = soft_confirmation_chain(
+        [0; 32],
+        &da_block_headers.iter().collect::<Vec<_>>(),
+        |index| vec![tx(&private_key, TestCall::Set(index as u64), index as u64)],
+    );
+    let da_data = vec![commitment_blob(
+        &soft_confirmations,
+        1,
+        SEQUENCER_DA_PUBLIC_KEY,
+    )];
+
+    let stf = TestBlueprint::new();
+    let apply_without_cache = StateTransitionFunction::<TestZkvm, MockDaSpec>::apply_soft_confirmations_from_sequencer_commitments;
+    let witnesses = || {
+        VecDeque::from([soft_confirmations
+            .iter()
+            .map(|_| Default::default())
+            .collect()])
+    };
+    let apply = |applied_cache: Option<&mut HashMap<_, _>>| {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+        match applied_cache {
+            Some(applied_cache) => {
+                let (state_root, state_diff, _) = stf
+                    .apply_soft_confirmations_from_sequencer_commitments_with_options(
+                        &public_key,
+                        &SEQUENCER_DA_PUBLIC_KEY,
+                        &genesis_root,
+                        [0; 32],
+                        storage,
+                        da_data.clone(),
+                        (0, 0),
+                        witnesses(),
+                        VecDeque::from([da_block_headers.clone()]),
+                        &MockValidityCond::default(),
+                        VecDeque::from([soft_confirmations.clone()]),
+                        vec![(SpecId::Genesis, 0)],
+                        ApplyCommitmentsOptions {
+                            applied_cache: Some(applied_cache),
+                            ..Default::default()
+                        },
+                    );
+                (state_root, state_diff)
+            }
+            None => apply_without_cache(
+                &stf,
+                &public_key,
+                &SEQUENCER_DA_PUBLIC_KEY,
+                &genesis_root,
+                [0; 32],
+                storage,
+                da_data.clone(),
+                (0, 0),
+                witnesses(),
+                VecDeque::from([da_block_headers.clone()]),
+                &MockValidityCond::default(),
+                VecDeque::from([soft_confirmations.clone()]),
+                vec![(SpecId::Genesis, 0)],
+            ),
+        }
+    };
+
+    let (state_root, state_diff) = apply(None);
+    assert!(!state_diff.is_empty());
+
+    let mut applied_cache = HashMap::new();
+    assert_eq!(
+        apply(Some(&mut applied_cache)),
+        (state_root, state_diff.clone())
+    );
+    assert_eq!(applied_cache.len(), 2);
+    // every soft confirmation is taken from the cache now, with the same state diff
+    assert_eq!(apply(Some(&mut applied_cache)), (state_root, state_diff));
+}
+
+/// Applies a commitment of the sequencer alongside an identical one sent by another DA key.
+#[cfg(feature = "native")]
+#[test]
+fn lookalike_commitment_is_ignored() {
+    use sov_mock_da::{MockBlockHeader, MockDaSpec, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        commitment_blob, genesis_storage, sequencer_key, soft_confirmation_chain, tx,
+        TestBlueprint, TestCall, TestZkvm, SEQUENCER_DA_PUBLIC_KEY,
+    };
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_headers = (1..=2)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    let soft_confirmations = soft_confirmation_chain(
+        [0; 32],
+        &da_block_headers.iter().collect::<Vec<_>>(),
+        |index| vec![tx(&private_key, TestCall::Set(index as u64), index as u64)],
+    );
+    let da_data = vec![
+        commitment_blob(&soft_confirmations, 1, SEQUENCER_DA_PUBLIC_KEY),
+        commitment_blob(&soft_confirmations, 1, [8; 32]),
+    ];
+
+    let stf = TestBlueprint::new();
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (state_root, _) =
+        StateTransitionFunction::<TestZkvm, MockDaSpec>::apply_soft_confirmations_from_sequencer_commitments(
+            &stf,
+            &public_key,
+            &SEQUENCER_DA_PUBLIC_KEY,
+            &genesis_root,
+            [0; 32],
+            storage,
+            da_data,
+            (0, 0),
+            VecDeque::from([soft_confirmations
+                .iter()
+                .map(|_| Default::default())
+                .collect()]),
+            VecDeque::from([da_block_headers]),
+            &MockValidityCond::default(),
+            VecDeque::from([soft_confirmations]),
+            vec![(SpecId::Genesis, 0)],
+        );
+    assert_ne!(state_root, genesis_root);
+}
+
+/// Applies a commitment whose soft confirmations go back and forth between two DA blocks.
+#[cfg(feature = "native")]
+fn apply_commitment_with_interleaved_da_slot_heights(interleaved_da_slot_specs: &'static [SpecId]) {
+    use sov_mock_da::{MockBlockHeader, MockDaSpec, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        commitment_blob, genesis_storage, sequencer_key, soft_confirmation_chain, tx, Recorded,
+        TestBlueprint, TestCall, TestZkvm, SEQUENCER_DA_PUBLIC_KEY,
+    };
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_headers = (1..=2)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    // go back and forth between DA heights 1 and 2
+    let soft_confirmation_headers = [
+        &da_block_headers[0],
+        &da_block_headers[1],
+        &da_block_headers[0],
+        &da_block_headers[1],
+    ];
+    let soft_confirmations =
+        soft_confirmation_chain([0; 32], &soft_confirmation_headers, |index| {
+            vec![tx(&private_key, TestCall::Set(index as u64), index as u64)]
+        });
+    let stf = TestBlueprint::new().with_interleaved_da_slot_specs(interleaved_da_slot_specs);
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (state_root, _) =
+        StateTransitionFunction::<TestZkvm, MockDaSpec>::apply_soft_confirmations_from_sequencer_commitments(
+            &stf,
+            &public_key,
+            &SEQUENCER_DA_PUBLIC_KEY,
+            &genesis_root,
+            [0; 32],
+            storage.clone(),
+            vec![commitment_blob(&soft_confirmations, 1, SEQUENCER_DA_PUBLIC_KEY)],
+            (0, 0),
+            VecDeque::from([soft_confirmations
+                .iter()
+                .map(|_| Default::default())
+                .collect()]),
+            VecDeque::from([da_block_headers.clone()]),
+            &MockValidityCond::default(),
+            VecDeque::from([soft_confirmations.clone()]),
+            vec![(SpecId::Genesis, 0)],
+        );
+    assert_eq!(Recorded::BeginHookCalls.read(&storage), Some(4));
+    assert_eq!(Recorded::TxDaSlotHeight.read(&storage), Some(2));
+    assert_eq!(Recorded::Value.read(&storage), Some(3));
+
+    // the same soft confirmations applied one by one on their own DA block headers
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let mut expected_state_root = genesis_root;
+    for (soft_confirmation, da_block_header) in
+        soft_confirmations.iter().zip(soft_confirmation_headers)
+    {
+        expected_state_root = stf
+            .apply_soft_confirmation(
+                SpecId::Genesis,
+                &public_key,
+                &expected_state_root,
+                storage.clone(),
+                Default::default(),
+                da_block_header,
+                &MockValidityCond::default(),
+                &mut soft_confirmation.clone(),
+            )
+            .state_root;
+    }
+    assert_eq!(state_root, expected_state_root);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn interleaved_da_slot_heights_are_applied_in_interleaved_specs() {
+    apply_commitment_with_interleaved_da_slot_heights(&[SpecId::Genesis]);
+}
+
+#[cfg(feature = "native")]
+#[test]
+#[should_panic(expected = "is before the current DA block header height")]
+fn interleaved_da_slot_heights_are_rejected_outside_interleaved_specs() {
+    apply_commitment_with_interleaved_da_slot_heights(&[]);
+}
+
+/// Applies a commitment next to an oversized junk blob of another DA key,
+/// with the sequencer's DA blobs limited to their own size minus `bytes_below_limit` bytes.
+#[cfg(feature = "native")]
+fn apply_commitment_with_foreign_junk(bytes_below_limit: usize) {
+    use sov_mock_da::{MockAddress, MockBlob, MockBlockHeader, MockDaSpec, MockValidityCond};
+    use sov_rollup_interface::da::BlobReaderTrait;
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        commitment_blob, genesis_storage, sequencer_key, soft_confirmation_chain, tx,
+        TestBlueprint, TestCall, TestZkvm, SEQUENCER_DA_PUBLIC_KEY,
+    };
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_headers = (1..=2)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    let soft_confirmations = soft_confirmation_chain(
+        [0; 32],
+        &da_block_headers.iter().collect::<Vec<_>>(),
+        |index| vec![tx(&private_key, TestCall::Set(index as u64), index as u64)],
+    );
+    let sequencer_blob = commitment_blob(&soft_confirmations, 1, SEQUENCER_DA_PUBLIC_KEY);
+    let max_bytes = sequencer_blob.total_len() - bytes_below_limit;
+    let da_data = vec![
+        MockBlob::new(vec![0; 10 * max_bytes], MockAddress::new([8; 32]), [0; 32]),
+        sequencer_blob,
+    ];
+
+    let stf = TestBlueprint::new().with_max_da_bytes_per_commitment(max_bytes);
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (state_root, _) =
+        StateTransitionFunction::<TestZkvm, MockDaSpec>::apply_soft_confirmations_from_sequencer_commitments(
+            &stf,
+            &public_key,
+            &SEQUENCER_DA_PUBLIC_KEY,
+            &genesis_root,
+            [0; 32],
+            storage,
+            da_data,
+            (0, 0),
+            VecDeque::from([soft_confirmations
+                .iter()
+                .map(|_| Default::default())
+                .collect()]),
+            VecDeque::from([da_block_headers]),
+            &MockValidityCond::default(),
+            VecDeque::from([soft_confirmations]),
+            vec![(SpecId::Genesis, 0)],
+        );
+    assert_ne!(state_root, genesis_root);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn foreign_blobs_do_not_count_towards_da_byte_limit() {
+    apply_commitment_with_foreign_junk(0);
+}
+
+#[cfg(feature = "native")]
+#[test]
+#[should_panic(expected = "DA blobs of the sequencer exceed the limit")]
+fn sequencer_blobs_over_da_byte_limit_are_rejected() {
+    apply_commitment_with_foreign_junk(1);
+}
+
+#[test]
+fn check_da_data_size_trips_on_oversized_blobs() {
+    use sov_mock_da::{MockAddress, MockBlob};
+
+    use crate::check_da_data_size;
+
+    let sender = MockAddress::new([1; 32]);
+    let other_sender = MockAddress::new([2; 32]);
+    let blobs = vec![
+        MockBlob::new(vec![0; 100], sender, [0; 32]),
+        MockBlob::new(vec![0; 100], sender, [1; 32]),
+        MockBlob::new(vec![0; 10_000], other_sender, [2; 32]),
+        MockBlob::new(vec![0; 1_000], sender, [3; 32]),
+    ];
+
+    assert_eq!(check_da_data_size(&blobs, &[1; 32], 1_200), Ok(()));
+    assert_eq!(check_da_data_size::<MockBlob>(&[], &[1; 32], 0), Ok(()));
+    // the last blob takes the total of the sequencer's blobs to 1_200 bytes
+    assert_eq!(check_da_data_size(&blobs, &[1; 32], 1_199), Err(3));
+    assert_eq!(check_da_data_size(&blobs, &[1; 32], 150), Err(1));
+    // only the blob of the other sender counts for it
+    assert_eq!(check_da_data_size(&blobs, &[2; 32], 10_000), Ok(()));
+    assert_eq!(check_da_data_size(&blobs, &[2; 32], 9_999), Err(2));
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn extract_sequencer_commitments_records_rejected_blobs() {
+    use sov_mock_da::{MockAddress, MockBlob};
+    use sov_rollup_interface::da::{BlobReaderTrait, DaData};
+
+    use crate::{extract_sequencer_commitments, BlobRejectionReason, RejectedBlob};
+
+    let sequencer = MockAddress::new([1; 32]);
+    let other = MockAddress::new([2; 32]);
+    let (commitment, _, _) = commitment_fixture();
+    let commitment_data = borsh::to_vec(&DaData::SequencerCommitment(commitment.clone())).unwrap();
+
+    let mut blobs = vec![
+        MockBlob::new(commitment_data.clone(), sequencer, [0; 32]),
+        MockBlob::new(vec![0xff, 0xff], sequencer, [1; 32]),
+        MockBlob::new(commitment_data, other, [2; 32]),
+    ];
+    for blob in blobs.iter_mut() {
+        blob.full_data();
+    }
+
+    let mut rejected = vec![];
+    let commitments =
+        extract_sequencer_commitments(&blobs, sequencer.as_ref(), Some(&mut rejected));
+    assert_eq!(
+        commitments,
+        extract_sequencer_commitments(&blobs, sequencer.as_ref(), None)
+    );
+    assert_eq!(commitments, vec![commitment]);
+
+    assert_eq!(rejected.len(), 2);
+    assert_eq!(rejected[0].sender, sequencer);
+    assert!(matches!(
+        rejected[0].reason,
+        BlobRejectionReason::Malformed(_)
+    ));
+    assert_eq!(
+        rejected[1],
+        RejectedBlob {
+            sender: other,
+            reason: BlobRejectionReason::NotFromSequencer,
+        }
+    );
+}
+
+/// Storage whose state updates always fail, like a node with a corrupted database
+#[derive(Clone)]
+struct FailingStorage;
+
+impl Storage for FailingStorage {
+    type Witness = ArrayWitness;
+    type RuntimeConfig = ();
+    type Proof = ();
+    type Root = [u8; 32];
+    type StateUpdate = ();
+
+    fn get(
+        &self,
+        _key: &StorageKey,
+        _version: Option<u64>,
+        _witness: &mut Self::Witness,
+    ) -> Option<StorageValue> {
+        None
+    }
+
+    fn compute_state_update(
+        &self,
+        _state_accesses: OrderedReadsAndWrites,
+        _witness: &mut Self::Witness,
+    ) -> Result<(Self::Root, Self::StateUpdate, StateDiff), anyhow::Error> {
+        Err(anyhow::anyhow!("disk full"))
+    }
+
+    fn commit(&self, _node_batch: &Self::StateUpdate, _accessory_update: &OrderedReadsAndWrites) {
+        panic!("nothing must be committed after a failed state update");
+    }
+
+    fn open_proof(
+        _state_root: Self::Root,
+        _proof: StorageProof<Self::Proof>,
+    ) -> Result<(StorageKey, Option<StorageValue>), anyhow::Error> {
+        unimplemented!()
+    }
+
+    fn is_empty(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn commit_state_update_propagates_storage_errors() {
+    let result = commit_state_update(
+        &FailingStorage,
+        OrderedReadsAndWrites::default(),
+        &mut ArrayWitness::default(),
+        |_| panic!("finalize must not run after a failed state update"),
+    );
+
+    let Err(StorageError::StateUpdate(e)) = result else {
+        panic!("state update must fail");
+    };
+    assert_eq!(e.to_string(), "disk full");
+}
+
+#[test]
+fn tx_costs_record_sizes() {
+    let costs = tx_costs(&[vec![0; 10], vec![0; 100], vec![0; 1]]);
+
+    assert_eq!(
+        costs,
+        vec![
+            TxCost {
+                tx_size: 10,
+                cumulative_tx_size: 10,
+            },
+            TxCost {
+                tx_size: 100,
+                cumulative_tx_size: 110,
+            },
+            TxCost {
+                tx_size: 1,
+                cumulative_tx_size: 111,
+            },
+        ]
+    );
+    // a larger transaction reports a larger size
+    assert!(costs[1].tx_size > costs[0].tx_size);
+}
+
+#[test]
+fn batch_borsh_round_trip() {
+    let txs = vec![RawTx { data: vec![1; 10] }, RawTx { data: vec![] }];
+    let batch = Batch::new(txs.clone());
+    assert_eq!(batch.txs(), txs.as_slice());
+
+    let serialized = borsh::to_vec(&batch).unwrap();
+    let deserialized: Batch = borsh::BorshDeserialize::try_from_slice(&serialized).unwrap();
+    assert_eq!(deserialized, batch);
+
+    let empty = Batch::new(vec![]);
+    let serialized = borsh::to_vec(&empty).unwrap();
+    let deserialized: Batch = borsh::BorshDeserialize::try_from_slice(&serialized).unwrap();
+    assert!(deserialized.txs().is_empty());
+}
+
+#[test]
+fn batch_stream_txs_matches_buffered_parse() {
+    let batch = Batch {
+        txs: (0..2_000u32)
+            .map(|i| RawTx {
+                data: vec![i as u8; 1 + (i as usize % 4096)],
+            })
+            .collect(),
+    };
+    let serialized = borsh::to_vec(&batch).unwrap();
+
+    let streamed = Batch::stream_txs(serialized.as_slice())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let buffered: Batch = borsh::BorshDeserialize::try_from_slice(&serialized).unwrap();
+    assert_eq!(streamed, buffered.txs);
+}
+
+#[test]
+fn batch_stream_txs_truncated() {
+    let batch = Batch {
+        txs: vec![RawTx { data: vec![1; 10] }, RawTx { data: vec![2; 10] }],
+    };
+    let serialized = borsh::to_vec(&batch).unwrap();
+
+    let mut txs = Batch::stream_txs(&serialized[..serialized.len() - 1]).unwrap();
+    assert_eq!(txs.next().unwrap().unwrap(), batch.txs[0]);
+    assert!(txs.next().unwrap().is_err());
+    assert!(txs.next().is_none());
+}
+
+#[test]
+fn invalid_transaction_encoding_slashes_sequencer() {
+    use sov_mock_da::MockAddress;
+    use sov_modules_api::default_context::ZkDefaultContext;
+
+    use crate::tx_verifier::verify_txs_stateless;
+    use crate::{SequencerOutcome, SlashingReason};
+
+    let reason = verify_txs_stateless::<ZkDefaultContext>(vec![RawTx {
+        data: vec![0xff; 3],
+    }])
+    .err()
+    .unwrap();
+    assert_eq!(reason, SlashingReason::InvalidTransactionEncoding);
+
+    let sequencer = MockAddress::new([1; 32]);
+    assert_eq!(
+        reason.into_outcome(sequencer),
+        SequencerOutcome::Slashed {
+            reason: SlashingReason::InvalidTransactionEncoding,
+            sequencer_da_address: sequencer,
+        }
+    );
+}
+
+#[test]
+fn apply_commitments_until_stops_at_deadline() {
+    use std::time::{Duration, Instant};
+
+    use crate::apply_commitments_until;
+
+    let commitments = [(1, 10), (11, 20), (21, 30)];
+
+    let applied_up_to_height = apply_commitments_until(commitments, || false, |(_, l2_end)| l2_end);
+    assert_eq!(applied_up_to_height, Some(30));
+
+    let deadline = Instant::now() + Duration::from_millis(10);
+    let mut applied = vec![];
+    let applied_up_to_height = apply_commitments_until(
+        commitments,
+        || Instant::now() >= deadline,
+        |(l2_start, l2_end)| {
+            applied.push(l2_start);
+            std::thread::sleep(Duration::from_millis(20));
+            l2_end
+        },
+    );
+    // the first commitment is applied as a whole, the rest is left for later
+    assert_eq!(applied, vec![1]);
+    assert_eq!(applied_up_to_height, Some(10));
+
+    let applied_up_to_height = apply_commitments_until(commitments, || true, |(_, l2_end)| l2_end);
+    assert_eq!(applied_up_to_height, None);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn lazy_witnesses_give_the_state_root_of_eager_witnesses() {
+    use std::cell::RefCell;
+
+    use sov_mock_da::{MockBlockHeader, MockDaSpec, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        commitment_blob, genesis_storage, sequencer_key, soft_confirmation_chain, tx,
+        TestBlueprint, TestCall, TestZkvm, SEQUENCER_DA_PUBLIC_KEY,
+    };
+    use crate::ApplyCommitmentsOptions;
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_headers = (1..=4)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    let soft_confirmations = soft_confirmation_chain(
+        [0; 32],
+        &da_block_headers.iter().collect::<Vec<_>>(),
+        |index| vec![tx(&private_key, TestCall::Set(index as u64), index as u64)],
+    );
+    // two commitments over L2 heights 1..=2 and 3..=4
+    let da_data = vec![
+        commitment_blob(&soft_confirmations[..2], 1, SEQUENCER_DA_PUBLIC_KEY),
+        commitment_blob(&soft_confirmations[2..], 3, SEQUENCER_DA_PUBLIC_KEY),
+    ];
+    let slot_headers = VecDeque::from([
+        da_block_headers[..2].to_vec(),
+        da_block_headers[2..].to_vec(),
+    ]);
+    let soft_confirmations = VecDeque::from([
+        soft_confirmations[..2].to_vec(),
+        soft_confirmations[2..].to_vec(),
+    ]);
+    let stf = TestBlueprint::new();
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (eager_state_root, eager_state_diff) =
+        StateTransitionFunction::<TestZkvm, MockDaSpec>::apply_soft_confirmations_from_sequencer_commitments(
+            &stf,
+            &public_key,
+            &SEQUENCER_DA_PUBLIC_KEY,
+            &genesis_root,
+            [0; 32],
+            storage,
+            da_data.clone(),
+            (0, 1),
+            soft_confirmations
+                .iter()
+                .map(|soft_confirmations| {
+                    soft_confirmations
+                        .iter()
+                        .map(|_| Default::default())
+                        .collect()
+                })
+                .collect(),
+            slot_headers.clone(),
+            &MockValidityCond::default(),
+            soft_confirmations.clone(),
+            vec![(SpecId::Genesis, 0)],
+        );
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let requested_witnesses = RefCell::new(vec![]);
+    let (lazy_state_root, lazy_state_diff, applied_up_to) = stf
+        .apply_soft_confirmations_from_sequencer_commitments_with_options(
+            &public_key,
+            &SEQUENCER_DA_PUBLIC_KEY,
+            &genesis_root,
+            [0; 32],
+            storage,
+            da_data,
+            (0, 1),
+            VecDeque::from([vec![], vec![]]),
+            slot_headers,
+            &MockValidityCond::default(),
+            soft_confirmations,
+            vec![(SpecId::Genesis, 0)],
+            ApplyCommitmentsOptions {
+                witness_for: Some(Box::new(|commitment_index, index| {
+                    requested_witnesses
+                        .borrow_mut()
+                        .push((commitment_index, index));
+                    Default::default()
+                })),
+                ..Default::default()
+            },
+        );
+
+    assert_eq!(applied_up_to, Some(4));
+    assert_eq!(
+        *requested_witnesses.borrow(),
+        vec![(0, 0), (0, 1), (1, 0), (1, 1)]
+    );
+    assert_ne!(eager_state_root, genesis_root);
+    assert_eq!(lazy_state_root, eager_state_root);
+    assert_eq!(lazy_state_diff, eager_state_diff);
+}
+
+#[test]
+#[should_panic(expected = "Number of witnesses and soft confirmations must match")]
+fn eager_witnesses_rejects_missing_witness() {
+    let (_, soft_confirmations, _) = two_commitments_fixture();
+    let witnesses = VecDeque::from([vec![0u64; 3], vec![0]]);
+
+    let _ = eager_witnesses(witnesses, &soft_confirmations);
+}
+
+#[test]
+fn pinned_sequencer_public_key_rejects_other_keys() {
+    use crate::{check_pinned_sequencer_public_key, SequencerPublicKeyMismatch};
+
+    let pinned = [1; 32];
+
+    assert_eq!(
+        check_pinned_sequencer_public_key(Some(&pinned), &pinned),
+        Ok(())
+    );
+    for other in [&[2; 32][..], &[1; 31], &[]] {
+        assert_eq!(
+            check_pinned_sequencer_public_key(Some(&pinned), other),
+            Err(SequencerPublicKeyMismatch {
+                expected: pinned.to_vec(),
+                got: other.to_vec(),
+            })
+        );
+    }
+
+    // nothing pinned, any key is accepted
+    assert_eq!(check_pinned_sequencer_public_key(None, &[2; 32]), Ok(()));
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn signed_soft_confirmation_verifies() {
+    use sov_modules_api::default_context::DefaultContext;
+    use sov_modules_api::default_signature::private_key::DefaultPrivateKey;
+    use sov_modules_api::{PrivateKey, Spec, UnsignedSoftConfirmationBatch};
+    use sov_rollup_interface::digest::Digest;
+
+    use crate::{sign_soft_confirmation, verify_soft_confirmation_signature_unless_trusted};
+
+    let private_key = DefaultPrivateKey::generate();
+    let sequencer_public_key = borsh::to_vec(&private_key.pub_key()).unwrap();
+    let unsigned = UnsignedSoftConfirmationBatch::new(
+        7,
+        [1; 32],
+        [2; 32],
+        vec![vec![3; 10]],
+        vec![vec![4; 5]],
+        10,
+        1_000,
+    );
+
+    let signed = sign_soft_confirmation::<DefaultContext>(
+        unsigned.clone(),
+        [5; 32],
+        &private_key,
+        &sequencer_public_key,
+    );
+    assert_eq!(signed.prev_hash(), [5; 32]);
+    assert_eq!(signed.pub_key(), sequencer_public_key);
+
+    // rebuild the unsigned batch the way end_soft_confirmation does
+    let rebuilt = UnsignedSoftConfirmationBatch::new(
+        signed.da_slot_height(),
+        signed.da_slot_hash(),
+        signed.da_slot_txs_commitment(),
+        signed.txs(),
+        signed.deposit_data(),
+        signed.l1_fee_rate(),
+        signed.timestamp(),
+    );
+    assert_eq!(rebuilt, unsigned);
+
+    let unsigned_raw = borsh::to_vec(&rebuilt).unwrap();
+    assert_eq!(
+        signed.hash(),
+        Into::<[u8; 32]>::into(<DefaultContext as Spec>::Hasher::digest(&unsigned_raw))
+    );
+    assert!(
+        verify_soft_confirmation_signature_unless_trusted::<DefaultContext>(
+            &unsigned_raw,
+            &signed.signature(),
+            &sequencer_public_key,
+            true,
+        )
+        .is_ok()
+    );
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn end_soft_confirmation_accepts_signed_soft_confirmation() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::new();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    let result = stf.apply_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage.clone(),
+        Default::default(),
+        &da_block_header,
+        &MockValidityCond::default(),
+        &mut soft_confirmation,
+    );
+
+    assert_ne!(result.state_root, genesis_root);
+    assert_eq!(result.batch_receipts[0].hash, soft_confirmation.hash());
+    assert_eq!(
+        result.batch_receipts[0].inner,
+        crate::SequencerOutcome::Rewarded(0)
+    );
+    assert_eq!(Recorded::Value.read(&storage), Some(5));
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn tx_hook_observes_da_slot_height() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::new();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(42);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    stf.apply_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage.clone(),
+        Default::default(),
+        &da_block_header,
+        &MockValidityCond::default(),
+        &mut soft_confirmation,
+    );
+
+    assert_eq!(Recorded::TxDaSlotHeight.read(&storage), Some(42));
+}
+
+#[cfg(feature = "native")]
+#[test]
+#[should_panic(expected = "does not match the pinned sequencer public key")]
+fn pinned_blueprint_rejects_soft_confirmation_of_other_sequencer() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, TestBlueprint, TestCall,
+    };
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::with_sequencer_public_key(vec![7; 32]);
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    stf.apply_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage,
+        Default::default(),
+        &da_block_header,
+        &MockValidityCond::default(),
+        &mut soft_confirmation,
+    );
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn pinned_blueprint_applies_soft_confirmation_of_its_sequencer() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (private_key, public_key) = sequencer_key();
+    let stf = TestBlueprint::with_sequencer_public_key(public_key.clone());
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let da_block_header = MockBlockHeader::from_height(1);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    stf.apply_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage.clone(),
+        Default::default(),
+        &da_block_header,
+        &MockValidityCond::default(),
+        &mut soft_confirmation,
+    );
+
+    assert_eq!(Recorded::Value.read(&storage), Some(5));
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn invalid_transaction_slashes_sequencer_in_batch_receipt() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_modules_api::{PrivateKey, PublicKey};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+    use crate::{SequencerOutcome, SlashingReason};
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::new();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0), vec![0xff; 3]],
+        1,
+        1,
+    );
+
+    let result = stf.apply_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage.clone(),
+        Default::default(),
+        &da_block_header,
+        &MockValidityCond::default(),
+        &mut soft_confirmation,
+    );
+
+    let batch_receipt = &result.batch_receipts[0];
+    assert_eq!(
+        batch_receipt.inner,
+        SequencerOutcome::Slashed {
+            reason: SlashingReason::InvalidTransactionEncoding,
+            sequencer_da_address: private_key.pub_key().to_address(),
+        }
+    );
+    // none of the transactions are applied, the hooks still run
+    assert!(batch_receipt.tx_receipts.is_empty());
+    assert_eq!(Recorded::Value.read(&storage), None);
+    assert_eq!(Recorded::EndHookCalls.read(&storage), Some(1));
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn tx_limit_of_the_current_spec_slashes_sequencer() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_modules_api::{PrivateKey, PublicKey};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+    use crate::{SequencerOutcome, SlashingReason};
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![
+            tx(&private_key, TestCall::Set(5), 0),
+            tx(&private_key, TestCall::Set(6), 1),
+        ],
+        1,
+        1,
+    );
+
+    // only Fork1 limits soft confirmations to a single transaction
+    let apply = |spec: SpecId| {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let stf = TestBlueprint::new().with_soft_confirmation_tx_limits(&[(SpecId::Fork1, 1)]);
+        let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+        let result = stf.apply_soft_confirmation(
+            spec,
+            &public_key,
+            &genesis_root,
+            storage.clone(),
+            Default::default(),
+            &da_block_header,
+            &MockValidityCond::default(),
+            &mut soft_confirmation.clone(),
+        );
+        (
+            result.batch_receipts[0].inner.clone(),
+            result.batch_receipts[0].tx_receipts.len(),
+            Recorded::Value.read(&storage),
+        )
+    };
+
+    assert_eq!(
+        apply(SpecId::Genesis),
+        (SequencerOutcome::Rewarded(0), 2, Some(6))
+    );
+    assert_eq!(
+        apply(SpecId::Fork1),
+        (
+            SequencerOutcome::Slashed {
+                reason: SlashingReason::TooManyTransactions,
+                sequencer_da_address: private_key.pub_key().to_address(),
+            },
+            0,
+            None
+        )
+    );
+}
+
+/// Applies a soft confirmation whose contents were changed after signing, keeping the
+/// claimed hash and signature of the original.
+#[cfg(feature = "native")]
+fn apply_tampered_soft_confirmation(forge_signature: bool) {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_modules_api::default_signature::private_key::DefaultPrivateKey;
+    use sov_modules_api::PrivateKey;
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, TestBlueprint, TestCall,
+    };
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::new();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let signed = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    let (txs, signature) = if forge_signature {
+        let forged = DefaultPrivateKey::generate().sign(&[0; 32]);
+        (signed.txs(), borsh::to_vec(&forged).unwrap())
+    } else {
+        (
+            vec![tx(&private_key, TestCall::Set(6), 0)],
+            signed.signature(),
+        )
+    };
+    let mut soft_confirmation = SignedSoftConfirmationBatch::new(
+        signed.hash(),
+        signed.prev_hash(),
+        signed.da_slot_height(),
+        signed.da_slot_hash(),
+        signed.da_slot_txs_commitment(),
+        signed.l1_fee_rate(),
+        txs,
+        signed.deposit_data(),
+        signature,
+        public_key.clone(),
+        signed.timestamp(),
+    );
+
+    stf.apply_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage,
+        Default::default(),
+        &da_block_header,
+        &MockValidityCond::default(),
+        &mut soft_confirmation,
+    );
+}
+
+#[cfg(feature = "native")]
+#[test]
+#[should_panic(expected = "Soft confirmation hashes must match")]
+fn end_soft_confirmation_rejects_contents_changed_after_signing() {
+    apply_tampered_soft_confirmation(false);
+}
+
+#[cfg(feature = "native")]
+#[test]
+#[should_panic(expected = "Signature verification must succeed")]
+fn end_soft_confirmation_rejects_forged_signature() {
+    apply_tampered_soft_confirmation(true);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn apply_chain_of_empty_soft_confirmations() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, soft_confirmation_chain, Recorded, TestBlueprint,
+    };
+
+    let da_block_headers = (1..=3)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    let soft_confirmations = soft_confirmation_chain(
+        [0; 32],
+        &da_block_headers.iter().collect::<Vec<_>>(),
+        |_| vec![],
+    );
+    let (_, public_key) = sequencer_key();
+
+    let apply_chain = || {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let stf = TestBlueprint::new();
+        let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+        let mut state_roots = vec![genesis_root];
+        for (soft_confirmation, da_block_header) in soft_confirmations.iter().zip(&da_block_headers)
+        {
+            let result = stf.apply_soft_confirmation(
+                SpecId::Genesis,
+                &public_key,
+                state_roots.last().unwrap(),
+                storage.clone(),
+                Default::default(),
+                da_block_header,
+                &MockValidityCond::default(),
+                &mut soft_confirmation.clone(),
+            );
+            assert_eq!(result.batch_receipts.len(), 1);
+            assert_eq!(result.batch_receipts[0].hash, soft_confirmation.hash());
+            assert!(result.batch_receipts[0].tx_receipts.is_empty());
+            state_roots.push(result.state_root);
+        }
+        // the hooks still ran for every empty soft confirmation
+        assert_eq!(Recorded::BeginHookCalls.read(&storage), Some(3));
+        assert_eq!(Recorded::EndHookCalls.read(&storage), Some(3));
+        assert_eq!(Recorded::Value.read(&storage), None);
+        state_roots
+    };
+
+    let state_roots = apply_chain();
+    assert!(state_roots.windows(2).all(|roots| roots[0] != roots[1]));
+    assert_eq!(state_roots, apply_chain());
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn failing_validity_condition_reverts_soft_confirmation() {
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::new();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    let result = stf.apply_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage.clone(),
+        Default::default(),
+        &da_block_header,
+        &MockValidityCond { is_valid: false },
+        &mut soft_confirmation,
+    );
+
+    assert_eq!(result.state_root, genesis_root);
+    assert!(result.batch_receipts.is_empty());
+    assert!(result.state_diff.is_empty());
+    assert_eq!(Recorded::BeginHookCalls.read(&storage), None);
+    assert_eq!(Recorded::Value.read(&storage), None);
+}
+
+#[cfg(feature = "native")]
+#[test]
+#[should_panic(expected = "Validity condition does not hold for DA block 1")]
+fn failing_validity_condition_fails_commitments() {
+    use sov_mock_da::{MockBlockHeader, MockDaSpec, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    use self::runtime::{
+        commitment_blob, genesis_storage, sequencer_key, soft_confirmation_chain, tx,
+        TestBlueprint, TestCall, TestZkvm, SEQUENCER_DA_PUBLIC_KEY,
+    };
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_headers = (1..=2)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    let soft_confirmations = soft_confirmation_chain(
+        [0; 32],
+        &da_block_headers.iter().collect::<Vec<_>>(),
+        |index| vec![tx(&private_key, TestCall::Set(index as u64), index as u64)],
+    );
+    let da_data = vec![commitment_blob(
+        &soft_confirmations,
+        1,
+        SEQUENCER_DA_PUBLIC_KEY,
+    )];
+
+    let stf = TestBlueprint::new();
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    StateTransitionFunction::<TestZkvm, MockDaSpec>::apply_soft_confirmations_from_sequencer_commitments(
+        &stf,
+        &public_key,
+        &SEQUENCER_DA_PUBLIC_KEY,
+        &genesis_root,
+        [0; 32],
+        storage,
+        da_data,
+        (0, 0),
+        VecDeque::from([soft_confirmations
+            .iter()
+            .map(|_| Default::default())
+            .collect()]),
+        VecDeque::from([da_block_headers]),
+        &MockValidityCond { is_valid: false },
+        VecDeque::from([soft_confirmations]),
+        vec![(SpecId::Genesis, 0)],
+    );
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn trusted_replay_skips_signature_verification() {
+    use sov_modules_api::default_context::DefaultContext;
+    use sov_modules_api::default_signature::private_key::DefaultPrivateKey;
+    use sov_modules_api::PrivateKey;
+
+    use crate::verify_soft_confirmation_signature_unless_trusted;
+
+    let private_key = DefaultPrivateKey::generate();
+    let sequencer_public_key = borsh::to_vec(&private_key.pub_key()).unwrap();
+    let unsigned_raw = b"unsigned soft confirmation".to_vec();
+    let signature = borsh::to_vec(&private_key.sign(&unsigned_raw)).unwrap();
+    let forged_signature =
+        borsh::to_vec(&DefaultPrivateKey::generate().sign(&unsigned_raw)).unwrap();
+
+    let verify = |signature: &[u8], verify_signatures| {
+        verify_soft_confirmation_signature_unless_trusted::<DefaultContext>(
+            &unsigned_raw,
+            signature,
+            &sequencer_public_key,
+            verify_signatures,
+        )
+    };
+
+    assert!(verify(&signature, true).is_ok());
+    assert!(verify(&forged_signature, true).is_err());
+
+    // a trusted replay takes the signature as is
+    assert!(verify(&signature, false).is_ok());
+    assert!(verify(&forged_signature, false).is_ok());
+}
+
+/// Applies a soft confirmation hook by hook, returning the state root and the hook counters.
+#[cfg(feature = "native")]
+fn apply_soft_confirmation_with_signature_checks(
+    verify_signatures: bool,
+) -> (runtime::TestRoot, Option<u64>, Option<u64>, Option<u64>) {
+    use sov_mock_da::MockBlockHeader;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+    use crate::StfBlueprintTrait;
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::new();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    let (begin_result, batch_workspace) = stf.begin_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage.clone(),
+        Default::default(),
+        &da_block_header,
+        &mut soft_confirmation,
+    );
+    begin_result.unwrap();
+    let (batch_workspace, tx_receipts) = stf.apply_soft_confirmation_txs(
+        SpecId::Genesis,
+        soft_confirmation.da_slot_height(),
+        soft_confirmation.txs(),
+        batch_workspace,
+    );
+    let (batch_receipt, checkpoint) = stf.end_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &mut soft_confirmation,
+        tx_receipts,
+        batch_workspace,
+        verify_signatures,
+    );
+    let slot_result = stf.finalize_soft_confirmation(
+        SpecId::Genesis,
+        batch_receipt,
+        checkpoint,
+        storage.clone(),
+        &mut soft_confirmation,
+    );
+
+    (
+        slot_result.state_root,
+        Recorded::Value.read(&storage),
+        Recorded::BeginHookCalls.read(&storage),
+        Recorded::EndHookCalls.read(&storage),
+    )
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn skipping_signature_checks_keeps_hooks_and_state_root() {
+    let verified = apply_soft_confirmation_with_signature_checks(true);
+    let trusted = apply_soft_confirmation_with_signature_checks(false);
+
+    assert_eq!(verified.1, Some(5));
+    assert_eq!(verified.2, Some(1));
+    assert_eq!(verified.3, Some(1));
+    assert_eq!(trusted, verified);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn apply_single_soft_confirmation_matches_applying_it_hook_by_hook() {
+    use sov_mock_da::MockBlockHeader;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, signed_soft_confirmation, tx, Recorded, TestBlueprint,
+        TestCall,
+    };
+    use crate::StfBlueprintTrait;
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let stf = TestBlueprint::new();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let (private_key, public_key) = sequencer_key();
+    let da_block_header = MockBlockHeader::from_height(1);
+    let mut soft_confirmation = signed_soft_confirmation(
+        [0; 32],
+        &da_block_header,
+        vec![tx(&private_key, TestCall::Set(5), 0)],
+        1,
+        1,
+    );
+
+    let (result, slot_result) = stf.apply_single_soft_confirmation(
+        SpecId::Genesis,
+        &public_key,
+        &genesis_root,
+        storage.clone(),
+        Default::default(),
+        &da_block_header,
+        &mut soft_confirmation,
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(slot_result.batch_receipts.len(), 1);
+    assert_eq!(slot_result.batch_receipts[0].hash, soft_confirmation.hash());
+    assert_eq!(
+        (
+            slot_result.state_root,
+            Recorded::Value.read(&storage),
+            Recorded::BeginHookCalls.read(&storage),
+            Recorded::EndHookCalls.read(&storage),
+        ),
+        apply_soft_confirmation_with_signature_checks(true)
+    );
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn revert_recorded_state_roots_and_reapply_divergent_chain() {
+    use std::collections::BTreeMap;
+
+    use citrea_primitives::fork::{Fork, ForkManager};
+
+    use crate::truncate_recorded_state_roots;
+
+    let forks = vec![(SpecId::Genesis, 0), (SpecId::Fork1, 7)];
+    let mut fork_manager = ForkManager::for_height(0, forks).unwrap();
+    let mut recorded_state_roots = BTreeMap::new();
+    // stands in for applying soft confirmations, every branch yields different state roots
+    fn apply(
+        recorded_state_roots: &mut BTreeMap<u64, [u8; 32]>,
+        fork_manager: &mut ForkManager,
+        heights: std::ops::RangeInclusive<u64>,
+        branch: u8,
+    ) {
+        for height in heights {
+            let mut state_root = [branch; 32];
+            state_root[31] = height as u8;
+            recorded_state_roots.insert(height, state_root);
+            fork_manager.register_block(height).unwrap();
+        }
+    }
+
+    apply(&mut recorded_state_roots, &mut fork_manager, 1..=10, 1);
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+
+    let state_root = truncate_recorded_state_roots(&mut recorded_state_roots, 5).unwrap();
+    fork_manager.rollback_to(5).unwrap();
+    assert_eq!(state_root, recorded_state_roots[&5]);
+    assert_eq!(recorded_state_roots.keys().last(), Some(&5));
+    assert_eq!(fork_manager.active_fork(), SpecId::Genesis);
+
+    // heights above the target are gone, there is nothing to revert to
+    assert!(truncate_recorded_state_roots(&mut recorded_state_roots, 8).is_err());
+    assert_eq!(recorded_state_roots.len(), 5);
+
+    apply(&mut recorded_state_roots, &mut fork_manager, 6..=10, 2);
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+    assert_eq!(recorded_state_roots.len(), 10);
+    assert_eq!(recorded_state_roots[&5][0], 1);
+    assert_eq!(recorded_state_roots[&10][0], 2);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn revert_to_l2_height_discards_state_and_reapplies_divergent_chain() {
+    use std::collections::BTreeMap;
+
+    use citrea_primitives::fork::{Fork, ForkManager};
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::stf::StateTransitionFunction;
+    use sov_state::storage::NativeStorage;
+
+    use self::runtime::{
+        genesis_storage, sequencer_key, soft_confirmation_chain, tx, Recorded, TestBlueprint,
+        TestCall, TestRoot, TestStorage,
+    };
+    use crate::RecordedState;
+
+    let forks = vec![(SpecId::Genesis, 0), (SpecId::Fork1, 7)];
+    let (private_key, public_key) = sequencer_key();
+    let stf = TestBlueprint::new();
+
+    // applies a chain of soft confirmations above the last recorded height,
+    // the one at L2 height `height` sets the value to `branch + height`
+    let apply = |storage: &TestStorage,
+                 recorded_states: &mut BTreeMap<u64, RecordedState<TestRoot>>,
+                 fork_manager: &mut ForkManager,
+                 prev_hash: [u8; 32],
+                 heights: std::ops::RangeInclusive<u64>,
+                 branch: u64| {
+        let start_height = *heights.start();
+        let da_block_headers = heights
+            .map(MockBlockHeader::from_height)
+            .collect::<Vec<_>>();
+        let soft_confirmations = soft_confirmation_chain(
+            prev_hash,
+            &da_block_headers.iter().collect::<Vec<_>>(),
+            |index| {
+                let height = start_height + index as u64;
+                vec![tx(&private_key, TestCall::Set(branch + height), height - 1)]
+            },
+        );
+        for (soft_confirmation, da_block_header) in soft_confirmations.iter().zip(&da_block_headers)
+        {
+            let height = da_block_header.height;
+            let result = stf.apply_soft_confirmation(
+                fork_manager.active_fork(),
+                &public_key,
+                &recorded_states[&(height - 1)].state_root,
+                storage.clone(),
+                Default::default(),
+                da_block_header,
+                &MockValidityCond::default(),
+                &mut soft_confirmation.clone(),
+            );
+            recorded_states.insert(
+                height,
+                RecordedState {
+                    state_root: result.state_root,
+                    version: storage.latest_version(),
+                },
+            );
+            fork_manager.register_block(height).unwrap();
+        }
+        soft_confirmations
+    };
+    let genesis_state = |storage: &TestStorage, state_root: TestRoot| {
+        BTreeMap::from([(
+            0,
+            RecordedState {
+                state_root,
+                version: storage.latest_version(),
+            },
+        )])
+    };
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let mut recorded_states = genesis_state(&storage, genesis_root);
+    let mut fork_manager = ForkManager::for_height(0, forks.clone()).unwrap();
+    let chain = apply(
+        &storage,
+        &mut recorded_states,
+        &mut fork_manager,
+        [0; 32],
+        1..=10,
+        0,
+    );
+    let reverted_state_root = recorded_states[&10].state_root;
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+    assert_eq!(Recorded::Value.read(&storage), Some(10));
+
+    let state_root = stf
+        .revert_to_l2_height(storage.clone(), 5, &mut recorded_states, &mut fork_manager)
+        .unwrap();
+    assert_eq!(state_root, recorded_states[&5].state_root);
+    assert_eq!(recorded_states.keys().last(), Some(&5));
+    assert_eq!(storage.latest_version(), recorded_states[&5].version);
+    assert_eq!(fork_manager.active_fork(), SpecId::Genesis);
+    // the state written above L2 height 5 is gone
+    assert_eq!(Recorded::Value.read(&storage), Some(5));
+    assert_eq!(Recorded::EndHookCalls.read(&storage), Some(5));
+
+    // a divergent 6'..=10' on top of the reverted storage
+    apply(
+        &storage,
+        &mut recorded_states,
+        &mut fork_manager,
+        chain[4].hash(),
+        6..=10,
+        100,
+    );
+    assert_eq!(fork_manager.active_fork(), SpecId::Fork1);
+    assert_eq!(Recorded::Value.read(&storage), Some(110));
+    assert_ne!(recorded_states[&10].state_root, reverted_state_root);
+
+    // ends at the same state root as applying 1..=5 and 6'..=10' on fresh storage
+    let tmpdir = tempfile::tempdir().unwrap();
+    let (fresh_storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+    let mut fresh_states = genesis_state(&fresh_storage, genesis_root);
+    let mut fresh_fork_manager = ForkManager::for_height(0, forks).unwrap();
+    let fresh_chain = apply(
+        &fresh_storage,
+        &mut fresh_states,
+        &mut fresh_fork_manager,
+        [0; 32],
+        1..=5,
+        0,
+    );
+    apply(
+        &fresh_storage,
+        &mut fresh_states,
+        &mut fresh_fork_manager,
+        fresh_chain[4].hash(),
+        6..=10,
+        100,
+    );
+    assert_eq!(fresh_states, recorded_states);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn apply_commitments_options_report_each_soft_confirmation() {
+    use std::cell::RefCell;
+    use std::time::Instant;
+
+    use sov_mock_da::{MockBlockHeader, MockValidityCond};
+    use sov_rollup_interface::zk::CumulativeStateDiff;
+
+    use self::runtime::{
+        commitment_blob, genesis_storage, sequencer_key, soft_confirmation_chain, tx,
+        TestBlueprint, TestCall, SEQUENCER_DA_PUBLIC_KEY,
+    };
+    use crate::ApplyCommitmentsOptions;
+
+    let (private_key, public_key) = sequencer_key();
+    let da_block_headers = (1..=3)
+        .map(MockBlockHeader::from_height)
+        .collect::<Vec<_>>();
+    let soft_confirmations = soft_confirmation_chain(
+        [0; 32],
+        &da_block_headers.iter().collect::<Vec<_>>(),
+        |index| vec![tx(&private_key, TestCall::Set(index as u64), index as u64)],
+    );
+    let da_data = vec![commitment_blob(
+        &soft_confirmations,
+        1,
+        SEQUENCER_DA_PUBLIC_KEY,
+    )];
+
+    let stf = TestBlueprint::new();
+    let apply = |options| {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let (storage, genesis_root) = genesis_storage(&stf, tmpdir.path());
+        let applied = stf.apply_soft_confirmations_from_sequencer_commitments_with_options(
+            &public_key,
+            &SEQUENCER_DA_PUBLIC_KEY,
+            &genesis_root,
+            [0; 32],
+            storage,
+            da_data.clone(),
+            (0, 0),
+            // no witnesses are given, they are requested through `witness_for` instead
+            VecDeque::from([vec![]]),
+            VecDeque::from([da_block_headers.clone()]),
+            &MockValidityCond::default(),
+            VecDeque::from([soft_confirmations.clone()]),
+            vec![(SpecId::Genesis, 0)],
+            options,
+        );
+        (genesis_root, applied)
+    };
+
+    // shared, so they can be read while `apply` still holds the options' borrows
+    let requested_witnesses = RefCell::new(vec![]);
+    let applied = RefCell::new(vec![]);
+    let (_, (state_root, state_diff, applied_up_to)) = apply(ApplyCommitmentsOptions {
+        witness_for: Some(Box::new(|commitment_index, index| {
+            requested_witnesses
+                .borrow_mut()
+                .push((commitment_index, index));
+            Default::default()
+        })),
+        on_applied: Some(Box::new(|l2_height, state_root, state_diff| {
+            applied
+                .borrow_mut()
+                .push((l2_height, *state_root, state_diff.clone()));
+        })),
+        ..Default::default()
+    });
+
+    assert_eq!(applied_up_to, Some(3));
+    assert_eq!(*requested_witnesses.borrow(), vec![(0, 0), (0, 1), (0, 2)]);
+    let applied = applied.borrow();
+    assert_eq!(
+        applied
+            .iter()
+            .map(|(l2_height, _, _)| *l2_height)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    // the reported state roots end at the state root of the whole range
+    assert_eq!(applied.last().unwrap().1, state_root);
+    // and their state diffs add up to the cumulative one
+    let reported_state_diff = applied
+        .iter()
+        .flat_map(|(_, _, state_diff)| state_diff.clone())
+        .collect::<CumulativeStateDiff>();
+    assert_eq!(reported_state_diff, state_diff);
+
+    // a passed deadline applies nothing
+    let (genesis_root, (state_root, state_diff, applied_up_to)) = apply(ApplyCommitmentsOptions {
+        deadline: Some(Instant::now()),
+        witness_for: Some(Box::new(|_, _| Default::default())),
+        ..Default::default()
+    });
+    assert_eq!(applied_up_to, None);
+    assert_eq!(state_root, genesis_root);
+    assert!(state_diff.is_empty());
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn compressed_state_diff_round_trip() {
+    use sov_rollup_interface::zk::CumulativeStateDiff;
+
+    use crate::{compress_state_diff, decompress_state_diff};
+
+    // storage keys of the same module share long prefixes
+    let state_diff: CumulativeStateDiff = (0..1_000u32)
+        .map(|i| {
+            let key = format!("sov_accounts/Accounts/accounts/{:08}", i).into_bytes();
+            let value = (i % 3 != 0).then(|| vec![i as u8 % 4; 32]);
+            (key, value)
+        })
+        .collect();
+
+    let compressed = compress_state_diff(&state_diff);
+    assert_eq!(decompress_state_diff(&compressed).unwrap(), state_diff);
+
+    let serialized = borsh::to_vec(&state_diff).unwrap();
+    assert!(
+        compressed.len() * 5 < serialized.len(),
+        "compressed {} bytes to {} bytes",
+        serialized.len(),
+        compressed.len()
+    );
+
+    let empty = CumulativeStateDiff::default();
+    assert_eq!(
+        decompress_state_diff(&compress_state_diff(&empty)).unwrap(),
+        empty
+    );
+    assert!(decompress_state_diff(&compressed[..compressed.len() / 2]).is_err());
+}
+
+#[test]
+fn applied_summary_counts_reverted_txs() {
+    use sov_rollup_interface::stf::TransactionReceipt;
+
+    use crate::{applied_summary, AppliedSummary, BatchReceipt, TxEffect};
+
+    let receipt = |tx_hash: u8, receipt: TxEffect| TransactionReceipt {
+        tx_hash: [tx_hash; 32],
+        body_to_save: None,
+        events: vec![],
+        receipt,
+    };
+    let batch_receipt = BatchReceipt::<(), TxEffect> {
+        hash: [1; 32],
+        prev_hash: [0; 32],
+        tx_receipts: vec![
+            receipt(1, TxEffect::Successful(TxCost::default())),
+            receipt(2, TxEffect::Reverted(TxCost::default())),
+            receipt(3, TxEffect::Successful(TxCost::default())),
+            receipt(4, TxEffect::Reverted(TxCost::default())),
+            receipt(5, TxEffect::Reverted(TxCost::default())),
+        ],
+        inner: (),
+    };
+
+    assert_eq!(
+        applied_summary(42, &[batch_receipt]),
+        AppliedSummary {
+            l2_height: 42,
+            tx_count: 5,
+            reverted_tx_count: 3,
+        }
+    );
+    assert_eq!(
+        applied_summary::<()>(43, &[]),
+        AppliedSummary {
+            l2_height: 43,
+            tx_count: 0,
+            reverted_tx_count: 0,
+        }
+    );
+}
+
+#[test]
+fn cumulative_state_diff_serialization_is_canonical() {
+    use sov_rollup_interface::zk::CumulativeStateDiff;
+
+    let entries: StateDiff = vec![
+        (b"b".to_vec(), Some(vec![2])),
+        (b"a".to_vec(), None),
+        (b"c".to_vec(), Some(vec![3])),
+        (b"ab".to_vec(), Some(vec![1])),
+    ];
+
+    let mut forward = CumulativeStateDiff::default();
+    forward.extend(entries.clone());
+    let mut backward = CumulativeStateDiff::default();
+    backward.extend(entries.iter().rev().cloned());
+
+    let serialized = borsh::to_vec(&forward).unwrap();
+    assert_eq!(serialized, borsh::to_vec(&backward).unwrap());
+
+    let mut sorted = entries;
+    sorted.sort();
+    let expected: Vec<u8> = borsh::to_vec(&(sorted.len() as u32))
+        .unwrap()
+        .into_iter()
+        .chain(
+            sorted
+                .iter()
+                .flat_map(|entry| borsh::to_vec(entry).unwrap()),
+        )
+        .collect();
+    assert_eq!(serialized, expected);
+}
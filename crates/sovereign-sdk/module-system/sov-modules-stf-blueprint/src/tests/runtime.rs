@@ -0,0 +1,363 @@
+//! A runtime without modules to drive the blueprint end to end in tests.
+//! Transactions carry a [`TestCall`] and the hooks record what they observe in state,
+//! so tests can check the effects of applying soft confirmations through the blueprint.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sov_mock_da::{MockAddress, MockBlob, MockBlockHeader, MockDaSpec, MockValidityCond};
+use sov_mock_zkvm::MockZkvm;
+use sov_modules_api::default_context::DefaultContext;
+use sov_modules_api::default_signature::private_key::DefaultPrivateKey;
+use sov_modules_api::hooks::{
+    ApplyBlobHooks, ApplySoftConfirmationError, ApplySoftConfirmationHooks, FinalizeHook,
+    HookSoftConfirmationInfo, SlotHooks, TxHooks,
+};
+use sov_modules_api::prelude::StateValueAccessor;
+use sov_modules_api::transaction::Transaction;
+use sov_modules_api::{
+    AccessoryWorkingSet, Address, CallResponse, Context, DispatchCall, Genesis, ModuleError,
+    PrivateKey, PublicKey, Spec, StateValue, UnsignedSoftConfirmationBatch, WorkingSet,
+};
+use sov_rollup_interface::da::{BlobReaderTrait, DaData, SequencerCommitment};
+use sov_rollup_interface::soft_confirmation::SignedSoftConfirmationBatch;
+use sov_rollup_interface::spec::SpecId;
+use sov_state::{Prefix, Storage};
+
+use crate::{
+    sign_soft_confirmation, GenesisParams, Runtime, RuntimeTxHook, SequencerOutcome, StfBlueprint,
+};
+
+pub(crate) type TestContext = DefaultContext;
+pub(crate) type TestZkvm = MockZkvm<MockValidityCond>;
+pub(crate) type TestBlueprint = StfBlueprint<TestContext, MockDaSpec, TestZkvm, TestRuntime>;
+pub(crate) type TestStorage = <TestContext as Spec>::Storage;
+pub(crate) type TestRoot = <TestStorage as Storage>::Root;
+
+/// The DA public key the sequencer sends its commitments with.
+pub(crate) const SEQUENCER_DA_PUBLIC_KEY: [u8; 32] = [9; 32];
+
+/// The L1 fee rate the begin hook rejects soft confirmations with.
+pub(crate) const REJECTED_L1_FEE_RATE: u128 = u128::MAX;
+
+/// Call messages of the [`TestRuntime`].
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub(crate) enum TestCall {
+    /// Sets the value to the given number.
+    Set(u64),
+    /// Fails, reverting the transaction.
+    Fail,
+}
+
+/// Values the [`TestRuntime`] records in state.
+pub(crate) enum Recorded {
+    /// Value last set by a [`TestCall::Set`]
+    Value,
+    /// DA slot height the last transaction was applied at
+    TxDaSlotHeight,
+    /// Number of times the begin soft confirmation hook ran
+    BeginHookCalls,
+    /// Number of times the end soft confirmation hook ran
+    EndHookCalls,
+}
+
+impl Recorded {
+    fn state_value(&self) -> StateValue<u64> {
+        let name: &[u8] = match self {
+            Recorded::Value => b"test_runtime/value",
+            Recorded::TxDaSlotHeight => b"test_runtime/tx_da_slot_height",
+            Recorded::BeginHookCalls => b"test_runtime/begin_hook_calls",
+            Recorded::EndHookCalls => b"test_runtime/end_hook_calls",
+        };
+        StateValue::new(Prefix::new(name.to_vec()))
+    }
+
+    /// Reads the recorded value from the latest state of `storage`.
+    pub(crate) fn read(&self, storage: &TestStorage) -> Option<u64> {
+        self.state_value()
+            .get(&mut WorkingSet::<TestContext>::new(storage.clone()))
+    }
+
+    fn increment(&self, working_set: &mut WorkingSet<TestContext>) {
+        let state_value = self.state_value();
+        let calls = state_value.get(working_set).unwrap_or_default();
+        state_value.set(&(calls + 1), working_set);
+    }
+}
+
+pub(crate) struct TestRuntime {
+    address: Address,
+}
+
+impl Default for TestRuntime {
+    fn default() -> Self {
+        Self {
+            address: Address::from([7; 32]),
+        }
+    }
+}
+
+impl DispatchCall for TestRuntime {
+    type Context = TestContext;
+    type Decodable = TestCall;
+
+    fn decode_call(serialized_message: &[u8]) -> Result<Self::Decodable, std::io::Error> {
+        TestCall::try_from_slice(serialized_message)
+    }
+
+    fn dispatch_call(
+        &self,
+        message: Self::Decodable,
+        working_set: &mut WorkingSet<Self::Context>,
+        _current_spec: SpecId,
+        _context: &Self::Context,
+    ) -> Result<CallResponse, ModuleError> {
+        match message {
+            TestCall::Set(value) => {
+                Recorded::Value.state_value().set(&value, working_set);
+                Ok(CallResponse::default())
+            }
+            TestCall::Fail => Err(ModuleError::ModuleError(anyhow::anyhow!("call failed"))),
+        }
+    }
+
+    fn module_address(&self, _message: &Self::Decodable) -> &Address {
+        &self.address
+    }
+}
+
+impl Genesis for TestRuntime {
+    type Context = TestContext;
+    type Config = ();
+
+    fn genesis(
+        &self,
+        _config: &Self::Config,
+        _working_set: &mut WorkingSet<Self::Context>,
+    ) -> Result<(), ModuleError> {
+        Ok(())
+    }
+}
+
+impl TxHooks for TestRuntime {
+    type Context = TestContext;
+    type PreArg = RuntimeTxHook<TestContext>;
+    type PreResult = TestContext;
+
+    fn pre_dispatch_tx_hook(
+        &self,
+        tx: &Transaction<Self::Context>,
+        working_set: &mut WorkingSet<Self::Context>,
+        arg: &Self::PreArg,
+    ) -> anyhow::Result<Self::PreResult> {
+        Recorded::TxDaSlotHeight
+            .state_value()
+            .set(&arg.da_slot_height, working_set);
+        Ok(TestContext::new(
+            tx.pub_key().to_address(),
+            arg.sequencer.to_address(),
+            arg.height,
+        ))
+    }
+
+    fn post_dispatch_tx_hook(
+        &self,
+        _tx: &Transaction<Self::Context>,
+        _ctx: &Self::Context,
+        _working_set: &mut WorkingSet<Self::Context>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl SlotHooks<MockDaSpec> for TestRuntime {
+    type Context = TestContext;
+
+    fn begin_slot_hook(
+        &self,
+        _slot_header: &MockBlockHeader,
+        _validity_condition: &MockValidityCond,
+        _pre_state_root: &TestRoot,
+        _working_set: &mut WorkingSet<Self::Context>,
+    ) {
+    }
+
+    fn end_slot_hook(&self, _working_set: &mut WorkingSet<Self::Context>) {}
+}
+
+impl FinalizeHook<MockDaSpec> for TestRuntime {
+    type Context = TestContext;
+
+    fn finalize_hook(
+        &self,
+        _root_hash: &TestRoot,
+        _accessory_working_set: &mut AccessoryWorkingSet<Self::Context>,
+    ) {
+    }
+}
+
+impl ApplySoftConfirmationHooks<MockDaSpec> for TestRuntime {
+    type Context = TestContext;
+    type SoftConfirmationResult = SequencerOutcome<MockAddress>;
+
+    fn begin_soft_confirmation_hook(
+        &self,
+        soft_confirmation: &mut HookSoftConfirmationInfo,
+        working_set: &mut WorkingSet<Self::Context>,
+    ) -> Result<(), ApplySoftConfirmationError> {
+        Recorded::BeginHookCalls.increment(working_set);
+        if soft_confirmation.l1_fee_rate() == REJECTED_L1_FEE_RATE {
+            return Err(
+                ApplySoftConfirmationError::L1FeeRateChangeMoreThanAllowedPercentage {
+                    l1_fee_rate: REJECTED_L1_FEE_RATE,
+                    l1_fee_rate_change_percentage: 0,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn end_soft_confirmation_hook(
+        &self,
+        working_set: &mut WorkingSet<Self::Context>,
+    ) -> Result<(), ApplySoftConfirmationError> {
+        Recorded::EndHookCalls.increment(working_set);
+        Ok(())
+    }
+}
+
+impl ApplyBlobHooks<MockBlob> for TestRuntime {
+    type Context = TestContext;
+    type BlobResult = SequencerOutcome<MockAddress>;
+
+    fn begin_blob_hook(
+        &self,
+        _blob: &mut MockBlob,
+        _working_set: &mut WorkingSet<Self::Context>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn end_blob_hook(&self, _working_set: &mut WorkingSet<Self::Context>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl Runtime<TestContext, MockDaSpec> for TestRuntime {
+    type GenesisConfig = ();
+    type GenesisPaths = ();
+
+    fn rpc_methods(_storage: TestStorage) -> jsonrpsee::RpcModule<()> {
+        jsonrpsee::RpcModule::new(())
+    }
+
+    fn genesis_config(_genesis_paths: &Self::GenesisPaths) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Storage in `path` with the genesis of the [`TestRuntime`] applied.
+/// Returns the storage and the genesis state root.
+pub(crate) fn genesis_storage(
+    stf: &TestBlueprint,
+    path: &std::path::Path,
+) -> (TestStorage, TestRoot) {
+    use sov_rollup_interface::stf::StateTransitionFunction;
+
+    let storage = sov_prover_storage_manager::new_orphan_storage(path).unwrap();
+    let (genesis_root, storage) = stf
+        .init_chain(storage, GenesisParams { runtime: () })
+        .unwrap();
+    (storage, genesis_root)
+}
+
+/// The sequencer key every soft confirmation is signed with and its serialized public key.
+pub(crate) fn sequencer_key() -> (DefaultPrivateKey, Vec<u8>) {
+    let private_key = DefaultPrivateKey::from_hex(
+        "1212121212121212121212121212121212121212121212121212121212121212",
+    )
+    .unwrap();
+    let public_key = borsh::to_vec(&private_key.pub_key()).unwrap();
+    (private_key, public_key)
+}
+
+/// A serialized transaction signed by `private_key` calling `call`.
+pub(crate) fn tx(private_key: &DefaultPrivateKey, call: TestCall, nonce: u64) -> Vec<u8> {
+    let message = borsh::to_vec(&call).unwrap();
+    borsh::to_vec(&Transaction::<TestContext>::new_signed_tx(
+        private_key,
+        message,
+        0,
+        nonce,
+    ))
+    .unwrap()
+}
+
+/// A soft confirmation on top of the one with `prev_hash`, built on `da_block_header` and signed
+/// by the [`sequencer_key`].
+pub(crate) fn signed_soft_confirmation(
+    prev_hash: [u8; 32],
+    da_block_header: &MockBlockHeader,
+    txs: Vec<Vec<u8>>,
+    l1_fee_rate: u128,
+    timestamp: u64,
+) -> SignedSoftConfirmationBatch {
+    let (private_key, public_key) = sequencer_key();
+    let unsigned = UnsignedSoftConfirmationBatch::new(
+        da_block_header.height,
+        da_block_header.hash.into(),
+        da_block_header.txs_commitment.into(),
+        txs,
+        vec![],
+        l1_fee_rate,
+        timestamp,
+    );
+    sign_soft_confirmation::<TestContext>(unsigned, prev_hash, &private_key, &public_key)
+}
+
+/// A chain of soft confirmations on top of the one with `prev_hash`, one per DA block header in
+/// `da_block_headers`, each with the transactions `txs_for` returns for its index.
+pub(crate) fn soft_confirmation_chain(
+    prev_hash: [u8; 32],
+    da_block_headers: &[&MockBlockHeader],
+    mut txs_for: impl FnMut(usize) -> Vec<Vec<u8>>,
+) -> Vec<SignedSoftConfirmationBatch> {
+    let mut prev_hash = prev_hash;
+    da_block_headers
+        .iter()
+        .enumerate()
+        .map(|(index, da_block_header)| {
+            let soft_confirmation = signed_soft_confirmation(
+                prev_hash,
+                da_block_header,
+                txs_for(index),
+                1,
+                index as u64,
+            );
+            prev_hash = soft_confirmation.hash();
+            soft_confirmation
+        })
+        .collect()
+}
+
+/// A DA blob sent by `sender` with a commitment to `soft_confirmations` at `l2_start_height`.
+pub(crate) fn commitment_blob(
+    soft_confirmations: &[SignedSoftConfirmationBatch],
+    l2_start_height: u64,
+    sender: [u8; 32],
+) -> MockBlob {
+    let hashes = soft_confirmations
+        .iter()
+        .map(|soft_confirmation| soft_confirmation.hash())
+        .collect::<Vec<_>>();
+    let commitment = SequencerCommitment {
+        merkle_root: crate::compute_commitment_merkle_root(&hashes).unwrap(),
+        l2_start_block_number: l2_start_height,
+        l2_end_block_number: l2_start_height + soft_confirmations.len() as u64 - 1,
+    };
+    let mut blob = MockBlob::new(
+        borsh::to_vec(&DaData::SequencerCommitment(commitment)).unwrap(),
+        MockAddress::new(sender),
+        hashes[0],
+    );
+    blob.full_data();
+    blob
+}
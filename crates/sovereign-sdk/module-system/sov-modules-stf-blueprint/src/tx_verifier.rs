@@ -3,11 +3,13 @@ use std::io::Cursor;
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use sov_modules_api::transaction::Transaction;
-use sov_modules_api::{native_debug, Context, Spec};
+use sov_modules_api::{native_debug, native_error, Context, Spec};
 use sov_rollup_interface::digest::Digest;
 #[cfg(feature = "native")]
 use tracing::instrument;
 
+use crate::SlashingReason;
+
 type RawTxHash = [u8; 32];
 
 pub(crate) struct TransactionAndRawHash<C: Context> {
@@ -31,14 +33,28 @@ impl RawTx {
 #[cfg_attr(feature = "native", instrument(level = "trace", skip_all, err))]
 pub(crate) fn verify_txs_stateless<C: Context>(
     raw_txs: Vec<RawTx>,
-) -> anyhow::Result<Vec<TransactionAndRawHash<C>>> {
+) -> Result<Vec<TransactionAndRawHash<C>>, SlashingReason> {
     let mut txs = Vec::with_capacity(raw_txs.len());
     native_debug!("Verifying {} transactions", raw_txs.len());
     for raw_tx in raw_txs {
         let raw_tx_hash = raw_tx.hash::<C>();
         let mut data = Cursor::new(&raw_tx.data);
-        let tx = Transaction::<C>::deserialize_reader(&mut data)?;
-        tx.verify()?;
+        let tx = Transaction::<C>::deserialize_reader(&mut data).map_err(|e| {
+            native_error!(
+                "Tx 0x{} deserialization error: {}",
+                hex::encode(raw_tx_hash),
+                e
+            );
+            SlashingReason::InvalidTransactionEncoding
+        })?;
+        tx.verify().map_err(|e| {
+            native_error!(
+                "Tx 0x{} verification error: {}",
+                hex::encode(raw_tx_hash),
+                e
+            );
+            SlashingReason::StatelessVerificationFailed
+        })?;
         txs.push(TransactionAndRawHash { tx, raw_tx_hash });
     }
     Ok(txs)
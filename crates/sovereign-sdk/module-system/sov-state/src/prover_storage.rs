@@ -249,4 +249,18 @@ impl<S: MerkleProofSpec, Q: QueryManager> NativeStorage for ProverStorage<S, Q>
             JellyfishMerkleTree::new(&self.db);
         temp_merkle.get_root_hash(version)
     }
+
+    fn latest_version(&self) -> Version {
+        self.db.get_next_version() - 1
+    }
+
+    fn rollback_to_version(&self, version: Version) -> anyhow::Result<()> {
+        // `commit` stores the accessory writes of the update at JMT version `v` at `v - 1`,
+        // the latest version before it increments it, and `get_accessory` reads them there.
+        // Keeping the JMT versions up to `version` therefore keeps the accessory writes up to
+        // `version - 1`, and the ones stored at `version` belong to the first discarded update.
+        self.native_db
+            .rollback_to_version(version.saturating_sub(1))?;
+        self.db.rollback_to_version(version)
+    }
 }
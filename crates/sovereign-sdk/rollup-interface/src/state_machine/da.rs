@@ -9,6 +9,13 @@ use serde::{Deserialize, Serialize};
 use crate::zk::{Proof, ValidityCondition};
 use crate::BasicAddress;
 
+/// Internal buffer size of the brotli compressor and decompressor of DA blobs.
+pub const BLOB_COMPRESSION_BUFFER_SIZE: usize = 4096;
+/// Brotli quality DA blobs are compressed with.
+pub const BLOB_COMPRESSION_QUALITY: u32 = 11;
+/// Base 2 logarithm of the brotli window size DA blobs are compressed with.
+pub const BLOB_COMPRESSION_LG_WINDOW_SIZE: u32 = 22;
+
 /// Commitments made to the DA layer from the sequencer.
 /// Has merkle root of soft confirmation hashes from L1 start block to L1 end block (inclusive)
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, BorshDeserialize, BorshSerialize)]
@@ -21,6 +28,60 @@ pub struct SequencerCommitment {
     pub l2_end_block_number: u64,
 }
 
+impl SequencerCommitment {
+    /// Checks the fields of the commitment that are meaningless on their own:
+    /// the L2 range must not be inverted and the merkle root must not be zero.
+    pub fn validate(&self) -> Result<(), CommitmentError> {
+        if self.l2_start_block_number > self.l2_end_block_number {
+            return Err(CommitmentError::InvertedL2Range {
+                l2_start: self.l2_start_block_number,
+                l2_end: self.l2_end_block_number,
+            });
+        }
+        if self.merkle_root == [0; 32] {
+            return Err(CommitmentError::ZeroMerkleRoot);
+        }
+        Ok(())
+    }
+
+    /// Returns the inclusive L2 range both commitments cover, if any,
+    /// e.g. to find the soft confirmations a competing commitment seen during a DA reorg replaces.
+    pub fn overlaps(&self, other: &Self) -> Option<(u64, u64)> {
+        let start = self.l2_start_block_number.max(other.l2_start_block_number);
+        let end = self.l2_end_block_number.min(other.l2_end_block_number);
+        (start <= end).then_some((start, end))
+    }
+
+    /// Whether both commitments cover the same L2 range but commit to different soft confirmations.
+    /// Commitments over ranges that only partially overlap have different merkle roots regardless,
+    /// telling whether they diverge over the overlap needs the soft confirmation hashes.
+    pub fn diverges_from(&self, other: &Self) -> bool {
+        self.l2_start_block_number == other.l2_start_block_number
+            && self.l2_end_block_number == other.l2_end_block_number
+            && self.merkle_root != other.merkle_root
+    }
+}
+
+/// An error returned when a [`SequencerCommitment`] is malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(thiserror::Error))]
+pub enum CommitmentError {
+    /// The L2 range ends before it starts.
+    #[cfg_attr(
+        feature = "std",
+        error("Sequencer commitment L2 range {l2_start}..={l2_end} ends before it starts")
+    )]
+    InvertedL2Range {
+        /// Start L2 block's number
+        l2_start: u64,
+        /// End L2 block's number
+        l2_end: u64,
+    },
+    /// The merkle root is all zeros.
+    #[cfg_attr(feature = "std", error("Sequencer commitment merkle root is zero"))]
+    ZeroMerkleRoot,
+}
+
 impl core::cmp::PartialOrd for SequencerCommitment {
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
@@ -332,3 +393,71 @@ impl Time {
         self.nanos
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitmentError, SequencerCommitment};
+
+    #[test]
+    fn validate_sequencer_commitment() {
+        let commitment = SequencerCommitment {
+            merkle_root: [1; 32],
+            l2_start_block_number: 5,
+            l2_end_block_number: 5,
+        };
+        assert_eq!(commitment.validate(), Ok(()));
+
+        let inverted = SequencerCommitment {
+            l2_end_block_number: 4,
+            ..commitment.clone()
+        };
+        assert_eq!(
+            inverted.validate(),
+            Err(CommitmentError::InvertedL2Range {
+                l2_start: 5,
+                l2_end: 4,
+            })
+        );
+
+        let zero_root = SequencerCommitment {
+            merkle_root: [0; 32],
+            ..commitment
+        };
+        assert_eq!(zero_root.validate(), Err(CommitmentError::ZeroMerkleRoot));
+    }
+
+    #[test]
+    fn sequencer_commitment_overlaps() {
+        let commitment =
+            |merkle_root: u8, l2_start_block_number, l2_end_block_number| SequencerCommitment {
+                merkle_root: [merkle_root; 32],
+                l2_start_block_number,
+                l2_end_block_number,
+            };
+
+        // disjoint
+        assert_eq!(commitment(1, 1, 5).overlaps(&commitment(1, 8, 10)), None);
+        // adjacent
+        assert_eq!(commitment(1, 1, 5).overlaps(&commitment(1, 6, 10)), None);
+        // overlapping, in either order
+        assert_eq!(
+            commitment(1, 1, 5).overlaps(&commitment(1, 4, 10)),
+            Some((4, 5))
+        );
+        assert_eq!(
+            commitment(1, 4, 10).overlaps(&commitment(1, 1, 5)),
+            Some((4, 5))
+        );
+        // contained
+        assert_eq!(
+            commitment(1, 1, 10).overlaps(&commitment(1, 3, 3)),
+            Some((3, 3))
+        );
+
+        assert!(commitment(1, 1, 5).diverges_from(&commitment(2, 1, 5)));
+        assert!(!commitment(1, 1, 5).diverges_from(&commitment(1, 1, 5)));
+        // the roots of different ranges can't be compared
+        assert!(!commitment(1, 1, 5).diverges_from(&commitment(2, 4, 10)));
+        assert!(!commitment(1, 1, 5).diverges_from(&commitment(2, 6, 10)));
+    }
+}
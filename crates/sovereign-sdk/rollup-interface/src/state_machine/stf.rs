@@ -80,7 +80,7 @@ pub struct BatchReceipt<BatchReceiptContents, TxReceiptContents> {
     /// The receipts of all the transactions in this batch.
     pub tx_receipts: Vec<TransactionReceipt<TxReceiptContents>>,
     /// Any additional structured data to be saved in the database and served over RPC
-    pub phantom_data: PhantomData<BatchReceiptContents>,
+    pub inner: BatchReceiptContents,
 }
 
 /// A receipt for a soft confirmation of transactions. These receipts are stored in the rollup's database
@@ -114,6 +114,29 @@ pub struct SoftConfirmationReceipt<BatchReceiptContents, TxReceiptContents, DS:
     pub timestamp: u64,
 }
 
+/// An error that occurs while initializing the rollup state at genesis.
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(thiserror::Error))]
+pub enum InitChainError {
+    /// The runtime failed to process its genesis configuration.
+    #[cfg_attr(feature = "std", error("Runtime initialization failed: {0}"))]
+    RuntimeGenesis(anyhow::Error),
+    /// The genesis state could not be written to the storage.
+    #[cfg_attr(feature = "std", error("Storage update failed: {0}"))]
+    StorageUpdate(anyhow::Error),
+    /// The computed genesis hash is not the expected one, the genesis configuration is likely wrong.
+    #[cfg_attr(
+        feature = "std",
+        error("Genesis hash {} does not match the expected genesis hash {}", hex::encode(.got), hex::encode(.expected))
+    )]
+    GenesisHashMismatch {
+        /// The expected genesis hash
+        expected: Vec<u8>,
+        /// The computed genesis hash
+        got: Vec<u8>,
+    },
+}
+
 /// A diff of the state, represented as a list of key-value pairs.
 pub type StateDiff = Vec<(Vec<u8>, Option<Vec<u8>>)>;
 
@@ -177,12 +200,35 @@ pub trait StateTransitionFunction<Vm: Zkvm, Da: DaSpec> {
 
     /// Perform one-time initialization for the genesis block and
     /// returns the resulting root hash and changeset.
-    /// If the init chain fails we panic.
+    /// Returns an [`InitChainError`] if the genesis state cannot be built.
     fn init_chain(
         &self,
         genesis_state: Self::PreState,
         params: Self::GenesisParams,
-    ) -> (Self::StateRoot, Self::ChangeSet);
+    ) -> Result<(Self::StateRoot, Self::ChangeSet), InitChainError>;
+
+    /// Same as [`StateTransitionFunction::init_chain`], but if `expected_genesis_hash` is given
+    /// the computed genesis hash must match it, otherwise
+    /// [`InitChainError::GenesisHashMismatch`] is returned.
+    fn init_chain_checked(
+        &self,
+        genesis_state: Self::PreState,
+        params: Self::GenesisParams,
+        expected_genesis_hash: Option<Self::StateRoot>,
+    ) -> Result<(Self::StateRoot, Self::ChangeSet), InitChainError> {
+        let (genesis_hash, change_set) = self.init_chain(genesis_state, params)?;
+
+        if let Some(expected) = expected_genesis_hash {
+            if expected.as_ref() != genesis_hash.as_ref() {
+                return Err(InitChainError::GenesisHashMismatch {
+                    expected: expected.as_ref().to_vec(),
+                    got: genesis_hash.as_ref().to_vec(),
+                });
+            }
+        }
+
+        Ok((genesis_hash, change_set))
+    }
 
     /// Called at each **DA-layer block** - whether or not that block contains any
     /// data relevant to the rollup.
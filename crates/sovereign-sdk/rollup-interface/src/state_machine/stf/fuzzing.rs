@@ -1,7 +1,5 @@
 //! Implements fuzzing strategies for structs in the stf module
 
-use core::marker::PhantomData;
-
 use digest::typenum::U32;
 use digest::Digest;
 use proptest::prelude::{any, Arbitrary};
@@ -181,7 +179,7 @@ impl<B: Arbitrary + 'static, R: Arbitrary + 'static> Arbitrary for BatchReceipt<
                 ),
                 any::<B>(),
             )
-                .prop_map(move |(batch_hash, txs, _receipt)| {
+                .prop_map(move |(batch_hash, txs, receipt)| {
                     let batch_hash = match args.hasher {
                         Some(ref hasher) => {
                             let mut merkle_hasher = FuzzMerkleHasher { hasher };
@@ -194,7 +192,7 @@ impl<B: Arbitrary + 'static, R: Arbitrary + 'static> Arbitrary for BatchReceipt<
                         hash: batch_hash,
                         prev_hash: batch_hash,
                         tx_receipts: txs,
-                        phantom_data: PhantomData,
+                        inner: receipt,
                     }
                 })
                 .boxed()
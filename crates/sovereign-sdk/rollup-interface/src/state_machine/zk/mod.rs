@@ -18,7 +18,7 @@ use digest::Digest;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-use crate::da::DaSpec;
+use crate::da::{BlockHeaderTrait, DaSpec};
 use crate::soft_confirmation::SignedSoftConfirmationBatch;
 
 /// The ZK proof generated by the [`ZkvmHost::run`] method.
@@ -111,9 +111,14 @@ pub trait ValidityCondition:
     /// Combine two conditions into one (typically run inside a recursive proof).
     /// Returns an error if the two conditions cannot be combined
     fn combine<H: Digest>(&self, rhs: Self) -> Result<Self, Self::Error>;
+    /// Check that the condition holds for the given DA block header.
+    /// Returns an error if the condition was not produced for this block.
+    fn check<B: BlockHeaderTrait>(&self, block_header: &B) -> Result<(), Self::Error>;
 }
 
-/// State diff produced by the Zk proof
+/// State diff produced by the Zk proof.
+/// Being a `BTreeMap`, it serializes sorted by key whatever order the entries were added in,
+/// so the same diff always has the same DA payload.
 pub type CumulativeStateDiff = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
 
 /// The public output of a SNARK proof in Sovereign, this struct makes a claim that